@@ -0,0 +1,80 @@
+use crate::camera::Camera;
+use crate::image::Image;
+use crate::intersection::IntersectionPool;
+use crate::world::World;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+// frames render concurrently, each on top of the same shared world and intersection buffers
+pub fn render_frames<I>(frames: I, world: &World) -> Vec<Image>
+where
+    I: Iterator<Item = Camera>,
+{
+    let cameras: Vec<Camera> = frames.collect();
+    let pool = IntersectionPool::new();
+
+    cameras
+        .par_iter()
+        .map(|camera| Image::par_render_pooled(camera, world, &pool))
+        .collect()
+}
+
+// writes frame_0000.png, frame_0001.png, ... and returns their paths in order,
+// each frame is dropped as soon as it is written
+pub fn render_sequence<I, P>(frames: I, world: &World, out_dir: P) -> io::Result<Vec<PathBuf>>
+where
+    I: Iterator<Item = Camera>,
+    P: AsRef<Path>,
+{
+    let out_dir = out_dir.as_ref();
+    fs::create_dir_all(out_dir)?;
+
+    let cameras: Vec<Camera> = frames.collect();
+    let pool = IntersectionPool::new();
+
+    cameras
+        .par_iter()
+        .enumerate()
+        .map(|(i, camera)| {
+            let image = Image::par_render_pooled(camera, world, &pool);
+            let path = out_dir.join(format!("frame_{:04}.png", i));
+            image.png(&path)?;
+
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::approx::Approx;
+    use crate::linalg::Vector;
+
+    use std::f64::consts::PI;
+
+    #[test]
+    fn frames_match_single_renders() {
+        let world = World::default();
+        let center = Vector::point(0.0, 0.0, 0.0);
+        let camera = |i: usize| {
+            let angle = i as f64 * PI / 4.0;
+            Camera::new(11, 7, PI / 3.0, Camera::orbit(center, 5.0, 1.0, angle))
+        };
+
+        let frames = render_frames((0..4).map(camera), &world);
+
+        assert!(
+            frames.len() == 4
+                && frames.iter().enumerate().all(|(i, frame)| {
+                    let single = Image::par_render(&camera(i), &world);
+                    (0..7).all(|y| (0..11).all(|x| frame.read(x, y).approx(&single.read(x, y))))
+                })
+        )
+    }
+}
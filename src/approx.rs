@@ -10,6 +10,12 @@ impl Approx<bool> for bool {
     }
 }
 
+impl Approx<u32> for u32 {
+    fn approx(&self, other: &u32) -> bool {
+        self == other
+    }
+}
+
 impl Approx<usize> for usize {
     fn approx(&self, other: &usize) -> bool {
         self == other
@@ -22,6 +28,47 @@ impl Approx<f64> for f64 {
     }
 }
 
+impl<T> Approx<Option<T>> for Option<T>
+where
+    T: Approx<T>,
+{
+    fn approx(&self, other: &Option<T>) -> bool {
+        match (self, other) {
+            (Some(x), Some(y)) => x.approx(y),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<A, B> Approx<(A, B)> for (A, B)
+where
+    A: Approx<A>,
+    B: Approx<B>,
+{
+    fn approx(&self, other: &(A, B)) -> bool {
+        self.0.approx(&other.0) && self.1.approx(&other.1)
+    }
+}
+
+impl<T, const N: usize> Approx<[T; N]> for [T; N]
+where
+    T: Approx<T>,
+{
+    fn approx(&self, other: &[T; N]) -> bool {
+        self.iter().zip(other.iter()).all(|(x, y)| x.approx(y))
+    }
+}
+
+impl<T> Approx<Box<T>> for Box<T>
+where
+    T: Approx<T>,
+{
+    fn approx(&self, other: &Box<T>) -> bool {
+        self.as_ref().approx(other.as_ref())
+    }
+}
+
 impl<T> Approx<Vec<T>> for Vec<T>
 where
     T: Approx<T>,
@@ -0,0 +1,157 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::image::Image;
+use crate::light::{Light, PointLight};
+use crate::linalg::{Matrix, Vector};
+use crate::material::{Material, Pattern};
+use crate::shape::{Element, GroupKind, ShapeArgs};
+use crate::world::World;
+
+use std::f64::consts::PI;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Scene {
+    DefaultWorld,
+    SphereBlocks,
+}
+
+pub fn scene(scene: Scene, (hsize, vsize): (usize, usize)) -> (Camera, World) {
+    match scene {
+        Scene::DefaultWorld => {
+            let camera = Camera::new(
+                hsize,
+                vsize,
+                PI / 2.0,
+                Camera::transform(
+                    Vector::point(0.0, 0.0, -5.0),
+                    Vector::point(0.0, 0.0, 0.0),
+                    Vector::vector(0.0, 1.0, 0.0),
+                ),
+            );
+
+            (camera, World::default())
+        }
+        Scene::SphereBlocks => sphere_blocks(hsize, vsize),
+    }
+}
+
+pub fn render_scene(scene_id: Scene, resolution: (usize, usize)) -> (Duration, Image) {
+    let (camera, world) = scene(scene_id, resolution);
+
+    let now = Instant::now();
+    let image = Image::par_render(&camera, &world);
+
+    (now.elapsed(), image)
+}
+
+fn sphere_block(n: usize, h: f64) -> Vec<Element> {
+    let mut elements = vec![];
+
+    for x in 0..n {
+        for z in 0..n {
+            for y in 0..n {
+                let sphere = Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(
+                        0.1 + x as f64 * h,
+                        0.1 + y as f64 * h,
+                        0.1 + z as f64 * h,
+                    ) * Matrix::scaling(0.1, 0.1, 0.1),
+                    material: Material {
+                        pattern: Pattern::plain(Color::black()),
+                        ..Material::default()
+                    },
+                    ..ShapeArgs::default()
+                });
+
+                elements.push(sphere);
+            }
+        }
+    }
+
+    elements
+}
+
+fn sphere_blocks(hsize: usize, vsize: usize) -> (Camera, World) {
+    let backdrop = Element::plane(ShapeArgs {
+        material: Material {
+            pattern: Pattern::plain(Color::white()),
+            specular: 0.0,
+            ..Material::default()
+        },
+        ..ShapeArgs::default()
+    });
+
+    let n = 5;
+    let h = 0.3;
+
+    let mut spheres = vec![];
+    let offset = (n - 1) as f64 * (0.1 + h) - 0.1;
+
+    for x in 0..2 {
+        for z in 0..2 {
+            for y in 0..2 {
+                let group = Element::composite(
+                    Matrix::translation(x as f64 * offset, y as f64 * offset, z as f64 * offset),
+                    None,
+                    GroupKind::Aggregation,
+                    sphere_block(n, h),
+                );
+                spheres.push(group);
+            }
+        }
+    }
+
+    let mut elements = vec![backdrop];
+    elements.push(Element::composite(
+        Matrix::id(),
+        Some(Material {
+            pattern: Pattern::gradient(
+                Matrix::scaling(1.0, 2.9, 1.0) * Matrix::rotation_z(PI / 2.0),
+                Pattern::plain(Color::new(1.0, 0.0, 0.0)),
+                Pattern::plain(Color::new(0.0, 0.0, 1.0)),
+            ),
+            ..Material::default()
+        }),
+        GroupKind::Aggregation,
+        spheres,
+    ));
+
+    let world = World {
+        lights: vec![Light::Point(PointLight {
+            intensity: Color::white(),
+            origin: Vector::point(-5.0, 7.0, -1.0),
+        })],
+        elements,
+        ..World::default()
+    };
+
+    let camera = Camera::new(
+        hsize,
+        vsize,
+        1.0,
+        Camera::transform(
+            Vector::point(-8.0, 8.0, -8.0),
+            Vector::point(2.0, 2.0, 2.0),
+            Vector::vector(0.0, 1.0, 0.0),
+        ),
+    );
+
+    (camera, world)
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_unit)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case(Scene::DefaultWorld ; "default world")]
+    #[test_case(Scene::SphereBlocks ; "sphere blocks")]
+    fn render_scene(scene: Scene) {
+        let (duration, image) = super::render_scene(scene, (16, 9));
+
+        assert!(duration > Duration::from_secs(0) && image.hsize() == 16 && image.vsize() == 9)
+    }
+}
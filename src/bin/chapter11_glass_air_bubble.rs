@@ -1,7 +1,7 @@
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::shape::{Element, ShapeArgs};
@@ -9,7 +9,6 @@ use raytracer::world::World;
 
 use std::f64::consts::PI;
 use std::fs;
-use std::process::Command;
 use std::time::Instant;
 
 fn construct_world() -> (Camera, World) {
@@ -55,11 +54,12 @@ fn construct_world() -> (Camera, World) {
     });
 
     let world = World {
-        lights: vec![PointLight {
+        lights: vec![Light::Point(PointLight {
             intensity: Color::new(0.7, 0.7, 0.7),
             origin: Vector::point(20.0, 10.0, 0.0),
-        }],
+        })],
         elements: vec![floor, glass, air],
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -78,7 +78,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/chapter11_glass_air_bubble";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -99,11 +98,6 @@ fn main() {
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
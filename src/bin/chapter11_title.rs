@@ -1,7 +1,7 @@
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::shape::{Element, ShapeArgs};
@@ -9,7 +9,6 @@ use raytracer::world::World;
 
 use std::f64::consts::PI;
 use std::fs;
-use std::process::Command;
 use std::time::Instant;
 
 fn construct_world() -> (Camera, World) {
@@ -164,10 +163,10 @@ fn construct_world() -> (Camera, World) {
     });
 
     let world = World {
-        lights: vec![PointLight {
+        lights: vec![Light::Point(PointLight {
             intensity: Color::white(),
             origin: Vector::point(-4.9, 4.9, -1.0),
-        }],
+        })],
         elements: vec![
             floor,
             ceiling,
@@ -183,6 +182,7 @@ fn construct_world() -> (Camera, World) {
             foreground2,
             foreground3,
         ],
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -201,7 +201,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/chapter11_title";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -222,11 +221,6 @@ fn main() {
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
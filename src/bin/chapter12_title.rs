@@ -1,14 +1,13 @@
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::shape::{Element, ShapeArgs};
 use raytracer::world::World;
 
 use std::fs;
-use std::process::Command;
 use std::time::Instant;
 
 fn construct_world() -> (Camera, World) {
@@ -240,10 +239,10 @@ fn construct_world() -> (Camera, World) {
     });
 
     let world = World {
-        lights: vec![PointLight {
+        lights: vec![Light::Point(PointLight {
             intensity: Color::new(1.0, 1.0, 0.9),
             origin: Vector::point(0.0, 6.9, -5.0),
-        }],
+        })],
         elements: vec![
             floor_ceiling,
             walls,
@@ -264,6 +263,7 @@ fn construct_world() -> (Camera, World) {
             mirror_frame,
             mirror,
         ],
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -282,7 +282,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/chapter12_title";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -297,17 +296,14 @@ fn main() {
     // Render image
     let now = Instant::now();
     print!("Rendering image ...");
-    let image = Image::par_render(&camera, &world);
+    let mut image = Image::par_render(&camera, &world);
+    // keeps the highlights in the glass cube from clipping to white
+    image.tone_map_reinhard();
     println!(" {} ms.", now.elapsed().as_millis());
 
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
@@ -1,14 +1,13 @@
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::shape::{Element, ShapeArgs};
 use raytracer::world::World;
 
 use std::fs;
-use std::process::Command;
 use std::time::Instant;
 
 fn construct_world() -> (Camera, World) {
@@ -219,10 +218,10 @@ fn construct_world() -> (Camera, World) {
     );
 
     let world = World {
-        lights: vec![PointLight {
+        lights: vec![Light::Point(PointLight {
             intensity: Color::white(),
             origin: Vector::point(1.0, 6.9, -4.9),
-        }],
+        })],
         elements: vec![
             floor,
             cylinder,
@@ -236,6 +235,7 @@ fn construct_world() -> (Camera, World) {
             deco4,
             glass_cylinder,
         ],
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -254,7 +254,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/chapter13_title";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -275,11 +274,6 @@ fn main() {
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
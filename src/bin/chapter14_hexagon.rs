@@ -1,7 +1,7 @@
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::noise::Noise;
@@ -10,7 +10,6 @@ use raytracer::world::World;
 
 use std::f64::consts::PI;
 use std::fs;
-use std::process::Command;
 use std::time::Instant;
 
 fn hexagon_corner() -> Element {
@@ -67,11 +66,12 @@ fn hexagon() -> Element {
 
 fn construct_world() -> (Camera, World) {
     let world = World {
-        lights: vec![PointLight {
+        lights: vec![Light::Point(PointLight {
             intensity: Color::white(),
             origin: Vector::point(1.0, 6.9, -4.9),
-        }],
+        })],
         elements: vec![hexagon()],
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -90,7 +90,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/chapter14_hexagon";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -111,11 +110,6 @@ fn main() {
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
@@ -1,7 +1,9 @@
+#![allow(clippy::approx_constant, clippy::vec_init_then_push)]
+
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::shape::{Element, GroupKind, ShapeArgs};
@@ -9,7 +11,6 @@ use raytracer::world::World;
 
 use std::f64::consts::PI;
 use std::fs;
-use std::process::Command;
 use std::time::Instant;
 
 fn leg(transform: Matrix) -> Element {
@@ -131,24 +132,25 @@ fn construct_world() -> (Camera, World) {
 
     let world = World {
         lights: vec![
-            PointLight {
+            Light::Point(PointLight {
                 intensity: Color::new(0.25, 0.25, 0.25),
                 origin: Vector::point(10000.0, 10000.0, -10000.0),
-            },
-            PointLight {
+            }),
+            Light::Point(PointLight {
                 intensity: Color::new(0.25, 0.25, 0.25),
                 origin: Vector::point(-10000.0, 10000.0, -10000.0),
-            },
-            PointLight {
+            }),
+            Light::Point(PointLight {
                 intensity: Color::new(0.25, 0.25, 0.25),
                 origin: Vector::point(10000.0, -10000.0, -10000.0),
-            },
-            PointLight {
+            }),
+            Light::Point(PointLight {
                 intensity: Color::new(0.25, 0.25, 0.25),
                 origin: Vector::point(-10000.0, -10000.0, -10000.0),
-            },
+            }),
         ],
         elements,
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -167,7 +169,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/chapter14_title";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -188,11 +189,6 @@ fn main() {
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
@@ -1,7 +1,7 @@
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::obj::ObjParser;
@@ -10,13 +10,13 @@ use raytracer::world::World;
 
 use std::f64::consts::PI;
 use std::fs;
-use std::process::Command;
+use std::io::{self, Write};
 use std::time::Instant;
 
 fn construct_world() -> (Camera, World) {
     let obj_parser = ObjParser::new("obj/teapot_high.obj");
 
-    let (_ignored, teapot) = obj_parser
+    let (_ignored, mut teapot) = obj_parser
         .parse_obj(
             Matrix::rotation_x(-PI / 2.0),
             Material {
@@ -30,6 +30,7 @@ fn construct_world() -> (Camera, World) {
             },
         )
         .unwrap();
+    teapot.subdivide(8);
 
     let material = Material {
         pattern: Pattern::plain(Color::black()),
@@ -65,16 +66,17 @@ fn construct_world() -> (Camera, World) {
 
     let world = World {
         lights: vec![
-            PointLight {
+            Light::Point(PointLight {
                 intensity: Color::new(0.7, 0.7, 0.7),
                 origin: Vector::point(-100.0, 100.0, -100.0),
-            },
-            PointLight {
+            }),
+            Light::Point(PointLight {
                 intensity: Color::new(0.7, 0.7, 0.7),
                 origin: Vector::point(100.0, 100.0, -100.0),
-            },
+            }),
         ],
         elements,
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -93,7 +95,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/chapter15_teapot";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -107,18 +108,15 @@ fn main() {
 
     // Render image
     let now = Instant::now();
-    print!("Rendering image ...");
-    let image = Image::par_render(&camera, &world);
+    let image = Image::par_render_tiled(&camera, &world, 32, |done, total| {
+        print!("\rRendering image ... {:>3}%", 100 * done / total);
+        let _ = io::stdout().flush();
+    });
     println!(" {} ms.", now.elapsed().as_millis());
 
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
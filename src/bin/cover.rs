@@ -1,7 +1,7 @@
 use raytracer::camera::Camera;
 use raytracer::color::Color;
 use raytracer::image::Image;
-use raytracer::light::PointLight;
+use raytracer::light::{Light, PointLight};
 use raytracer::linalg::{Matrix, Vector};
 use raytracer::material::{Material, Pattern};
 use raytracer::shape::{Element, ShapeArgs};
@@ -9,7 +9,6 @@ use raytracer::world::World;
 
 use std::f64::consts::PI;
 use std::fs;
-use std::process::Command;
 use std::time::Instant;
 
 fn construct_world() -> (Camera, World) {
@@ -24,17 +23,17 @@ fn construct_world() -> (Camera, World) {
 
     let material_blue = Material {
         pattern: Pattern::plain(Color::new(0.537, 0.831, 0.914)),
-        ..material_white
+        ..material_white.clone()
     };
 
     let material_red = Material {
         pattern: Pattern::plain(Color::new(0.941, 0.322, 0.388)),
-        ..material_white
+        ..material_white.clone()
     };
 
     let material_purple = Material {
         pattern: Pattern::plain(Color::new(0.373, 0.404, 0.550)),
-        ..material_white
+        ..material_white.clone()
     };
 
     let standard_transform = Matrix::scaling(0.5, 0.5, 0.5) * Matrix::translation(1.0, -1.0, 1.0);
@@ -197,16 +196,17 @@ fn construct_world() -> (Camera, World) {
 
     let world = World {
         lights: vec![
-            PointLight {
+            Light::Point(PointLight {
                 intensity: Color::white(),
                 origin: Vector::point(50.0, 100.0, -50.0),
-            },
-            PointLight {
+            }),
+            Light::Point(PointLight {
                 intensity: Color::new(0.2, 0.2, 0.2),
                 origin: Vector::point(-400.0, 50.0, -10.0),
-            },
+            }),
         ],
         elements: vec![backdrop, group_all],
+        ..World::default()
     };
 
     let camera = Camera::new(
@@ -225,7 +225,6 @@ fn construct_world() -> (Camera, World) {
 
 fn main() {
     let path = "./image/cover";
-    let ppm = &format!("{}.ppm", path);
     let png = &format!("{}.png", path);
 
     // Build image
@@ -246,11 +245,6 @@ fn main() {
     // Write image to disk
     let now = Instant::now();
     print!("Writing image to disk ...");
-    let _ = fs::write(ppm, image.ppm());
-    let _ = Command::new("zsh")
-        .arg("-c")
-        .arg(format!("pnmtopng {} > {}", ppm, png))
-        .output();
-    let _ = fs::remove_file(ppm);
+    let _ = image.png(png);
     println!(" {} ms.", now.elapsed().as_millis());
 }
@@ -3,6 +3,29 @@ use crate::linalg::{Matrix, Vector};
 use crate::ray::Ray;
 use crate::shape::Geometry;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    NegX,
+    PosX,
+    NegY,
+    PosY,
+    NegZ,
+    PosZ,
+}
+
+impl Face {
+    pub fn normal(&self) -> Vector {
+        match self {
+            Face::NegX => Vector::vector(-1.0, 0.0, 0.0),
+            Face::PosX => Vector::vector(1.0, 0.0, 0.0),
+            Face::NegY => Vector::vector(0.0, -1.0, 0.0),
+            Face::PosY => Vector::vector(0.0, 1.0, 0.0),
+            Face::NegZ => Vector::vector(0.0, 0.0, -1.0),
+            Face::PosZ => Vector::vector(0.0, 0.0, 1.0),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct BoundingBox {
     pub min: Vector,
@@ -46,6 +69,42 @@ impl BoundingBox {
         self.insert(other.min).insert(other.max)
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        !self.is_empty()
+            && [
+                self.min.x, self.min.y, self.min.z, self.max.x, self.max.y, self.max.z,
+            ]
+            .iter()
+            .all(|v| v.is_finite())
+    }
+
+    pub fn center(&self) -> Vector {
+        Vector::point(
+            (self.min.x + self.max.x) / 2.0,
+            (self.min.y + self.max.y) / 2.0,
+            (self.min.z + self.max.z) / 2.0,
+        )
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let (dx, dy, dz) = (
+            self.max.x - self.min.x,
+            self.max.y - self.min.y,
+            self.max.z - self.min.z,
+        );
+        if dx >= dy && dx >= dz {
+            0
+        } else if dy >= dz {
+            1
+        } else {
+            2
+        }
+    }
+
     pub fn contains(&self, point: Vector) -> bool {
         self.min.x <= point.x
             && point.x <= self.max.x
@@ -70,7 +129,7 @@ impl BoundingBox {
         let p8 = self.max;
 
         let mut bbox = BoundingBox::empty();
-        for p in vec![p1, p2, p3, p4, p5, p6, p7, p8] {
+        for p in [p1, p2, p3, p4, p5, p6, p7, p8] {
             bbox = bbox.insert(matrix * p);
         }
 
@@ -78,6 +137,10 @@ impl BoundingBox {
     }
 
     pub fn intersects(&self, ray: Ray) -> bool {
+        self.intersects_t(ray).is_some()
+    }
+
+    pub fn intersects_t(&self, ray: Ray) -> Option<(f64, f64)> {
         let (x_t_min, x_t_max) =
             Geometry::intersect_cube_axis(ray.origin.x, ray.direction.x, self.min.x, self.max.x);
         let (y_t_min, y_t_max) =
@@ -88,11 +151,47 @@ impl BoundingBox {
         let t_min = x_t_min.max(y_t_min).max(z_t_min);
         let t_max = x_t_max.min(y_t_max).min(z_t_max);
 
-        t_min <= t_max
+        if t_min <= t_max {
+            Some((t_min, t_max))
+        } else {
+            None
+        }
+    }
+
+    // the entry face lies on the axis whose slab is entered last
+    pub fn intersect_face(&self, ray: Ray) -> Option<(f64, Face)> {
+        let (t_min, _) = self.intersects_t(ray)?;
+
+        let entry = |origin: f64, direction: f64, min: f64, max: f64| {
+            Geometry::intersect_cube_axis(origin, direction, min, max).0
+        };
+        let x = entry(ray.origin.x, ray.direction.x, self.min.x, self.max.x);
+        let y = entry(ray.origin.y, ray.direction.y, self.min.y, self.max.y);
+
+        let face = if x == t_min {
+            if ray.direction.x > 0.0 {
+                Face::NegX
+            } else {
+                Face::PosX
+            }
+        } else if y == t_min {
+            if ray.direction.y > 0.0 {
+                Face::NegY
+            } else {
+                Face::PosY
+            }
+        } else if ray.direction.z > 0.0 {
+            Face::NegZ
+        } else {
+            Face::PosZ
+        };
+
+        Some((t_min, face))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit, clippy::approx_constant)]
 mod tests {
     use super::*;
 
@@ -130,6 +229,13 @@ mod tests {
         assert!(bbox3.approx(&expected))
     }
 
+    #[test_case(BoundingBox::empty(), true ; "empty")]
+    #[test_case(BoundingBox::empty().insert(Vector::point(1.0, 2.0, 3.0)), false ; "single point")]
+    #[test_case(BoundingBox::new(Vector::point(0.0, 1.0, 0.0), Vector::point(1.0, 0.0, 1.0)), true ; "inverted")]
+    fn is_empty(bbox: BoundingBox, expected: bool) {
+        assert_eq!(bbox.is_empty(), expected)
+    }
+
     #[test_case(Vector::point( 5.0, -2.0,  0.0), true  ; "example 1")]
     #[test_case(Vector::point(11.0,  4.0,  7.0), true  ; "example 2")]
     #[test_case(Vector::point( 8.0,  1.0,  3.0), true  ; "example 3")]
@@ -194,6 +300,8 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let intersects = bbox.intersects(ray);
 
@@ -218,9 +326,51 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let intersects = bbox.intersects(ray);
 
         assert_eq!(intersects, expected)
     }
+
+    #[test]
+    fn intersects_t() {
+        let bbox = BoundingBox::new(Vector::point(5.0, -2.0, 0.0), Vector::point(11.0, 4.0, 7.0));
+        let ray = Ray {
+            origin: Vector::point(0.0, 1.0, 2.0),
+            direction: Vector::vector(1.0, 0.0, 0.0),
+            differentials: None,
+            time: 0.0,
+        };
+        let (t_min, t_max) = bbox.intersects_t(ray).unwrap();
+
+        assert!(t_min.approx(&5.0) && t_max.approx(&11.0))
+    }
+
+    #[test_case(Vector::point(0.0, 0.0, -5.0), Vector::vector( 0.0,  0.0,  1.0), Some((4.0, Face::NegZ)) ; "near z face")]
+    #[test_case(Vector::point(0.0, 0.0,  5.0), Vector::vector( 0.0,  0.0, -1.0), Some((4.0, Face::PosZ)) ; "far z face")]
+    #[test_case(Vector::point(5.0, 0.5,  0.0), Vector::vector(-1.0,  0.0,  0.0), Some((4.0, Face::PosX)) ; "far x face")]
+    #[test_case(Vector::point(0.0, 3.0, -2.5), Vector::vector( 0.0, -1.0,  1.0), Some((2.0, Face::PosY)) ; "diagonal")]
+    #[test_case(Vector::point(0.0, 5.0, -5.0), Vector::vector( 0.0,  0.0,  1.0), None                    ; "miss")]
+    fn intersect_face(origin: Vector, direction: Vector, expected: Option<(f64, Face)>) {
+        let bbox = BoundingBox::new(
+            Vector::point(-1.0, -1.0, -1.0),
+            Vector::point(1.0, 1.0, 1.0),
+        );
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
+
+        let face = bbox.intersect_face(ray);
+
+        assert!(match (face, expected) {
+            (Some((t, face)), Some((et, eface))) => t.approx(&et) && face == eface,
+            (None, None) => true,
+            _ => false,
+        })
+    }
 }
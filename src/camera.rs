@@ -1,11 +1,26 @@
+use crate::config::EPSILON;
 use crate::linalg::{Matrix, Vector};
-use crate::ray::Ray;
+use crate::noise::Noise;
+use crate::ray::{Ray, RayDifferentials};
+
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub hsize: usize,
     pub vsize: usize,
     pub field_of_view: f64,
+    pub aperture: f64,
+    pub focal_distance: f64,
+    pub projection: Projection,
+    // only set through `with_lens`, which keeps it at one or more
+    samples: usize,
     transform_inv: Matrix,
     pixel_size: f64,
     half_width: f64,
@@ -15,6 +30,46 @@ pub struct Camera {
 impl Camera {
     pub fn new(hsize: usize, vsize: usize, field_of_view: f64, transform: Matrix) -> Camera {
         let half_view = (field_of_view / 2.0).tan();
+
+        Camera {
+            field_of_view,
+            ..Camera::with_half_view(hsize, vsize, half_view, transform)
+        }
+    }
+
+    // the sensor spans the larger side of the image, as the field of view does
+    pub fn from_focal_length(
+        hsize: usize,
+        vsize: usize,
+        focal_mm: f64,
+        sensor_mm: f64,
+        transform: Matrix,
+    ) -> Camera {
+        Camera::new(
+            hsize,
+            vsize,
+            Camera::focal_to_fov(focal_mm, sensor_mm),
+            transform,
+        )
+    }
+
+    pub fn focal_to_fov(focal_mm: f64, sensor_mm: f64) -> f64 {
+        2.0 * (sensor_mm / (2.0 * focal_mm)).atan()
+    }
+
+    pub fn fov_to_focal(field_of_view: f64, sensor_mm: f64) -> f64 {
+        sensor_mm / (2.0 * (field_of_view / 2.0).tan())
+    }
+
+    // parallel rays, the view plane spans world_width units across the larger side
+    pub fn orthographic(hsize: usize, vsize: usize, world_width: f64, transform: Matrix) -> Camera {
+        Camera {
+            projection: Projection::Orthographic,
+            ..Camera::with_half_view(hsize, vsize, world_width / 2.0, transform)
+        }
+    }
+
+    fn with_half_view(hsize: usize, vsize: usize, half_view: f64, transform: Matrix) -> Camera {
         let aspect = hsize as f64 / vsize as f64;
 
         let (half_width, half_height) = if aspect >= 1.0 {
@@ -28,7 +83,11 @@ impl Camera {
         Camera {
             hsize,
             vsize,
-            field_of_view,
+            field_of_view: 0.0,
+            aperture: 0.0,
+            focal_distance: 1.0,
+            samples: 1,
+            projection: Projection::Perspective,
             transform_inv: transform.inverse(),
             pixel_size,
             half_width,
@@ -36,50 +95,126 @@ impl Camera {
         }
     }
 
+    pub fn with_lens(self, aperture: f64, focal_distance: f64, samples: usize) -> Camera {
+        assert!(samples >= 1);
+
+        Camera {
+            aperture,
+            focal_distance,
+            samples,
+            ..self
+        }
+    }
+
+    pub fn samples(self) -> usize {
+        self.samples
+    }
+
+    fn primary(self, world_x: f64, world_y: f64, sample: usize) -> (Vector, Vector) {
+        match self.projection {
+            Projection::Perspective => {
+                let (lens_x, lens_y) = self.lens_offset(sample);
+                let origin = self.transform_inv * Vector::point(lens_x, lens_y, 0.0);
+
+                (origin, self.direction_at(origin, world_x, world_y))
+            }
+            Projection::Orthographic => (
+                self.transform_inv * Vector::point(world_x, world_y, 0.0),
+                (self.transform_inv * Vector::vector(0.0, 0.0, -1.0)).normalize(),
+            ),
+        }
+    }
+
+    fn direction_at(self, origin: Vector, world_x: f64, world_y: f64) -> Vector {
+        // thin lens: every ray through the pixel meets at the plane of focus
+        let distance = self.focal_distance;
+        let focus =
+            self.transform_inv * Vector::point(world_x * distance, world_y * distance, -distance);
+        (focus - origin).normalize()
+    }
+
+    fn lens_offset(self, sample: usize) -> (f64, f64) {
+        if self.aperture == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        // samples spread evenly over the lens disk along a golden angle spiral
+        let radius = self.aperture / 2.0 * ((sample as f64 + 0.5) / self.samples as f64).sqrt();
+        let angle = sample as f64 * PI * (3.0 - 5.0_f64.sqrt());
+
+        (radius * angle.cos(), radius * angle.sin())
+    }
+
     pub fn ray_at_pixel(self, x: usize, y: usize) -> Ray {
-        let xoffset = (x as f64 + 0.5) * self.pixel_size;
-        let yoffset = (y as f64 + 0.5) * self.pixel_size;
+        self.ray_at_subpixel(x, y, 0.5, 0.5)
+    }
+
+    pub fn ray_at_lens(self, x: usize, y: usize, sample: usize) -> Ray {
+        self.ray_through(x, y, 0.5, 0.5, sample)
+    }
+
+    pub fn ray_at_subpixel(self, x: usize, y: usize, ox: f64, oy: f64) -> Ray {
+        self.ray_through(x, y, ox, oy, 0)
+    }
+
+    pub fn ray_through(self, x: usize, y: usize, ox: f64, oy: f64, sample: usize) -> Ray {
+        let xoffset = (x as f64 + ox) * self.pixel_size;
+        let yoffset = (y as f64 + oy) * self.pixel_size;
 
         let world_x = self.half_width - xoffset;
         let world_y = self.half_height - yoffset;
 
-        let pixel = self.transform_inv * Vector::point(world_x, world_y, -1.0);
-        let origin = Vector::point(
-            self.transform_inv[0][3],
-            self.transform_inv[1][3],
-            self.transform_inv[2][3],
-        ); // self.transform_inv * Vector::point(0.0, 0.0, 0.0)
-        let direction = (pixel - origin).normalize();
+        let (origin, direction) = self.primary(world_x, world_y, sample);
+        let (origin_x, direction_x) = self.primary(world_x - self.pixel_size, world_y, sample);
+        let (origin_y, direction_y) = self.primary(world_x, world_y - self.pixel_size, sample);
+
+        let differentials = RayDifferentials {
+            origin_dx: origin_x - origin,
+            origin_dy: origin_y - origin,
+            dx: direction_x - direction,
+            dy: direction_y - direction,
+        };
 
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            differentials: Some(differentials),
+            time: 0.0,
+        }
     }
 
-    pub fn transform(from: Vector, to: Vector, up: Vector) -> Matrix {
-        let forward = (to - from).normalize();
+    pub fn jitter(noise: Noise, x: usize, y: usize, sample: usize) -> (f64, f64) {
+        // simplex noise vanishes on the integer lattice, seed off-lattice
+        let (sx, sy, sz) = (
+            x as f64 + 0.31,
+            y as f64 + 0.67,
+            sample as f64 * 0.71 + 0.13,
+        );
+        let (jx, jy, _) = noise.jitter_3d(sx, sy, sz);
+
+        let offset = |n: f64| (0.5 + n / 2.0).clamp(0.0, 1.0 - EPSILON);
+        (offset(jx - sx), offset(jy - sy))
+    }
 
-        let up = up.normalize();
-        let left = forward.cross(up);
-        let up = left.cross(forward);
+    pub fn transform(from: Vector, to: Vector, up: Vector) -> Matrix {
+        Matrix::view(from, to, up)
+    }
 
-        #[rustfmt::skip]
-        let orientation = Matrix::new([
-            [     left.x,     left.y,     left.z, 0.0 ],
-            [       up.x,       up.y,       up.z, 0.0 ],
-            [ -forward.x, -forward.y, -forward.z, 0.0 ],
-            [        0.0,        0.0,        0.0, 1.0 ],
-        ]);
+    // looks at `center` from a circle around it, an angle of 0 starts out on the -z side
+    pub fn orbit(center: Vector, radius: f64, height: f64, angle: f64) -> Matrix {
+        let from = center + Vector::vector(radius * angle.sin(), height, -radius * angle.cos());
 
-        orientation * Matrix::translation(-from.x, -from.y, -from.z)
+        Camera::transform(from, center, Vector::vector(0.0, 1.0, 0.0))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
     use crate::approx::Approx;
 
-    use std::f64::consts::PI;
     use test_case::test_case;
 
     #[test_case(
@@ -121,6 +256,20 @@ mod tests {
         assert!(view_transform.approx(&expected))
     }
 
+    #[test_case(0.0      , Vector::point(0.0, 3.0, -3.0) ; "start"  )]
+    #[test_case(PI / 2.0 , Vector::point(5.0, 3.0, 2.0 ) ; "quarter")]
+    #[test_case(PI       , Vector::point(0.0, 3.0, 7.0 ) ; "half"   )]
+    fn orbit(angle: f64, from: Vector) {
+        let center = Vector::point(0.0, 2.0, 2.0);
+        let orbit = Camera::orbit(center, 5.0, 1.0, angle);
+
+        assert!(orbit.approx(&Camera::transform(
+            from,
+            center,
+            Vector::vector(0.0, 1.0, 0.0)
+        )))
+    }
+
     #[test_case(200, 125, 0.01 ; "horizontal image")]
     #[test_case(125, 200, 0.01 ; "vertical image"  )]
     fn pixel_size(hsize: usize, vsize: usize, size: f64) {
@@ -159,4 +308,144 @@ mod tests {
 
         assert!(ray.origin.approx(&origin) && ray.direction.approx(&direction))
     }
+
+    #[test]
+    fn from_focal_length() {
+        let camera = Camera::from_focal_length(160, 120, 50.0, 36.0, Matrix::id());
+
+        assert!(camera.field_of_view.approx(&0.69111))
+    }
+
+    #[test_case(PI / 2.0, 36.0 ; "wide full frame")]
+    #[test_case(0.3     , 36.0 ; "tele full frame")]
+    #[test_case(1.0     , 23.6 ; "crop sensor"    )]
+    fn fov_focal_round_trip(field_of_view: f64, sensor_mm: f64) {
+        let focal_mm = Camera::fov_to_focal(field_of_view, sensor_mm);
+
+        assert!(Camera::focal_to_fov(focal_mm, sensor_mm).approx(&field_of_view))
+    }
+
+    #[test_case(200, 125, 0.02 ; "horizontal image")]
+    #[test_case(125, 200, 0.02 ; "vertical image"  )]
+    fn orthographic_pixel_size(hsize: usize, vsize: usize, size: f64) {
+        let camera = Camera::orthographic(hsize, vsize, 4.0, Matrix::id());
+
+        assert!(camera.pixel_size.approx(&size))
+    }
+
+    #[test_case(Matrix::id()                                                    ; "default orientation")]
+    #[test_case(Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0) ; "transformed"     )]
+    fn orthographic_rays_parallel(transform: Matrix) {
+        let camera = Camera::orthographic(201, 101, 4.0, transform);
+        let center = camera.ray_at_pixel(100, 50);
+
+        let rays: Vec<Ray> = [(0, 0), (200, 0), (0, 100), (37, 81)]
+            .iter()
+            .map(|&(x, y)| camera.ray_at_pixel(x, y))
+            .collect();
+
+        assert!(rays.iter().all(|ray| {
+            let offset = ray.origin - center.origin;
+            ray.direction.approx(&center.direction) && offset.dot(center.direction).approx(&0.0)
+        }));
+        assert!(camera
+            .ray_at_pixel(0, 0)
+            .origin
+            .approx(&(transform.inverse() * Vector::point(1.99005, 0.99502, 0.0))));
+    }
+
+    #[test_case(201, 101 ; "fine")]
+    #[test_case(101, 51  ; "coarse")]
+    fn ray_differentials(hsize: usize, vsize: usize) {
+        let camera = Camera::new(hsize, vsize, PI / 2.0, Matrix::id());
+        let differentials = camera
+            .ray_at_pixel(hsize / 2, vsize / 2)
+            .differentials
+            .unwrap();
+
+        assert!(
+            differentials.dx.magnitude().approx(&camera.pixel_size)
+                && differentials.dy.magnitude().approx(&camera.pixel_size)
+                && differentials.dx.x < 0.0
+                && differentials.dy.y < 0.0
+                && differentials
+                    .origin_dx
+                    .approx(&Vector::vector(0.0, 0.0, 0.0))
+                && differentials
+                    .origin_dy
+                    .approx(&Vector::vector(0.0, 0.0, 0.0))
+        )
+    }
+
+    #[test_case(201, 101 ; "fine")]
+    #[test_case(101, 51  ; "coarse")]
+    fn orthographic_ray_differentials(hsize: usize, vsize: usize) {
+        let transform = Matrix::rotation_y(PI / 4.0) * Matrix::translation(0.0, -2.0, 5.0);
+        let camera = Camera::orthographic(hsize, vsize, 4.0, transform);
+        let differentials = camera
+            .ray_at_pixel(hsize / 2, vsize / 2)
+            .differentials
+            .unwrap();
+
+        let (dx, dy) = (
+            transform.inverse() * Vector::vector(-camera.pixel_size, 0.0, 0.0),
+            transform.inverse() * Vector::vector(0.0, -camera.pixel_size, 0.0),
+        );
+
+        assert!(
+            differentials.origin_dx.approx(&dx)
+                && differentials.origin_dy.approx(&dy)
+                && differentials.dx.approx(&Vector::vector(0.0, 0.0, 0.0))
+                && differentials.dy.approx(&Vector::vector(0.0, 0.0, 0.0))
+        )
+    }
+
+    #[test]
+    fn ray_at_subpixel_center() {
+        let camera = Camera::new(201, 101, PI / 2.0, Matrix::id());
+
+        assert!(camera
+            .ray_at_subpixel(10, 20, 0.5, 0.5)
+            .direction
+            .approx(&camera.ray_at_pixel(10, 20).direction))
+    }
+
+    #[test]
+    fn jitter() {
+        let noise = Noise::Simplex { scale: 1.0 };
+
+        let offsets: Vec<(f64, f64)> = (0..16)
+            .map(|i| Camera::jitter(noise, i % 4, i / 4, 0))
+            .collect();
+
+        assert!(offsets
+            .iter()
+            .all(|&(ox, oy)| (0.0..1.0).contains(&ox) && (0.0..1.0).contains(&oy)));
+        assert!(offsets[5].0.approx(&Camera::jitter(noise, 1, 1, 0).0));
+        assert!(offsets[5].1.approx(&Camera::jitter(noise, 1, 1, 0).1));
+        assert!(offsets
+            .iter()
+            .any(|&(ox, oy)| !ox.approx(&offsets[0].0) || !oy.approx(&offsets[0].1)));
+    }
+
+    #[test]
+    fn with_lens() {
+        let pinhole = Camera::new(201, 101, PI / 2.0, Matrix::id());
+        let lens = pinhole.with_lens(0.5, 4.0, 16);
+
+        let focus = Vector::point(0.0, 0.0, -4.0);
+        let rays: Vec<Ray> = (0..lens.samples())
+            .map(|s| lens.ray_at_lens(100, 50, s))
+            .collect();
+
+        assert!(pinhole
+            .ray_at_lens(100, 50, 3)
+            .origin
+            .approx(&Vector::point(0.0, 0.0, 0.0)));
+        assert!(rays.iter().all(|ray| {
+            let t = (focus - ray.origin).magnitude();
+            ray.origin.magnitude() <= 0.25 && ray.position(t).approx(&focus)
+        }));
+        assert!(rays.iter().any(|ray| !ray.origin.approx(&rays[0].origin)));
+    }
 }
@@ -39,15 +39,114 @@ impl Color {
         }
     }
 
+    pub fn from_hsv(h: f64, s: f64, v: f64) -> Color {
+        let h = h.rem_euclid(360.0) / 60.0;
+        let c = v * s;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let m = v - c;
+
+        let (r, g, b) = match h as u8 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+
+        Color::new(r + m, g + m, b + m)
+    }
+
+    pub fn to_hsv(self) -> (f64, f64, f64) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let delta = max - min;
+
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / delta).rem_euclid(6.0)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / delta + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / delta + 4.0)
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+
+        (h, s, max)
+    }
+
+    // a NaN or infinite channel from a degenerate shading path turns black
+    pub fn sanitize(self) -> Color {
+        let sanitize = |x: f64| if x.is_finite() { x } else { 0.0 };
+
+        Color::new(sanitize(self.r), sanitize(self.g), sanitize(self.b))
+    }
+
+    // Rec. 709 weights of the linear channels
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn clamp01(self) -> Color {
+        let color = self.sanitize();
+
+        Color::new(
+            color.r.clamp(0.0, 1.0),
+            color.g.clamp(0.0, 1.0),
+            color.b.clamp(0.0, 1.0),
+        )
+    }
+
     pub fn clamp(self) -> (u8, u8, u8) {
-        let clamp = |x: f64| (x.min(1.0).max(0.0) * 255.0).round() as u8;
+        let color = self.sanitize();
+        let clamp = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        (clamp(color.r), clamp(color.g), clamp(color.b))
+    }
+
+    pub fn clamp_gamma(self, gamma: f64) -> (u8, u8, u8) {
+        let color = self.sanitize();
+        let clamp = |x: f64| (x.clamp(0.0, 1.0).powf(1.0 / gamma) * 255.0).round() as u8;
 
-        (clamp(self.r), clamp(self.g), clamp(self.b))
+        (clamp(color.r), clamp(color.g), clamp(color.b))
     }
 
     pub fn avg(self, other: Color) -> Color {
         (self + other) * 0.5
     }
+
+    pub fn lerp(self, other: Color, t: f64) -> Color {
+        self.lerp_unclamped(other, t.clamp(0.0, 1.0))
+    }
+
+    // weighting both ends keeps t = 0 and t = 1 exact
+    pub fn lerp_unclamped(self, other: Color, t: f64) -> Color {
+        self * (1.0 - t) + other * t
+    }
+
+    pub fn average(colors: &[Color]) -> Color {
+        let sum = colors
+            .iter()
+            .fold(Color::black(), |sum, color| sum + *color);
+
+        sum * (1.0 / colors.len() as f64)
+    }
+
+    pub fn srgb(self) -> (u8, u8, u8) {
+        let encode = |x: f64| {
+            let x = x.clamp(0.0, 1.0);
+            if x <= 0.0031308 {
+                12.92 * x
+            } else {
+                1.055 * x.powf(1.0 / 2.4) - 0.055
+            }
+        };
+
+        let color = self.sanitize();
+
+        Color::new(encode(color.r), encode(color.g), encode(color.b)).clamp()
+    }
 }
 
 impl fmt::Display for Color {
@@ -104,6 +203,7 @@ impl ops::Mul<f64> for Color {
     }
 }
 
+// component-wise (Hadamard) product, e.g. a light's intensity filtered by a surface color
 impl ops::Mul<Color> for Color {
     type Output = Color;
 
@@ -117,11 +217,14 @@ impl ops::Mul<Color> for Color {
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
     use crate::approx::Approx;
 
+    use test_case::test_case;
+
     #[test]
     fn adding_colors() {
         let c0 = Color::new(0.9, 0.6, 0.75);
@@ -148,6 +251,20 @@ mod tests {
         assert!((c0 * 2.0).approx(&c1))
     }
 
+    #[test_case(Color::white()           , 1.0    ; "white")]
+    #[test_case(Color::new(1.0, 0.0, 0.0), 0.2126 ; "red"  )]
+    #[test_case(Color::new(0.0, 0.0, 1.0), 0.0722 ; "blue" )]
+    fn luminance(color: Color, expected: f64) {
+        assert!(color.luminance().approx(&expected))
+    }
+
+    #[test]
+    fn clamp01() {
+        let color = Color::new(1.5, -0.2, f64::NAN).clamp01();
+
+        assert!(color.approx(&Color::new(1.0, 0.0, 0.0)))
+    }
+
     #[test]
     fn multiplying_colors() {
         let c0 = Color::new(1.0, 0.2, 0.4);
@@ -156,4 +273,71 @@ mod tests {
 
         assert!((c0 * c1).approx(&c2))
     }
+
+    #[test]
+    fn average_then_encode() {
+        let average = Color::average(&[Color::black(), Color::white()]);
+
+        assert!(average.approx(&Color::new(0.5, 0.5, 0.5)));
+        assert_eq!(average.srgb(), (188, 188, 188));
+        assert_eq!(average.clamp(), (128, 128, 128))
+    }
+
+    #[test_case(Color::new(f64::NAN, 0.5, 1.0)     , Color::new(0.0, 0.5, 1.0) ; "nan")]
+    #[test_case(Color::new(f64::INFINITY, -0.2, 0.3), Color::new(0.0, -0.2, 0.3) ; "infinite")]
+    #[test_case(Color::new(0.1, 2.5, -0.3)          , Color::new(0.1, 2.5, -0.3) ; "finite untouched")]
+    fn sanitize(color: Color, expected: Color) {
+        assert!(color.sanitize().approx(&expected))
+    }
+
+    #[test]
+    fn clamp_non_finite() {
+        let color = Color::new(f64::NAN, f64::INFINITY, 0.5);
+
+        assert_eq!(color.clamp(), (0, 0, 128));
+        assert_eq!(color.clamp_gamma(2.2), (0, 0, 186));
+        assert_eq!(color.srgb(), (0, 0, 188))
+    }
+
+    #[test_case(-1.0, Color::new(0.1, 0.2 , 0.3), Color::new(-0.5, -0.3 , -0.1) ; "before")]
+    #[test_case( 0.0, Color::new(0.1, 0.2 , 0.3), Color::new( 0.1,  0.2 ,  0.3) ; "start" )]
+    #[test_case( 0.5, Color::new(0.4, 0.45, 0.5), Color::new( 0.4,  0.45,  0.5) ; "middle")]
+    #[test_case( 1.0, Color::new(0.7, 0.7 , 0.7), Color::new( 0.7,  0.7 ,  0.7) ; "end"   )]
+    #[test_case( 2.0, Color::new(0.7, 0.7 , 0.7), Color::new( 1.3,  1.2 ,  1.1) ; "after" )]
+    fn lerp(t: f64, clamped: Color, unclamped: Color) {
+        let from = Color::new(0.1, 0.2, 0.3);
+        let to = Color::new(0.7, 0.7, 0.7);
+
+        assert!(from.lerp(to, t).approx(&clamped) && from.lerp_unclamped(to, t).approx(&unclamped))
+    }
+
+    #[test]
+    fn lerp_exact() {
+        let from = Color::new(0.1, 0.2, 0.3);
+        let to = Color::new(0.7, 1e9, 0.3);
+        let (start, end) = (from.lerp(to, 0.0), from.lerp(to, 1.0));
+
+        assert!(
+            (start.r, start.g, start.b) == (from.r, from.g, from.b)
+                && (end.r, end.g, end.b) == (to.r, to.g, to.b)
+        )
+    }
+
+    #[test_case(0.0  , Color::new(1.0, 0.0, 0.0) ; "red"  )]
+    #[test_case(120.0, Color::new(0.0, 1.0, 0.0) ; "green")]
+    #[test_case(240.0, Color::new(0.0, 0.0, 1.0) ; "blue" )]
+    #[test_case(420.0, Color::new(1.0, 1.0, 0.0) ; "wrapped yellow")]
+    fn from_hsv(h: f64, expected: Color) {
+        assert!(Color::from_hsv(h, 1.0, 1.0).approx(&expected))
+    }
+
+    #[test_case(Color::new(0.2, 0.4, 0.6) ; "blueish")]
+    #[test_case(Color::new(0.9, 0.1, 0.3) ; "reddish")]
+    #[test_case(Color::new(0.5, 0.5, 0.5) ; "gray"   )]
+    #[test_case(Color::black()            ; "black"  )]
+    fn hsv_round_trip(color: Color) {
+        let (h, s, v) = color.to_hsv();
+
+        assert!(Color::from_hsv(h, s, v).approx(&color))
+    }
 }
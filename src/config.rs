@@ -1,2 +1,33 @@
 pub const EPSILON: f64 = 0.00001;
+// slack on the barycentric bounds so rays hitting a shared triangle edge can't slip through
+pub const EDGE_EPSILON: f64 = 1e-9;
 pub const FUEL: i32 = 5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Settings {
+    pub max_depth: i32,
+    pub epsilon: f64,
+    pub caustics: bool,
+    pub enable_reflections: bool,
+    pub enable_refractions: bool,
+    pub glossy_samples: usize,
+    pub light_bias: f64,
+    // point and spot lights act as a disk this wide for the single ray penumbra
+    // estimate, zero keeps their shadows hard
+    pub soft_shadow_radius: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_depth: FUEL,
+            epsilon: EPSILON,
+            caustics: false,
+            enable_reflections: true,
+            enable_refractions: true,
+            glossy_samples: 16,
+            light_bias: 0.0,
+            soft_shadow_radius: 0.0,
+        }
+    }
+}
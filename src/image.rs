@@ -1,12 +1,42 @@
 use crate::camera::Camera;
 use crate::color::Color;
-use crate::config::FUEL;
 // use crate::intersection::Intersection;
-use crate::world::World;
+use crate::intersection::IntersectionPool;
+use crate::noise::Noise;
+use crate::ray::Ray;
+#[cfg(feature = "stats")]
+use crate::stats::RenderStats;
+use crate::texture::ImageTexture;
+use crate::world::{NormalSpace, World};
+
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 // use crossbeam;
 use rayon::prelude::*;
 
+#[derive(Debug, Clone, Copy)]
+pub struct BilateralParams {
+    pub radius: usize,
+    pub sigma_spatial: f64,
+    pub sigma_normal: f64,
+    pub sigma_depth: f64,
+}
+
+impl Default for BilateralParams {
+    fn default() -> Self {
+        BilateralParams {
+            radius: 2,
+            sigma_spatial: 1.5,
+            sigma_normal: 0.1,
+            sigma_depth: 0.1,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Image {
     hsize: usize,
@@ -62,14 +92,231 @@ impl Image {
     //     }
     // }
 
+    /// Every render entry point borrows both the camera and the world:
+    ///
+    /// ```
+    /// use raytracer::camera::Camera;
+    /// use raytracer::image::Image;
+    /// use raytracer::linalg::Vector;
+    /// use raytracer::world::World;
+    ///
+    /// let from = Vector::point(0.0, 0.0, -5.0);
+    /// let to = Vector::point(0.0, 0.0, 0.0);
+    /// let up = Vector::vector(0.0, 1.0, 0.0);
+    ///
+    /// let camera = Camera::new(11, 11, 1.0, Camera::transform(from, to, up));
+    /// let world = World::default();
+    ///
+    /// let image = Image::par_render(&camera, &world);
+    /// assert_eq!((image.hsize(), image.vsize()), (11, 11));
+    /// ```
     pub fn par_render(camera: &Camera, world: &World) -> Image {
+        Image::par_render_pooled(camera, world, &IntersectionPool::new())
+    }
+
+    // keeping the pool around between frames avoids reallocating the intersection buffers
+    pub fn par_render_pooled<'a>(
+        camera: &Camera,
+        world: &'a World,
+        pool: &IntersectionPool<'a>,
+    ) -> Image {
+        let pixels: Vec<Color> = (0..camera.vsize)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                let mut intersections = pool.take();
+                let scanline = world.render_scanline(camera, y, &mut intersections);
+                pool.give(intersections);
+
+                scanline
+            })
+            .collect();
+
+        Image {
+            hsize: camera.hsize,
+            vsize: camera.vsize,
+            pixels,
+        }
+    }
+
+    // each scanline runs on a single thread, so its counter delta is exactly its own work
+    #[cfg(feature = "stats")]
+    pub fn par_render_with_stats(camera: &Camera, world: &World) -> (Image, RenderStats) {
+        let scanlines: Vec<(Vec<Color>, RenderStats)> = (0..camera.vsize)
+            .into_par_iter()
+            .map(|y| {
+                let before = RenderStats::current();
+                let scanline = world.render_scanline(camera, y, &mut vec![]);
+
+                (scanline, RenderStats::current() - before)
+            })
+            .collect();
+
+        let stats = scanlines.iter().map(|(_, stats)| *stats).sum();
+        let pixels = scanlines
+            .into_iter()
+            .flat_map(|(scanline, _)| scanline)
+            .collect();
+
+        let image = Image {
+            hsize: camera.hsize,
+            vsize: camera.vsize,
+            pixels,
+        };
+
+        (image, stats)
+    }
+
+    // `progress` receives the number of finished tiles and the total, from any worker thread
+    pub fn par_render_tiled<F>(camera: &Camera, world: &World, tile: usize, progress: F) -> Image
+    where
+        F: Fn(usize, usize) + Sync + Send,
+    {
+        Image::render_tiles(camera, world, tile, progress, || true)
+    }
+
+    // tiles not yet started at the deadline are skipped and stay black
+    pub fn par_render_deadline(camera: &Camera, world: &World, duration: Duration) -> Image {
+        let deadline = Instant::now() + duration;
+
+        Image::render_tiles(camera, world, 16, |_, _| (), || Instant::now() < deadline)
+    }
+
+    fn render_tiles<F, D>(
+        camera: &Camera,
+        world: &World,
+        tile: usize,
+        progress: F,
+        dispatch: D,
+    ) -> Image
+    where
+        F: Fn(usize, usize) + Sync + Send,
+        D: Fn() -> bool + Sync + Send,
+    {
+        assert!(tile > 0);
+
+        let tiles_x = camera.hsize.div_ceil(tile);
+        let tiles_y = camera.vsize.div_ceil(tile);
+        let total = tiles_x * tiles_y;
+        let done = AtomicUsize::new(0);
+
+        let tiles: Vec<(usize, usize, Vec<Color>)> = (0..total)
+            .into_par_iter()
+            .filter(|_| dispatch())
+            .map(|i| {
+                let (x0, y0) = ((i % tiles_x) * tile, (i / tiles_x) * tile);
+                let (x1, y1) = ((x0 + tile).min(camera.hsize), (y0 + tile).min(camera.vsize));
+
+                let mut intersections = vec![];
+                let colors = (y0..y1)
+                    .flat_map(|y| (x0..x1).map(move |x| (x, y)))
+                    .map(|(x, y)| world.render_pixel(camera, x, y, &mut intersections))
+                    .collect();
+
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+
+                (x0, y0, colors)
+            })
+            .collect();
+
+        let mut image = Image::new(camera.hsize, camera.vsize);
+        for (x0, y0, colors) in tiles {
+            let width = (x0 + tile).min(camera.hsize) - x0;
+            for (i, color) in colors.into_iter().enumerate() {
+                image.write(x0 + i % width, y0 + i / width, color);
+            }
+        }
+
+        image
+    }
+
+    pub fn par_render_aa(camera: &Camera, world: &World, samples_per_axis: usize) -> Image {
+        let noise = Noise::Simplex { scale: 1.0 };
+        let n = samples_per_axis;
+
+        // one jittered sample inside every cell of an n x n grid over the pixel
+        Image::par_render_sampled(camera, world, n * n, |x, y, sample| {
+            let (jx, jy) = Camera::jitter(noise, x, y, sample);
+            let ox = ((sample % n) as f64 + jx) / n as f64;
+            let oy = ((sample / n) as f64 + jy) / n as f64;
+
+            (ox, oy, 0.0)
+        })
+    }
+
+    pub fn par_render_motion(camera: &Camera, world: &World, samples: usize) -> Image {
+        let noise = Noise::Simplex { scale: 1.0 };
+
+        // every sample gets its own slot of the shutter interval
+        Image::par_render_sampled(camera, world, samples, |x, y, sample| {
+            let (ox, oy) = Camera::jitter(noise, x, y, sample);
+            let (jt, _) = Camera::jitter(noise, x, y, sample + samples);
+
+            (ox, oy, (sample as f64 + jt) / samples as f64)
+        })
+    }
+
+    pub fn par_render_jittered(
+        camera: &Camera,
+        world: &World,
+        noise: Noise,
+        samples: usize,
+    ) -> Image {
+        Image::par_render_sampled(camera, world, samples, |x, y, sample| {
+            let (ox, oy) = Camera::jitter(noise, x, y, sample);
+
+            (ox, oy, 0.0)
+        })
+    }
+
+    // `offset` places a sample inside the pixel and the shutter interval as (ox, oy, time),
+    // each of those is then taken through every lens sample of the camera
+    fn par_render_sampled<F>(camera: &Camera, world: &World, samples: usize, offset: F) -> Image
+    where
+        F: Fn(usize, usize, usize) -> (f64, f64, f64) + Sync,
+    {
+        assert!(samples >= 1);
+
+        let pixels: Vec<Color> = (0..(camera.hsize * camera.vsize))
+            .into_par_iter()
+            .map(|i| {
+                let x = i % camera.hsize;
+                let y = i / camera.hsize;
+                let mut intersections = vec![];
+
+                // average in linear space, any encoding happens once on output
+                let colors: Vec<Color> = (0..samples)
+                    .flat_map(|sample| {
+                        let (ox, oy, time) = offset(x, y, sample);
+                        (0..camera.samples()).map(move |lens| Ray {
+                            time,
+                            ..camera.ray_through(x, y, ox, oy, lens)
+                        })
+                    })
+                    .map(|ray| world.color_at(ray, world.settings.max_depth, &mut intersections))
+                    .collect();
+
+                Color::average(&colors)
+            })
+            .collect();
+
+        Image {
+            hsize: camera.hsize,
+            vsize: camera.vsize,
+            pixels,
+        }
+    }
+
+    pub fn par_render_normals(camera: &Camera, world: &World, space: NormalSpace) -> Image {
         let pixels: Vec<Color> = (0..(camera.hsize * camera.vsize))
             .into_par_iter()
             .map(|i| {
                 let x = i % camera.hsize;
                 let y = i / camera.hsize;
                 let ray = camera.ray_at_pixel(x, y);
-                world.color_at(ray, FUEL, &mut vec![])
+                world
+                    .normal_at(ray, space, &mut vec![])
+                    .map(|n| Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0) * 0.5)
+                    .unwrap_or_else(Color::black)
             })
             .collect();
 
@@ -80,6 +327,130 @@ impl Image {
         }
     }
 
+    pub fn bilateral_denoise(
+        &self,
+        normals: &Image,
+        depth: &DepthImage,
+        params: BilateralParams,
+    ) -> Image {
+        assert_eq!((normals.hsize, normals.vsize), (self.hsize, self.vsize));
+        assert_eq!((depth.hsize, depth.vsize), (self.hsize, self.vsize));
+
+        let gaussian = |d2: f64, sigma: f64| (-d2 / (2.0 * sigma * sigma)).exp();
+
+        let pixels: Vec<Color> = (0..(self.hsize * self.vsize))
+            .into_par_iter()
+            .map(|i| {
+                let x = i % self.hsize;
+                let y = i / self.hsize;
+
+                let normal = normals.read(x, y);
+                let z = depth.read(x, y);
+
+                let mut sum = Color::black();
+                let mut total = 0.0;
+
+                let (x_min, x_max) = (x.saturating_sub(params.radius), x + params.radius);
+                let (y_min, y_max) = (y.saturating_sub(params.radius), y + params.radius);
+
+                for ny in y_min..=y_max.min(self.vsize - 1) {
+                    for nx in x_min..=x_max.min(self.hsize - 1) {
+                        let spatial =
+                            (nx as f64 - x as f64).powi(2) + (ny as f64 - y as f64).powi(2);
+
+                        let n = normals.read(nx, ny) - normal;
+                        let n2 = n.r * n.r + n.g * n.g + n.b * n.b;
+
+                        let nz = depth.read(nx, ny);
+                        let z2 = if nz == z { 0.0 } else { (nz - z).powi(2) };
+
+                        let weight = gaussian(spatial, params.sigma_spatial)
+                            * gaussian(n2, params.sigma_normal)
+                            * gaussian(z2, params.sigma_depth);
+
+                        sum += self.read(nx, ny) * weight;
+                        total += weight;
+                    }
+                }
+
+                sum * (1.0 / total)
+            })
+            .collect();
+
+        Image {
+            hsize: self.hsize,
+            vsize: self.vsize,
+            pixels,
+        }
+    }
+
+    // compresses [0, inf) into [0, 1) per channel so bright highlights keep some detail
+    pub fn tone_map_reinhard(&mut self) {
+        for color in &mut self.pixels {
+            let map = |c: f64| {
+                let c = c.max(0.0);
+                c / (1.0 + c)
+            };
+            let sanitized = color.sanitize();
+
+            *color = Color::new(map(sanitized.r), map(sanitized.g), map(sanitized.b));
+        }
+    }
+
+    pub fn clip_mask(&self) -> Image {
+        let pixels = self
+            .pixels
+            .iter()
+            .map(|color| {
+                let channels = [color.r, color.g, color.b];
+                let over = channels.iter().any(|&c| c > 1.0);
+                let under = channels.iter().any(|&c| c < 0.0);
+
+                let flag = |set: bool| if set { 1.0 } else { 0.0 };
+
+                Color::new(flag(over), 0.0, flag(under))
+            })
+            .collect();
+
+        Image {
+            hsize: self.hsize,
+            vsize: self.vsize,
+            pixels,
+        }
+    }
+
+    // luminance at or above 1 lands in the last bin
+    pub fn luminance_histogram(&self, bins: usize) -> Vec<usize> {
+        assert!(bins > 0);
+        let mut histogram = vec![0; bins];
+
+        for color in &self.pixels {
+            let l = color.sanitize().luminance();
+            let bin = (l.max(0.0) * bins as f64) as usize;
+            histogram[bin.min(bins - 1)] += 1;
+        }
+
+        histogram
+    }
+
+    pub fn average_luminance(&self) -> f64 {
+        let sum: f64 = self
+            .pixels
+            .iter()
+            .map(|color| color.sanitize().luminance())
+            .sum();
+
+        sum / self.pixels.len() as f64
+    }
+
+    pub fn hsize(&self) -> usize {
+        self.hsize
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.vsize
+    }
+
     pub fn write(&mut self, x: usize, y: usize, color: Color) {
         let i = self.xy_to_idx(x, y);
         self.pixels[i] = color;
@@ -91,11 +462,24 @@ impl Image {
     }
 
     pub fn ppm(&self) -> String {
+        self.ppm_gamma(1.0)
+    }
+
+    pub fn ppm_gamma(&self, gamma: f64) -> String {
+        self.ppm_encoded(|color| color.clamp_gamma(gamma))
+    }
+
+    // pixels stay linear until here, so averaged samples are encoded exactly once
+    pub fn ppm_srgb(&self) -> String {
+        self.ppm_encoded(Color::srgb)
+    }
+
+    fn ppm_encoded(&self, encode: impl Fn(Color) -> (u8, u8, u8)) -> String {
         let mut ppm = format!("P3\n{} {}\n255", self.hsize, self.vsize);
 
         let mut j = 0;
         for (i, color) in self.pixels.iter().enumerate() {
-            let (r, g, b) = color.clamp();
+            let (r, g, b) = encode(*color);
 
             if i % self.hsize == 0 || j % 5 == 0 {
                 ppm.push('\n');
@@ -111,17 +495,172 @@ impl Image {
         ppm
     }
 
+    pub fn png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.png_gamma(path, 1.0)
+    }
+
+    pub fn png_gamma<P: AsRef<Path>>(&self, path: P, gamma: f64) -> io::Result<()> {
+        self.png_encoded(path, |color| color.clamp_gamma(gamma))
+    }
+
+    pub fn png_srgb<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.png_encoded(path, Color::srgb)
+    }
+
+    fn png_encoded<P: AsRef<Path>>(
+        &self,
+        path: P,
+        encode: impl Fn(Color) -> (u8, u8, u8),
+    ) -> io::Result<()> {
+        let file = fs::File::create(path)?;
+
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.hsize as u32, self.vsize as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgb_bytes(encode))?;
+
+        Ok(())
+    }
+
+    // writes <base>.png, <base>_depth.png and <base>_normal.png
+    pub fn write_multilayer<P: AsRef<Path>>(
+        base: P,
+        color: &Image,
+        depth: &DepthImage,
+        normals: &Image,
+    ) -> io::Result<()> {
+        let path = |suffix: &str| {
+            let mut path = base.as_ref().as_os_str().to_owned();
+            path.push(suffix);
+            PathBuf::from(path)
+        };
+
+        color.png(path(".png"))?;
+        depth.png(path("_depth.png"))?;
+        normals.png(path("_normal.png"))
+    }
+
+    pub fn ppm_binary(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.hsize, self.vsize).into_bytes();
+        ppm.extend(self.rgb_bytes(Color::clamp));
+
+        ppm
+    }
+
+    fn rgb_bytes(&self, encode: impl Fn(Color) -> (u8, u8, u8)) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for color in &self.pixels {
+            let (r, g, b) = encode(*color);
+            data.extend_from_slice(&[r, g, b]);
+        }
+
+        data
+    }
+
     fn xy_to_idx(&self, x: usize, y: usize) -> usize {
         y * self.hsize + x
     }
 }
 
+impl From<Image> for ImageTexture {
+    fn from(image: Image) -> ImageTexture {
+        ImageTexture::new(image.hsize, image.vsize, image.pixels)
+    }
+}
+
+#[derive(Debug)]
+pub struct Passes {
+    pub color: Image,
+    pub alpha: Image,
+    pub depth: DepthImage,
+    pub normal: Image,
+}
+
+#[derive(Debug)]
+pub struct DepthImage {
+    hsize: usize,
+    vsize: usize,
+    depths: Vec<f64>,
+}
+
+impl DepthImage {
+    pub fn new(hsize: usize, vsize: usize) -> DepthImage {
+        DepthImage {
+            hsize,
+            vsize,
+            depths: vec![f64::INFINITY; hsize * vsize],
+        }
+    }
+
+    pub fn par_render(camera: &Camera, world: &World) -> DepthImage {
+        let depths: Vec<f64> = (0..(camera.hsize * camera.vsize))
+            .into_par_iter()
+            .map(|i| {
+                let x = i % camera.hsize;
+                let y = i / camera.hsize;
+                let ray = camera.ray_at_pixel(x, y);
+                world.depth_at(ray, &mut vec![])
+            })
+            .collect();
+
+        DepthImage {
+            hsize: camera.hsize,
+            vsize: camera.vsize,
+            depths,
+        }
+    }
+
+    pub fn write(&mut self, x: usize, y: usize, depth: f64) {
+        self.depths[y * self.hsize + x] = depth;
+    }
+
+    pub fn read(&self, x: usize, y: usize) -> f64 {
+        self.depths[y * self.hsize + x]
+    }
+
+    // near is black, the farthest hit is white and misses stay white as well
+    pub fn png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let max = self
+            .depths
+            .iter()
+            .filter(|depth| depth.is_finite())
+            .fold(0.0_f64, |max, depth| max.max(*depth));
+
+        let pixels = self
+            .depths
+            .iter()
+            .map(|depth| {
+                let gray = if depth.is_finite() && max > 0.0 {
+                    depth / max
+                } else {
+                    1.0
+                };
+                Color::new(gray, gray, gray)
+            })
+            .collect();
+
+        Image {
+            hsize: self.hsize,
+            vsize: self.vsize,
+            pixels,
+        }
+        .png(path)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use crate::approx::Approx;
-    use crate::linalg::Vector;
+    use crate::config::{Settings, FUEL};
+    use crate::intersection::Intersection;
+    use crate::linalg::{Matrix, Vector};
+    use crate::material::Material;
+    use crate::shape::{Element, ShapeArgs};
 
     use std::f64::consts::PI;
 
@@ -145,6 +684,435 @@ mod tests {
             .approx(&Color::new(0.38066, 0.47583, 0.28550,)))
     }
 
+    #[test]
+    fn rendering_tiled() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 7, PI / 2.0, Camera::transform(from, to, up));
+        let world = World::default();
+
+        let finished = AtomicUsize::new(0);
+        let image = Image::par_render(&camera, &world);
+        let tiled = Image::par_render_tiled(&camera, &world, 4, |done, total| {
+            assert!(total == 6);
+            finished.fetch_max(done, Ordering::Relaxed);
+        });
+
+        // bit for bit, not just approximately equal
+        let identical = tiled
+            .pixels
+            .iter()
+            .zip(&image.pixels)
+            .all(|(a, b)| a.r == b.r && a.g == b.g && a.b == b.b);
+
+        assert!(identical && finished.into_inner() == 6)
+    }
+
+    #[test]
+    fn rendering_deadline() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(40, 30, PI / 2.0, Camera::transform(from, to, up));
+        let world = World::default();
+        let image = Image::par_render(&camera, &world);
+
+        let expired = Image::par_render_deadline(&camera, &world, Duration::from_secs(0));
+        let generous = Image::par_render_deadline(&camera, &world, Duration::from_secs(600));
+
+        assert!(
+            expired
+                .pixels
+                .iter()
+                .all(|color| color.approx(&Color::black()))
+                && generous.pixels.approx(&image.pixels)
+        )
+    }
+
+    #[test]
+    fn rendering_pooled() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 7, PI / 2.0, Camera::transform(from, to, up));
+        let world = World::default();
+        let image = Image::par_render(&camera, &world);
+
+        // a single worker keeps the pool down to exactly one buffer
+        let threads = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let pool = IntersectionPool::new();
+
+        let frames: Vec<(Image, *const Intersection, usize)> = (0..3)
+            .map(|_| {
+                let frame = threads.install(|| Image::par_render_pooled(&camera, &world, &pool));
+                let buffer = pool.take();
+                let allocation = (buffer.as_ptr(), buffer.capacity());
+                pool.give(buffer);
+
+                (frame, allocation.0, allocation.1)
+            })
+            .collect();
+
+        assert!(frames.iter().all(|(frame, ptr, capacity)| {
+            frame.pixels.approx(&image.pixels)
+                && *ptr == frames[0].1
+                && *capacity == frames[0].2
+                && *capacity > 0
+        }))
+    }
+
+    #[test]
+    fn rendering_scanlines() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 7, PI / 2.0, Camera::transform(from, to, up));
+        let world = World::default();
+
+        let image = Image::par_render(&camera, &world);
+        let scanlines: Vec<Color> = (0..camera.vsize)
+            .flat_map(|y| world.render_scanline(&camera, y, &mut vec![]))
+            .collect();
+
+        assert!(scanlines.approx(&image.pixels))
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn rendering_with_stats() {
+        use crate::shape::GroupKind;
+
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 11, PI / 2.0, Camera::transform(from, to, up));
+
+        // without lights every pixel traces exactly one ray
+        let world = World {
+            lights: vec![],
+            elements: vec![Element::composite(
+                Matrix::id(),
+                None,
+                GroupKind::Aggregation,
+                vec![Element::sphere(ShapeArgs::default())],
+            )],
+            ..World::default()
+        };
+
+        let (image, stats) = Image::par_render_with_stats(&camera, &world);
+
+        assert!(
+            image
+                .pixels
+                .approx(&Image::par_render(&camera, &world).pixels)
+                && stats.bbox_tests == 121
+                && stats.primitive_tests == stats.bbox_hits
+                && stats.culled() > 0
+                && stats.bbox_hits > 0
+        )
+    }
+
+    #[test]
+    fn rendering_aa() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(21, 21, PI / 4.0, Camera::transform(from, to, up));
+        let world = World::default();
+
+        let max_delta = |image: &Image| {
+            let mut max = 0.0_f64;
+            for y in 0..image.vsize {
+                for x in 1..image.hsize {
+                    max = max.max((image.read(x, y).g - image.read(x - 1, y).g).abs());
+                }
+            }
+            max
+        };
+
+        let single = Image::par_render(&camera, &world);
+        let supersampled = Image::par_render_aa(&camera, &world, 2);
+
+        assert!(max_delta(&supersampled) < max_delta(&single))
+    }
+
+    #[test]
+    fn rendering_motion_blur() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(21, 21, PI / 4.0, Camera::transform(from, to, up));
+        let world = World {
+            elements: vec![Element::sphere(ShapeArgs {
+                transform: Matrix::translation(-1.0, 0.0, 0.0) * Matrix::scaling(0.5, 0.5, 0.5),
+                material: Material {
+                    ambient: 1.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                    ..Material::default()
+                },
+                motion: Vector::vector(2.0, 0.0, 0.0),
+                ..ShapeArgs::default()
+            })],
+            ..World::default()
+        };
+
+        let partial = |image: &Image| {
+            image
+                .pixels
+                .iter()
+                .filter(|color| color.r > 0.05 && color.r < 0.95)
+                .count()
+        };
+
+        let crisp = Image::par_render(&camera, &world);
+        let blurred = Image::par_render_motion(&camera, &world, 16);
+
+        assert!(partial(&crisp) == 0 && partial(&blurred) > 0)
+    }
+
+    #[test]
+    fn rendering_depth_of_field() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(21, 21, PI / 4.0, Camera::transform(from, to, up));
+        let world = World {
+            elements: vec![Element::sphere(ShapeArgs::default())],
+            ..World::default()
+        };
+
+        // the silhouette edge blurs when the sphere is out of focus
+        let edge = |camera: Camera| Image::par_render(&camera, &world).read(17, 10);
+
+        let pinhole = edge(camera);
+        let focused = edge(camera.with_lens(0.5, 4.0, 32));
+        let blurred = edge(camera.with_lens(0.5, 1.0, 32));
+
+        assert!((focused.r - pinhole.r).abs() < (blurred.r - pinhole.r).abs())
+    }
+
+    #[test]
+    fn sampled_rendering_depth_of_field() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(21, 21, PI / 4.0, Camera::transform(from, to, up));
+        let world = World {
+            elements: vec![Element::sphere(ShapeArgs::default())],
+            ..World::default()
+        };
+
+        let renderers: [fn(&Camera, &World) -> Image; 3] = [
+            |camera, world| Image::par_render_aa(camera, world, 1),
+            |camera, world| Image::par_render_motion(camera, world, 1),
+            |camera, world| {
+                Image::par_render_jittered(camera, world, Noise::Simplex { scale: 1.0 }, 1)
+            },
+        ];
+
+        assert!(renderers.iter().all(|render| {
+            let edge = |camera: Camera| render(&camera, &world).read(17, 10);
+
+            let pinhole = edge(camera);
+            let focused = edge(camera.with_lens(0.5, 4.0, 32));
+            let blurred = edge(camera.with_lens(0.5, 1.0, 32));
+
+            (focused.r - pinhole.r).abs() < (blurred.r - pinhole.r).abs()
+        }))
+    }
+
+    #[test]
+    fn rendering_with_settings() {
+        let from = Vector::point(0.0, 1.5, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 11, PI / 3.0, Camera::transform(from, to, up));
+
+        let world = |max_depth: i32| {
+            let mut world = World::default();
+            world.elements.push(Element::plane(ShapeArgs {
+                transform: Matrix::translation(0.0, -1.0, 0.0),
+                material: Material {
+                    reflective: 0.5,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            }));
+            world.settings = Settings {
+                max_depth,
+                ..Settings::default()
+            };
+            world
+        };
+
+        let default = Image::par_render(&camera, &world(FUEL));
+        let shallow = Image::par_render(&camera, &world(0));
+
+        assert!(!default.pixels.approx(&shallow.pixels))
+    }
+
+    #[test]
+    fn rendering_normals() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 11, PI / 2.0, Camera::transform(from, to, up));
+
+        let world = World {
+            elements: vec![Element::sphere(ShapeArgs {
+                transform: Matrix::rotation_y(PI / 2.0),
+                ..ShapeArgs::default()
+            })],
+            ..World::default()
+        };
+
+        let world_normals = Image::par_render_normals(&camera, &world, NormalSpace::World);
+        let object_normals = Image::par_render_normals(&camera, &world, NormalSpace::Object);
+
+        assert!(
+            world_normals.read(5, 5).approx(&Color::new(0.5, 0.5, 0.0))
+                && object_normals.read(5, 5).approx(&Color::new(1.0, 0.5, 0.5))
+        )
+    }
+
+    #[test]
+    fn rendering_depth() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 11, PI / 2.0, Camera::transform(from, to, up));
+        let depth = DepthImage::par_render(&camera, &World::default());
+
+        assert!(depth.read(5, 5).approx(&4.0) && depth.read(0, 0) == f64::INFINITY)
+    }
+
+    #[test]
+    fn rendering_passes() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(11, 11, PI / 2.0, Camera::transform(from, to, up));
+        let world = World::default();
+
+        let passes = world.render_passes(&camera);
+        let image = Image::par_render(&camera, &world);
+        let depth = DepthImage::par_render(&camera, &world);
+        let normals = Image::par_render_normals(&camera, &world, NormalSpace::World);
+
+        assert!(
+            passes.color.pixels.approx(&image.pixels)
+                && passes.normal.pixels.approx(&normals.pixels)
+                && passes.depth.read(5, 5).approx(&depth.read(5, 5))
+                && passes.depth.read(0, 0) == f64::INFINITY
+                && passes.alpha.read(5, 5).approx(&Color::white())
+                && passes.alpha.read(0, 0).approx(&Color::black())
+        )
+    }
+
+    #[test]
+    fn bilateral_denoise() {
+        let (hsize, vsize) = (8, 5);
+
+        let mut image = Image::new(hsize, vsize);
+        let mut normals = Image::new(hsize, vsize);
+        let mut depth = DepthImage::new(hsize, vsize);
+
+        for y in 0..vsize {
+            for x in 0..hsize {
+                // noisy flat region on the left, sharp normal edge towards the right
+                let (color, normal) = if x < 6 {
+                    let noise = if (x + y) % 2 == 0 { 0.1 } else { -0.1 };
+                    (
+                        Color::new(0.5, 0.5, 0.5) + Color::new(noise, noise, noise),
+                        Color::new(0.5, 1.0, 0.5),
+                    )
+                } else {
+                    (Color::white(), Color::new(1.0, 0.5, 0.5))
+                };
+
+                image.write(x, y, color);
+                normals.write(x, y, normal);
+                depth.write(x, y, 1.0);
+            }
+        }
+
+        let denoised = image.bilateral_denoise(&normals, &depth, BilateralParams::default());
+
+        let deviation = |image: &Image| (image.read(2, 2).r - 0.5).abs();
+
+        assert!(deviation(&denoised) < deviation(&image) / 2.0);
+        assert!(denoised.read(6, 2).approx(&Color::white()));
+        assert!((denoised.read(5, 2).r - image.read(5, 2).r).abs() < 0.1);
+    }
+
+    #[test]
+    fn tone_map_reinhard() {
+        let values = [0.0, 0.5, 1.0, 2.0, 10.0, 1000.0];
+
+        let mut image = Image::new(values.len(), 1);
+        for (x, &value) in values.iter().enumerate() {
+            image.write(x, 0, Color::new(value, value, value));
+        }
+        image.tone_map_reinhard();
+
+        let mapped: Vec<f64> = (0..values.len()).map(|x| image.read(x, 0).r).collect();
+
+        assert!(
+            mapped.iter().all(|&c| (0.0..1.0).contains(&c))
+                && mapped.windows(2).all(|pair| pair[0] < pair[1])
+                && mapped[2].approx(&0.5)
+        )
+    }
+
+    #[test]
+    fn clip_mask() {
+        let mut image = Image::new(3, 1);
+        image.write(0, 0, Color::new(0.2, 1.5, 0.3));
+        image.write(1, 0, Color::new(0.2, 0.5, -0.3));
+        image.write(2, 0, Color::new(1.0, 0.0, 0.5));
+
+        let mask = image.clip_mask();
+
+        assert!(
+            mask.read(0, 0).approx(&Color::new(1.0, 0.0, 0.0))
+                && mask.read(1, 0).approx(&Color::new(0.0, 0.0, 1.0))
+                && mask.read(2, 0).approx(&Color::black())
+        )
+    }
+
+    #[test]
+    fn luminance_statistics() {
+        let mut image = Image::new(4, 3);
+        for y in 0..3 {
+            for x in 0..4 {
+                image.write(x, y, Color::new(0.5, 0.5, 0.5));
+            }
+        }
+
+        let mut expected = vec![0; 10];
+        expected[5] = 12;
+
+        assert!(image.luminance_histogram(10) == expected && image.average_luminance().approx(&0.5))
+    }
+
     #[test]
     fn image_ppm_example_01() {
         let mut image = Image::new(5, 3);
@@ -193,4 +1161,111 @@ mod tests {
 
         assert_eq!(ppm, expected);
     }
+
+    #[test]
+    fn image_ppm_gamma() {
+        let mut image = Image::new(3, 1);
+
+        image.write(0, 0, Color::new(0.5, 0.0, 1.0));
+        image.write(1, 0, Color::new(0.2, 0.8, 1.5));
+        image.write(2, 0, Color::new(0.735, 0.1, -0.5));
+
+        let corrected = image.ppm_gamma(2.2);
+
+        assert!(
+            image.ppm_gamma(1.0) == image.ppm()
+                && corrected.starts_with("P3\n3 1\n255\n186 0 255 123 230 255 222 90 0")
+        )
+    }
+
+    #[test]
+    fn image_ppm_srgb() {
+        let mut image = Image::new(2, 1);
+
+        // a pixel half covered by a white edge
+        image.write(0, 0, Color::average(&[Color::black(), Color::white()]));
+        image.write(1, 0, Color::new(1.5, 0.0, -0.5));
+
+        assert!(image.ppm_srgb() == "P3\n2 1\n255\n188 188 188 255 0 0\n")
+    }
+
+    #[test]
+    fn image_ppm_binary() {
+        let mut image = Image::new(5, 3);
+
+        image.write(0, 0, Color::new(1.5, 0.0, 0.0));
+        image.write(2, 1, Color::new(0.0, 0.5, 0.0));
+        image.write(4, 2, Color::new(-0.5, 0.0, 1.0));
+
+        let ppm = image.ppm_binary();
+        let header = "P6\n5 3\n255\n";
+        let payload = &ppm[header.len()..];
+
+        assert!(
+            ppm.starts_with(header.as_bytes())
+                && payload.len() == 3 * image.hsize * image.vsize
+                && payload[0..3] == [255, 0, 0]
+                && payload[(5 + 2) * 3..(5 + 2) * 3 + 3] == [0, 128, 0]
+                && payload[payload.len() - 3..] == [0, 0, 255]
+        )
+    }
+
+    #[test]
+    fn image_png() {
+        let path = "image_png.png";
+        let mut image = Image::new(5, 3);
+
+        image.write(0, 0, Color::new(1.5, 0.0, 0.0));
+        image.write(2, 1, Color::new(0.0, 0.5, 0.0));
+        image.write(4, 2, Color::new(-0.5, 0.0, 1.0));
+        image.png(path).unwrap();
+
+        let decoder = png::Decoder::new(fs::File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        let _ = fs::remove_file(path);
+
+        let expected: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|color| {
+                let (r, g, b) = color.clamp();
+                vec![r, g, b]
+            })
+            .collect();
+
+        assert!(info.width == 5 && info.height == 3 && data[..info.buffer_size()] == expected[..])
+    }
+
+    #[test]
+    fn image_write_multilayer() {
+        let from = Vector::point(0.0, 0.0, -5.0);
+        let to = Vector::point(0.0, 0.0, 0.0);
+        let up = Vector::vector(0.0, 1.0, 0.0);
+
+        let camera = Camera::new(7, 5, PI / 2.0, Camera::transform(from, to, up));
+        let world = World::default();
+
+        let color = Image::par_render(&camera, &world);
+        let depth = DepthImage::par_render(&camera, &world);
+        let normals = Image::par_render_normals(&camera, &world, NormalSpace::World);
+
+        Image::write_multilayer("image_multilayer", &color, &depth, &normals).unwrap();
+
+        let dimensions: Vec<Option<(u32, u32)>> = ["", "_depth", "_normal"]
+            .iter()
+            .map(|suffix| {
+                let path = format!("image_multilayer{}.png", suffix);
+                let decoder = png::Decoder::new(fs::File::open(&path).ok()?);
+                let info = decoder.read_info().ok()?.info().size();
+                let _ = fs::remove_file(&path);
+                Some(info)
+            })
+            .collect();
+
+        assert!(dimensions
+            .iter()
+            .all(|dimension| *dimension == Some((7, 5))))
+    }
 }
@@ -1,9 +1,11 @@
 use crate::config::EPSILON;
-use crate::linalg::Vector;
+use crate::linalg::{Matrix, Vector};
+use crate::material::Material;
 use crate::ray::Ray;
 use crate::shape::Shape;
 
 use std::collections::HashSet;
+use std::sync::Mutex;
 
 #[derive(Debug, Clone, Copy)]
 pub struct State<'a> {
@@ -19,9 +21,30 @@ pub struct State<'a> {
     pub n1: f64,
     pub n2: f64,
     pub reflectance: f64,
+    pub footprint: Option<f64>,
+    pub frame: Option<Matrix>,
+    pub time: f64,
 }
 
-fn schlick(eye: Vector, normal: Vector, n1: f64, n2: f64) -> f64 {
+impl<'a> State<'a> {
+    pub fn pattern_point(&self) -> Vector {
+        let point = self
+            .frame
+            .map_or(self.over_point, |frame| frame * self.over_point);
+
+        self.shape.at_rest(point, self.time)
+    }
+
+    pub fn material(&self) -> &'a Material {
+        match &self.shape.material.backface {
+            Some(backface) if self.inside => backface,
+            _ => &self.shape.material,
+        }
+    }
+}
+
+// Fresnel reflectance for light going from n1 into n2, 1 under total internal reflection
+pub fn schlick(eye: Vector, normal: Vector, n1: f64, n2: f64) -> f64 {
     let mut cos = eye.dot(normal);
 
     if n1 > n2 {
@@ -44,17 +67,45 @@ pub struct Intersection<'a> {
     pub shape: &'a Shape,
     pub u: Option<f64>,
     pub v: Option<f64>,
+    pub frame: Option<Matrix>,
 }
 
 impl<'a> Intersection<'a> {
+    pub fn normal(&self, point: Vector, time: f64) -> Vector {
+        match self.frame {
+            Some(frame) => {
+                let object_normal =
+                    self.shape
+                        .normal(self.shape.at_rest(frame * point, time), self.u, self.v);
+
+                let mut world_normal = frame.transpose() * object_normal;
+                world_normal.w = 0.0;
+
+                world_normal.normalize()
+            }
+            None => self
+                .shape
+                .normal(self.shape.at_rest(point, time), self.u, self.v),
+        }
+    }
+
     pub fn prepare_state(self, ray: Ray, intersections: &Vec<Intersection>) -> State<'a> {
+        self.prepare_state_with(ray, intersections, EPSILON)
+    }
+
+    pub fn prepare_state_with(
+        self,
+        ray: Ray,
+        intersections: &Vec<Intersection>,
+        epsilon: f64,
+    ) -> State<'a> {
         let t = self.t;
         let shape = self.shape;
 
         let point = ray.position(t);
         let eye = -ray.direction;
 
-        let mut normal = shape.normal(point, self.u, self.v);
+        let mut normal = self.normal(point, ray.time);
         let mut inside = false;
 
         if normal.dot(eye) < 0.0 {
@@ -62,8 +113,8 @@ impl<'a> Intersection<'a> {
             inside = true;
         }
 
-        let over_point = point + (normal * EPSILON);
-        let under_point = point - (normal * EPSILON);
+        let over_point = point + (normal * epsilon);
+        let under_point = point - (normal * epsilon);
 
         let reflect = ray.direction.reflect(normal);
 
@@ -81,11 +132,20 @@ impl<'a> Intersection<'a> {
                     .unwrap_or(1.0);
             }
 
+            // open surfaces have unpaired hits and would never leave the stack, so they only
+            // change the medium at their own hit, in the direction given by their normal
+            if !intersection.shape.geometry.is_closed() {
+                if self == *intersection {
+                    n2 = shape.material.refractive_index;
+                    if inside {
+                        std::mem::swap(&mut n1, &mut n2);
+                    }
+                }
+                continue;
+            }
+
             if set.contains(intersection.shape) {
-                shapes = shapes
-                    .into_iter()
-                    .filter(|shape| *shape != intersection.shape)
-                    .collect();
+                shapes.retain(|shape| *shape != intersection.shape);
 
                 set.remove(intersection.shape);
             } else {
@@ -104,6 +164,13 @@ impl<'a> Intersection<'a> {
 
         let reflectance = schlick(eye, normal, n1, n2);
 
+        let footprint = ray.differentials.map(|differentials| {
+            let dx = differentials.origin_dx + differentials.dx * t;
+            let dy = differentials.origin_dy + differentials.dy * t;
+
+            dx.magnitude().max(dy.magnitude())
+        });
+
         State {
             t,
             shape,
@@ -117,6 +184,9 @@ impl<'a> Intersection<'a> {
             n1,
             n2,
             reflectance,
+            footprint,
+            frame: self.frame,
+            time: ray.time,
         }
     }
 
@@ -128,7 +198,7 @@ impl<'a> Intersection<'a> {
         intersections
             .iter()
             .find(|intersection| intersection.t >= 0.0)
-            .map(|intersection| *intersection)
+            .copied()
     }
 }
 
@@ -140,7 +210,29 @@ impl PartialEq for Intersection<'_> {
 
 impl Eq for Intersection<'_> {}
 
+// buffers are handed back cleared but keep their capacity for the next scanline or frame
+#[derive(Debug, Default)]
+pub struct IntersectionPool<'a> {
+    buffers: Mutex<Vec<Vec<Intersection<'a>>>>,
+}
+
+impl<'a> IntersectionPool<'a> {
+    pub fn new() -> IntersectionPool<'a> {
+        IntersectionPool::default()
+    }
+
+    pub fn take(&self) -> Vec<Intersection<'a>> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    pub fn give(&self, mut buffer: Vec<Intersection<'a>>) {
+        buffer.clear();
+        self.buffers.lock().unwrap().push(buffer);
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
@@ -150,6 +242,10 @@ mod tests {
     use crate::material::Material;
     use crate::shape::ShapeArgs;
 
+    use std::f64::consts::PI;
+
+    use test_case::test_case;
+
     #[test]
     fn aggregating_intersections() {
         let sphere = Shape::sphere(ShapeArgs::default());
@@ -159,6 +255,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -166,6 +263,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let mut is = vec![i2, i1];
@@ -189,6 +287,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -196,6 +295,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let mut is = vec![i2, i1];
@@ -215,6 +315,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -222,6 +323,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i2, i1];
@@ -240,6 +342,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -247,6 +350,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i3 = Intersection {
@@ -254,6 +358,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i4 = Intersection {
@@ -261,6 +366,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let mut is = vec![i1, i2, i3, i4];
@@ -280,6 +386,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -287,6 +394,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i2, i1];
@@ -301,6 +409,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let sphere = Shape::sphere(ShapeArgs::default());
@@ -310,6 +420,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = i.prepare_state(ray, &vec![]);
@@ -328,6 +439,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let sphere = Shape::sphere(ShapeArgs::default());
@@ -337,6 +450,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = i.prepare_state(ray, &vec![]);
@@ -349,6 +463,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let sphere = Shape::sphere(ShapeArgs::default());
@@ -358,6 +474,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = i.prepare_state(ray, &vec![]);
@@ -375,6 +492,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let sphere = Shape::sphere(ShapeArgs {
@@ -387,6 +506,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = intersection.prepare_state(ray, &vec![]);
@@ -401,6 +521,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 1.0, -1.0),
             direction: Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / 2.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let intersection = Intersection {
@@ -408,6 +530,7 @@ mod tests {
             shape: &shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = intersection.prepare_state(ray, &vec![]);
@@ -454,6 +577,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -4.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -461,36 +586,42 @@ mod tests {
             shape: &a,
             u: None,
             v: None,
+            frame: None,
         };
         let i2 = Intersection {
             t: 2.75,
             shape: &b,
             u: None,
             v: None,
+            frame: None,
         };
         let i3 = Intersection {
             t: 3.25,
             shape: &c,
             u: None,
             v: None,
+            frame: None,
         };
         let i4 = Intersection {
             t: 4.75,
             shape: &b,
             u: None,
             v: None,
+            frame: None,
         };
         let i5 = Intersection {
             t: 5.25,
             shape: &c,
             u: None,
             v: None,
+            frame: None,
         };
         let i6 = Intersection {
             t: 6.0,
             shape: &a,
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1, i2, i3, i4, i5, i6];
@@ -518,11 +649,64 @@ mod tests {
         )
     }
 
+    #[test]
+    fn finding_n1_and_n2_through_open_surface() {
+        let plane = Shape::plane(ShapeArgs {
+            transform: Matrix::translation(0.0, 0.0, -3.0) * Matrix::rotation_x(-PI / 2.0),
+            material: Material {
+                transparency: GLASS,
+                refractive_index: 1.2,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        });
+
+        let sphere = Shape::sphere(ShapeArgs {
+            material: Material {
+                transparency: GLASS,
+                refractive_index: 1.5,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        });
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let hit = |t: f64, shape| Intersection {
+            t,
+            shape,
+            u: None,
+            v: None,
+            frame: None,
+        };
+        let is = vec![hit(2.0, &plane), hit(4.0, &sphere), hit(6.0, &sphere)];
+
+        let state1 = is[0].prepare_state(ray, &is);
+        let state2 = is[1].prepare_state(ray, &is);
+        let state3 = is[2].prepare_state(ray, &is);
+
+        assert!(
+            state1.n1.approx(&1.0)
+                && state1.n2.approx(&1.2)
+                && state2.n1.approx(&1.0)
+                && state2.n2.approx(&1.5)
+                && state3.n1.approx(&1.5)
+                && state3.n2.approx(&1.0)
+        )
+    }
+
     #[test]
     fn under_point_offset_below_surface() {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let sphere = Shape::sphere(ShapeArgs {
@@ -535,6 +719,7 @@ mod tests {
             shape: &sphere,
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = intersection.prepare_state(ray, &vec![]);
@@ -542,6 +727,15 @@ mod tests {
         assert!(state.under_point.z > EPSILON / 2.0 && state.point.z < state.under_point.z)
     }
 
+    #[test_case(Vector::vector(0.0    , 0.0, -1.0), 1.0, 1.5, 0.04 ; "perpendicular"             )]
+    #[test_case(Vector::vector(1.0    , 0.0,  0.0), 1.0, 1.5, 1.0  ; "grazing"                   )]
+    #[test_case(Vector::vector(0.86603, 0.0, -0.5), 1.5, 1.0, 1.0  ; "total internal reflection" )]
+    fn schlick_reflectance(eye: Vector, n1: f64, n2: f64, expected: f64) {
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+
+        assert!(schlick(eye, normal, n1, n2).approx(&expected))
+    }
+
     #[test]
     fn schlick_approximation_under_total_internal_reflection() {
         let shape = Shape::sphere(ShapeArgs {
@@ -557,6 +751,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 2.0f64.sqrt() / 2.0),
             direction: Vector::vector(0.0, 1.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -564,6 +760,7 @@ mod tests {
             shape: &shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -571,6 +768,7 @@ mod tests {
             shape: &shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1, i2];
@@ -596,6 +794,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.0),
             direction: Vector::vector(0.0, 1.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -603,6 +803,7 @@ mod tests {
             shape: &shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -610,6 +811,7 @@ mod tests {
             shape: &shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1, i2];
@@ -635,6 +837,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.99, -2.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -642,6 +846,7 @@ mod tests {
             shape: &shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1];
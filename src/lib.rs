@@ -1,4 +1,6 @@
+pub mod animation;
 pub mod approx;
+pub mod bench;
 pub mod bounding_box;
 pub mod camera;
 pub mod color;
@@ -11,5 +13,9 @@ pub mod material;
 pub mod noise;
 pub mod obj;
 pub mod ray;
+pub mod scene;
 pub mod shape;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod texture;
 pub mod world;
@@ -1,8 +1,242 @@
 use crate::color::Color;
+use crate::config::EPSILON;
 use crate::linalg::Vector;
+use crate::ray::Ray;
+
+// cycled through so that neighbouring cells don't sample the same offset
+const JITTER: [f64; 7] = [0.7, 0.3, 0.9, 0.1, 0.5, 0.2, 0.8];
 
 #[derive(Debug, Clone, Copy)]
 pub struct PointLight {
     pub intensity: Color,
     pub origin: Vector,
 }
+
+#[derive(Debug, Clone, Copy)]
+pub struct AreaLight {
+    pub intensity: Color,
+    pub origin: Vector,
+    pub uvec: Vector,
+    pub vvec: Vector,
+    pub usteps: usize,
+    pub vsteps: usize,
+}
+
+impl AreaLight {
+    pub fn samples(&self) -> usize {
+        self.usteps * self.vsteps
+    }
+
+    pub fn point_on_light(&self, u: usize, v: usize) -> Vector {
+        let index = v * self.usteps + u;
+        let ju = JITTER[(2 * index) % JITTER.len()];
+        let jv = JITTER[(2 * index + 1) % JITTER.len()];
+
+        self.origin
+            + self.uvec * ((u as f64 + ju) / self.usteps as f64)
+            + self.vvec * ((v as f64 + jv) / self.vsteps as f64)
+    }
+
+    pub fn center(&self) -> Vector {
+        self.origin + self.uvec * 0.5 + self.vvec * 0.5
+    }
+
+    pub fn area(&self) -> f64 {
+        self.uvec.cross(self.vvec).magnitude()
+    }
+
+    pub fn normal(&self) -> Vector {
+        self.uvec.cross(self.vvec).normalize()
+    }
+
+    pub fn intersect(&self, ray: Ray) -> Option<f64> {
+        let normal = self.uvec.cross(self.vvec);
+        let denom = normal.dot(ray.direction);
+        if denom.abs() < EPSILON {
+            return None;
+        }
+
+        let t = normal.dot(self.origin - ray.origin) / denom;
+        if t <= 0.0 {
+            return None;
+        }
+
+        // the edges need not be orthogonal, so project onto their duals
+        let p = ray.position(t) - self.origin;
+        let u = p.dot(self.vvec.cross(normal)) / self.uvec.dot(self.vvec.cross(normal));
+        let v = p.dot(normal.cross(self.uvec)) / self.vvec.dot(normal.cross(self.uvec));
+
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpotLight {
+    pub intensity: Color,
+    pub origin: Vector,
+    pub direction: Vector,
+    pub inner_angle: f64,
+    pub outer_angle: f64,
+}
+
+impl SpotLight {
+    pub fn attenuation(&self, point: Vector) -> f64 {
+        let cos = (point - self.origin)
+            .normalize()
+            .dot(self.direction.normalize());
+        let angle = cos.clamp(-1.0, 1.0).acos();
+
+        if angle <= self.inner_angle {
+            1.0
+        } else if angle >= self.outer_angle {
+            0.0
+        } else {
+            let t = (self.outer_angle - angle) / (self.outer_angle - self.inner_angle);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    Point(PointLight),
+    Area(AreaLight),
+    Spot(SpotLight),
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Light {
+        Light::Point(light)
+    }
+}
+
+impl From<AreaLight> for Light {
+    fn from(light: AreaLight) -> Light {
+        Light::Area(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Light {
+        Light::Spot(light)
+    }
+}
+
+impl Light {
+    pub fn intensity(&self) -> Color {
+        match self {
+            Light::Point(light) => light.intensity,
+            Light::Area(light) => light.intensity,
+            Light::Spot(light) => light.intensity,
+        }
+    }
+
+    pub fn origin(&self) -> Vector {
+        match self {
+            Light::Point(light) => light.origin,
+            Light::Area(light) => light.center(),
+            Light::Spot(light) => light.origin,
+        }
+    }
+
+    pub fn samples(&self) -> usize {
+        match self {
+            Light::Area(light) => light.samples(),
+            _ => 1,
+        }
+    }
+
+    pub fn sample(&self, index: usize) -> Vector {
+        match self {
+            Light::Area(light) => light.point_on_light(index % light.usteps, index / light.usteps),
+            _ => self.origin(),
+        }
+    }
+
+    pub fn attenuation(&self, point: Vector) -> f64 {
+        match self {
+            Light::Spot(light) => light.attenuation(point),
+            _ => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_unit)]
+mod tests {
+    use super::*;
+
+    use crate::approx::Approx;
+
+    use test_case::test_case;
+
+    fn area_light() -> AreaLight {
+        AreaLight {
+            intensity: Color::white(),
+            origin: Vector::point(0.0, 0.0, 0.0),
+            uvec: Vector::vector(2.0, 0.0, 0.0),
+            vvec: Vector::vector(0.0, 0.0, 1.0),
+            usteps: 4,
+            vsteps: 2,
+        }
+    }
+
+    // 10 * tan(0.3) lies halfway into the falloff band
+    #[test_case(Vector::point(0.0     , 0.0,   0.0), 1.0 ; "dead center")]
+    #[test_case(Vector::point(1.0     , 0.0,   0.0), 1.0 ; "inside inner cone")]
+    #[test_case(Vector::point(3.093362, 0.0,   0.0), 0.5 ; "falloff band")]
+    #[test_case(Vector::point(5.0     , 0.0,   0.0), 0.0 ; "outside")]
+    #[test_case(Vector::point(0.0     , 0.0, -20.0), 0.0 ; "behind")]
+    fn spot_light_attenuation(point: Vector, expected: f64) {
+        let light = Light::Spot(SpotLight {
+            intensity: Color::white(),
+            origin: Vector::point(0.0, 0.0, -10.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            inner_angle: 0.2,
+            outer_angle: 0.4,
+        });
+
+        assert!(light.attenuation(point).approx(&expected))
+    }
+
+    #[test_case(Vector::point(1.0, 1.0, 0.5), Vector::vector(0.0, -1.0, 0.0), Some(1.0) ; "hit")]
+    #[test_case(Vector::point(3.0, 1.0, 0.5), Vector::vector(0.0, -1.0, 0.0), None      ; "beside")]
+    #[test_case(Vector::point(1.0, 1.0, 0.5), Vector::vector(0.0,  1.0, 0.0), None      ; "behind")]
+    #[test_case(Vector::point(1.0, 1.0, 0.5), Vector::vector(1.0,  0.0, 0.0), None      ; "parallel")]
+    fn area_light_intersect(origin: Vector, direction: Vector, expected: Option<f64>) {
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
+
+        assert!(area_light().intersect(ray).approx(&expected))
+    }
+
+    #[test]
+    fn area_light_center() {
+        let light: Light = area_light().into();
+
+        assert!(light.samples() == 8 && light.origin().approx(&Vector::point(1.0, 0.0, 0.5)))
+    }
+
+    #[test_case(0, 0 ; "first cell")]
+    #[test_case(3, 0 ; "last column")]
+    #[test_case(2, 1 ; "second row")]
+    fn point_on_light_in_cell(u: usize, v: usize) {
+        let point = area_light().point_on_light(u, v);
+
+        assert!(
+            point.x > u as f64 * 0.5
+                && point.x < (u + 1) as f64 * 0.5
+                && point.z > v as f64 * 0.5
+                && point.z < (v + 1) as f64 * 0.5
+                && point.y.approx(&0.0)
+        )
+    }
+}
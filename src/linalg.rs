@@ -1,5 +1,8 @@
 mod matrix;
+pub mod polynomial;
+mod square;
 mod vector;
 
 pub use self::matrix::Matrix;
+pub use self::square::{Matrix2, Matrix3, SquareMatrix};
 pub use self::vector::Vector;
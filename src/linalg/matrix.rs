@@ -1,4 +1,5 @@
 use crate::approx::Approx;
+use crate::linalg::square::{self, Matrix3};
 use crate::linalg::Vector;
 
 use std::fmt;
@@ -99,6 +100,44 @@ impl Matrix {
         Matrix::new(data)
     }
 
+    fn basis(from: Vector, to: Vector, up: Vector) -> (Vector, Vector, Vector) {
+        let forward = (to - from).normalize();
+
+        let up = up.normalize();
+        let left = forward.cross(up);
+        let up = left.cross(forward);
+
+        (left, up, forward)
+    }
+
+    pub fn view(from: Vector, to: Vector, up: Vector) -> Matrix {
+        let (left, up, forward) = Matrix::basis(from, to, up);
+
+        #[rustfmt::skip]
+        let orientation = Matrix::new([
+            [     left.x,     left.y,     left.z, 0.0 ],
+            [       up.x,       up.y,       up.z, 0.0 ],
+            [ -forward.x, -forward.y, -forward.z, 0.0 ],
+            [        0.0,        0.0,        0.0, 1.0 ],
+        ]);
+
+        orientation * Matrix::translation(-from.x, -from.y, -from.z)
+    }
+
+    pub fn orient(from: Vector, to: Vector, up: Vector) -> Matrix {
+        let (left, up, forward) = Matrix::basis(from, to, up);
+        let (left, up) = (left.normalize(), up.normalize());
+
+        #[rustfmt::skip]
+        let data = [
+            [ left.x, up.x, -forward.x, from.x ],
+            [ left.y, up.y, -forward.y, from.y ],
+            [ left.z, up.z, -forward.z, from.z ],
+            [    0.0,  0.0,        0.0,    1.0 ],
+        ];
+        Matrix::new(data)
+    }
+
     pub fn translate(self, x: f64, y: f64, z: f64) -> Matrix {
         Matrix::translation(x, y, z) * self
     }
@@ -126,9 +165,9 @@ impl Matrix {
     pub fn transpose(self) -> Matrix {
         let mut data = [[0.0f64; N]; N];
 
-        for row in 0..N {
-            for col in 0..N {
-                data[col][row] = self.data[row][col];
+        for (row, values) in self.data.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                data[col][row] = *value;
             }
         }
 
@@ -155,11 +194,36 @@ impl Matrix {
         s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0
     }
 
+    pub fn submatrix(self, row: usize, col: usize) -> Matrix3 {
+        square::submatrix(&self.data, row, col)
+    }
+
+    pub fn minor(self, row: usize, col: usize) -> f64 {
+        self.submatrix(row, col).determinant()
+    }
+
+    pub fn cofactor(self, row: usize, col: usize) -> f64 {
+        let minor = self.minor(row, col);
+        if (row + col).is_multiple_of(2) {
+            minor
+        } else {
+            -minor
+        }
+    }
+
     pub fn is_invertible(self) -> bool {
         !self.determinant().approx(&0.0)
     }
 
+    pub fn is_finite(self) -> bool {
+        self.data.iter().flatten().all(|x| x.is_finite())
+    }
+
     pub fn inverse(self) -> Matrix {
+        self.try_inverse().expect("matrix is not invertible")
+    }
+
+    pub fn try_inverse(self) -> Option<Matrix> {
         let m = &self.data;
 
         let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
@@ -178,7 +242,9 @@ impl Matrix {
 
         let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
 
-        assert!(det != 0.0);
+        if det == 0.0 {
+            return None;
+        }
 
         let adj00 = (m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) / det;
         let adj02 = (m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) / det;
@@ -198,12 +264,12 @@ impl Matrix {
         let adj30 = (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) / det;
         let adj32 = (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) / det;
 
-        Matrix::new([
+        Some(Matrix::new([
             [adj00, adj01, adj02, adj03],
             [adj10, adj11, adj12, adj13],
             [adj20, adj21, adj22, adj23],
             [adj30, adj31, adj32, adj33],
-        ])
+        ]))
     }
 }
 
@@ -298,6 +364,7 @@ impl Approx<Matrix> for Matrix {
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
@@ -347,7 +414,7 @@ mod tests {
             [ 13.5, 14.5, 15.5, 16.5 ],
         ];
 
-        let m1 = Matrix::new(data.clone());
+        let m1 = Matrix::new(data);
         let m2 = Matrix::new(data);
 
         assert!(m1.approx(&m2))
@@ -528,6 +595,15 @@ mod tests {
         assert!(m.determinant().approx(&0.0) && !m.is_invertible())
     }
 
+    #[test]
+    fn is_finite() {
+        let mut m = Matrix::translation(1.0, 2.0, 3.0);
+        let finite = m.is_finite();
+        m[1][2] = f64::NAN;
+
+        assert!(finite && !m.is_finite())
+    }
+
     #[test_case(
         Matrix::new([
             [ -5.0,  2.0,  6.0, -8.0 ],
@@ -577,6 +653,16 @@ mod tests {
         assert!(matrix.inverse().approx(&expected))
     }
 
+    #[test_case(Matrix::scaling(0.0, 1.0, 1.0),                       false ; "zero scale")]
+    #[test_case(Matrix::scaling(1.0, 1.0, 0.0).translate(1.0, 2.0, 3.0), false ; "zero scale translated")]
+    #[test_case(Matrix::scaling(2.0, 3.0, 4.0),                       true  ; "scale")]
+    fn try_inverse(matrix: Matrix, invertible: bool) {
+        match matrix.try_inverse() {
+            Some(inv) => assert!(invertible && (matrix * inv).approx(&Matrix::id())),
+            None => assert!(!invertible),
+        }
+    }
+
     #[test]
     fn multiplication_inverse_identity() {
         #[rustfmt::skip]
@@ -747,4 +833,41 @@ mod tests {
 
         assert!((transform * p1).approx(&p2))
     }
+
+    #[test]
+    fn orient_default() {
+        let orientation = Matrix::orient(
+            Vector::point(0.0, 0.0, 0.0),
+            Vector::point(0.0, 0.0, -1.0),
+            Vector::vector(0.0, 1.0, 0.0),
+        );
+
+        assert!(orientation.approx(&Matrix::id()))
+    }
+
+    #[test]
+    fn orient_is_inverse_view() {
+        let from = Vector::point(1.0, 3.0, 2.0);
+        let to = Vector::point(4.0, -2.0, 8.0);
+        let up = Vector::vector(1.0, 1.0, 0.0);
+
+        let orientation = Matrix::orient(from, to, up);
+        let view = Matrix::view(from, to, up);
+
+        assert!((view * orientation * Vector::point(0.0, 0.0, -2.0))
+            .approx(&Vector::point(0.0, 0.0, -2.0)))
+    }
+
+    #[test]
+    fn orient_cone_axis() {
+        let from = Vector::point(1.0, 2.0, 3.0);
+        let to = Vector::point(-2.0, 6.0, 3.0);
+
+        // the cone's axis is local y, rotate it onto local -z first
+        let transform = Matrix::orient(from, to, Vector::vector(0.0, 1.0, 0.0))
+            * Matrix::rotation_x(-std::f64::consts::PI / 2.0);
+
+        assert!((transform * Vector::point(0.0, 0.0, 0.0)).approx(&from));
+        assert!((transform * Vector::point(0.0, 5.0, 0.0)).approx(&to))
+    }
 }
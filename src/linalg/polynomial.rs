@@ -0,0 +1,106 @@
+// coefficients are ordered from the highest to the lowest degree
+
+fn eval(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().fold(0.0, |acc, c| acc * x + c)
+}
+
+fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    let degree = coefficients.len() - 1;
+
+    coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * (degree - i) as f64)
+        .collect()
+}
+
+fn bisect(coefficients: &[f64], mut lo: f64, mut hi: f64) -> f64 {
+    let mut f_lo = eval(coefficients, lo);
+
+    for _ in 0..100 {
+        let mid = 0.5 * (lo + hi);
+        if mid <= lo || mid >= hi {
+            break;
+        }
+
+        let f_mid = eval(coefficients, mid);
+        if f_mid == 0.0 {
+            return mid;
+        }
+
+        if (f_mid < 0.0) == (f_lo < 0.0) {
+            lo = mid;
+            f_lo = f_mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    0.5 * (lo + hi)
+}
+
+pub fn real_roots(coefficients: &[f64]) -> Vec<f64> {
+    let scale = coefficients.iter().fold(0.0_f64, |max, c| max.max(c.abs()));
+    let leading = coefficients
+        .iter()
+        .position(|c| c.abs() > scale * 1e-12)
+        .unwrap_or(coefficients.len());
+    let coefficients = &coefficients[leading..];
+
+    match coefficients.len() {
+        0 | 1 => vec![],
+        2 => vec![-coefficients[1] / coefficients[0]],
+        _ => {
+            // roots are separated by the roots of the derivative, so every interval
+            // between consecutive critical points holds at most one of them
+            let bound = 1.0
+                + coefficients[1..]
+                    .iter()
+                    .fold(0.0_f64, |max, c| max.max((c / coefficients[0]).abs()));
+
+            let mut points = vec![-bound];
+            points.extend(
+                real_roots(&derivative(coefficients))
+                    .into_iter()
+                    .filter(|x| -bound < *x && *x < bound),
+            );
+            points.push(bound);
+
+            let mut roots = vec![];
+            for window in points.windows(2) {
+                let (lo, hi) = (window[0], window[1]);
+                let (f_lo, f_hi) = (eval(coefficients, lo), eval(coefficients, hi));
+
+                if f_lo == 0.0 {
+                    roots.push(lo);
+                } else if (f_lo < 0.0) != (f_hi < 0.0) && f_hi != 0.0 {
+                    roots.push(bisect(coefficients, lo, hi));
+                }
+            }
+
+            roots
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_unit)]
+mod tests {
+    use super::*;
+
+    use crate::approx::Approx;
+
+    use test_case::test_case;
+
+    #[test_case(&[1.0, -3.0], vec![3.0] ; "linear")]
+    #[test_case(&[1.0, 0.0, 1.0], vec![] ; "no real roots")]
+    #[test_case(&[0.0, 1.0, -3.0, 2.0], vec![1.0, 2.0] ; "vanishing leading coefficient")]
+    #[test_case(&[1.0, -10.0, 35.0, -50.0, 24.0], vec![1.0, 2.0, 3.0, 4.0] ; "quartic")]
+    #[test_case(&[2.0, 0.0, -2.0, 0.0, 0.0], vec![-1.0, 0.0, 1.0] ; "quartic with double root")]
+    fn real_roots(coefficients: &[f64], expected: Vec<f64>) {
+        let mut roots = super::real_roots(coefficients);
+        roots.dedup_by(|a, b| a.approx(b));
+
+        assert!(roots.approx(&expected))
+    }
+}
@@ -0,0 +1,190 @@
+use crate::approx::Approx;
+
+use std::ops;
+
+// small square matrices for the cofactor expansion of the 4x4 `Matrix`
+#[derive(Debug, Clone, Copy)]
+pub struct SquareMatrix<const M: usize> {
+    data: [[f64; M]; M],
+}
+
+pub type Matrix2 = SquareMatrix<2>;
+pub type Matrix3 = SquareMatrix<3>;
+
+// drops `row` and `col`, K has to be M - 1
+pub(super) fn submatrix<const M: usize, const K: usize>(
+    data: &[[f64; M]; M],
+    row: usize,
+    col: usize,
+) -> SquareMatrix<K> {
+    assert!(K + 1 == M && row < M && col < M);
+
+    let mut sub = [[0.0f64; K]; K];
+
+    for (r, values) in data.iter().enumerate().filter(|(r, _)| *r != row) {
+        for (c, value) in values.iter().enumerate().filter(|(c, _)| *c != col) {
+            sub[r - (r > row) as usize][c - (c > col) as usize] = *value;
+        }
+    }
+
+    SquareMatrix::new(sub)
+}
+
+impl<const M: usize> SquareMatrix<M> {
+    pub fn new(data: [[f64; M]; M]) -> SquareMatrix<M> {
+        SquareMatrix { data }
+    }
+}
+
+impl Matrix2 {
+    pub fn determinant(self) -> f64 {
+        let m = &self.data;
+
+        m[0][0] * m[1][1] - m[0][1] * m[1][0]
+    }
+}
+
+impl Matrix3 {
+    pub fn submatrix(self, row: usize, col: usize) -> Matrix2 {
+        submatrix(&self.data, row, col)
+    }
+
+    pub fn minor(self, row: usize, col: usize) -> f64 {
+        self.submatrix(row, col).determinant()
+    }
+
+    pub fn cofactor(self, row: usize, col: usize) -> f64 {
+        let minor = self.minor(row, col);
+        if (row + col).is_multiple_of(2) {
+            minor
+        } else {
+            -minor
+        }
+    }
+
+    pub fn determinant(self) -> f64 {
+        (0..3)
+            .map(|col| self.data[0][col] * self.cofactor(0, col))
+            .sum()
+    }
+}
+
+impl<const M: usize> ops::Index<usize> for SquareMatrix<M> {
+    type Output = [f64; M];
+
+    fn index(&self, row: usize) -> &[f64; M] {
+        &self.data[row]
+    }
+}
+
+impl<const M: usize> Approx<SquareMatrix<M>> for SquareMatrix<M> {
+    fn approx(&self, other: &SquareMatrix<M>) -> bool {
+        self.data
+            .iter()
+            .flatten()
+            .zip(other.data.iter().flatten())
+            .all(|(a, b)| a.approx(b))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_unit)]
+mod tests {
+    use super::*;
+
+    use crate::linalg::Matrix;
+
+    use test_case::test_case;
+
+    #[test]
+    fn determinant_2x2() {
+        let m = Matrix2::new([[1.0, 5.0], [-3.0, 2.0]]);
+
+        assert!(m.determinant().approx(&17.0))
+    }
+
+    #[test]
+    fn submatrix_3x3() {
+        #[rustfmt::skip]
+        let m = Matrix3::new([
+            [  1.0, 5.0,  0.0 ],
+            [ -3.0, 2.0,  7.0 ],
+            [  0.0, 6.0, -3.0 ],
+        ]);
+
+        assert!(m
+            .submatrix(0, 2)
+            .approx(&Matrix2::new([[-3.0, 2.0], [0.0, 6.0]])))
+    }
+
+    #[test]
+    fn submatrix_4x4() {
+        #[rustfmt::skip]
+        let m = Matrix::new([
+            [ -6.0, 1.0,  1.0, 6.0 ],
+            [ -8.0, 5.0,  8.0, 6.0 ],
+            [ -1.0, 0.0,  8.0, 2.0 ],
+            [ -7.0, 1.0, -1.0, 1.0 ],
+        ]);
+
+        #[rustfmt::skip]
+        let expected = Matrix3::new([
+            [ -6.0,  1.0, 6.0 ],
+            [ -8.0,  8.0, 6.0 ],
+            [ -7.0, -1.0, 1.0 ],
+        ]);
+
+        assert!(m.submatrix(2, 1).approx(&expected))
+    }
+
+    #[test_case(0, 0, -12.0, -12.0 ; "upper left"  )]
+    #[test_case(1, 0,  25.0, -25.0 ; "sign flipped")]
+    fn minor_cofactor_3x3(row: usize, col: usize, minor: f64, cofactor: f64) {
+        #[rustfmt::skip]
+        let m = Matrix3::new([
+            [ 3.0,  5.0,  0.0 ],
+            [ 2.0, -1.0, -7.0 ],
+            [ 6.0, -1.0,  5.0 ],
+        ]);
+
+        assert!(m.minor(row, col).approx(&minor) && m.cofactor(row, col).approx(&cofactor))
+    }
+
+    #[test]
+    fn determinant_3x3() {
+        #[rustfmt::skip]
+        let m = Matrix3::new([
+            [  1.0, 2.0,  6.0 ],
+            [ -5.0, 8.0, -4.0 ],
+            [  2.0, 6.0,  4.0 ],
+        ]);
+
+        assert!(
+            m.cofactor(0, 0).approx(&56.0)
+                && m.cofactor(0, 1).approx(&12.0)
+                && m.cofactor(0, 2).approx(&-46.0)
+                && m.determinant().approx(&-196.0)
+        )
+    }
+
+    #[test]
+    fn determinant_4x4_cofactors() {
+        #[rustfmt::skip]
+        let m = Matrix::new([
+            [ -2.0, -8.0,  3.0,  5.0 ],
+            [ -3.0,  1.0,  7.0,  3.0 ],
+            [  1.0,  2.0, -9.0,  6.0 ],
+            [ -6.0,  7.0,  7.0, -9.0 ],
+        ]);
+
+        let expansion: f64 = (0..4).map(|col| m[0][col] * m.cofactor(0, col)).sum();
+
+        assert!(
+            m.cofactor(0, 0).approx(&690.0)
+                && m.cofactor(0, 1).approx(&447.0)
+                && m.cofactor(0, 2).approx(&210.0)
+                && m.cofactor(0, 3).approx(&51.0)
+                && expansion.approx(&m.determinant())
+        )
+    }
+}
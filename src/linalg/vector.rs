@@ -1,6 +1,7 @@
 use crate::approx::Approx;
 use crate::linalg::Matrix;
 
+use std::f64::consts::PI;
 use std::fmt;
 use std::ops;
 
@@ -17,6 +18,7 @@ impl Vector {
         Vector { x, y, z, w: 1.0 }
     }
 
+    #[allow(clippy::self_named_constructors)]
     pub fn vector(x: f64, y: f64, z: f64) -> Vector {
         Vector { x, y, z, w: 0.0 }
     }
@@ -75,6 +77,38 @@ impl Vector {
     pub fn reflect(self, normal: Vector) -> Vector {
         self - normal * (2.0 * self.dot(normal))
     }
+
+    pub fn lerp(self, other: Vector, t: f64) -> Vector {
+        self.lerp_unclamped(other, t.clamp(0.0, 1.0))
+    }
+
+    // weighting both ends keeps t = 0 and t = 1 exact
+    pub fn lerp_unclamped(self, other: Vector, t: f64) -> Vector {
+        self * (1.0 - t) + other * t
+    }
+
+    pub fn slerp(self, other: Vector, t: f64) -> Vector {
+        let (from, to) = (self.normalize(), other.normalize());
+        let angle = from.dot(to).clamp(-1.0, 1.0).acos();
+
+        if angle.sin().approx(&0.0) {
+            if from.dot(to) > 0.0 {
+                return from.lerp(to, t).normalize();
+            }
+
+            // every great circle joins opposite directions, turn about any axis perpendicular to both
+            let axis = if from.x.abs() < 0.9 {
+                Vector::vector(1.0, 0.0, 0.0)
+            } else {
+                Vector::vector(0.0, 1.0, 0.0)
+            };
+            let perpendicular = from.cross(axis).normalize();
+
+            return from * (t * PI).cos() + perpendicular * (t * PI).sin();
+        }
+
+        (from * ((1.0 - t) * angle).sin() + to * (t * angle).sin()) * (1.0 / angle.sin())
+    }
 }
 
 impl fmt::Display for Vector {
@@ -89,6 +123,20 @@ impl Approx<Vector> for Vector {
     }
 }
 
+impl ops::Index<usize> for Vector {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            3 => &self.w,
+            _ => panic!("vector index out of bounds: {}", axis),
+        }
+    }
+}
+
 impl ops::Add<Vector> for Vector {
     type Output = Vector;
 
@@ -155,6 +203,7 @@ impl ops::Div<f64> for Vector {
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
@@ -291,4 +340,59 @@ mod tests {
 
         assert!(reflect.approx(&expected));
     }
+
+    #[test]
+    fn lerp() {
+        let from = Vector::point(1.0, 2.0, 3.0);
+        let to = Vector::point(3.0, -2.0, 5.0);
+
+        assert!(
+            from.lerp(to, 0.0).approx(&from)
+                && from.lerp(to, 0.5).approx(&Vector::point(2.0, 0.0, 4.0))
+                && from.lerp(to, 1.0).approx(&to)
+        )
+    }
+
+    #[test_case(0.0 ; "start")]
+    #[test_case(1.0 ; "end"  )]
+    fn lerp_exact(t: f64) {
+        let from = Vector::point(0.1, 0.2, 0.3);
+        let to = Vector::point(0.7, -1.3, 1e9);
+        let lerp = from.lerp(to, t);
+        let expected = if t == 0.0 { from } else { to };
+
+        assert!(lerp.x == expected.x && lerp.y == expected.y && lerp.z == expected.z)
+    }
+
+    #[test_case(-0.5, Vector::point(1.0, 2.0, 3.0), Vector::point(0.0, 4.0, 2.0) ; "before")]
+    #[test_case( 1.5, Vector::point(3.0, -2.0, 5.0), Vector::point(4.0, -4.0, 6.0) ; "after" )]
+    fn lerp_clamped(t: f64, clamped: Vector, unclamped: Vector) {
+        let from = Vector::point(1.0, 2.0, 3.0);
+        let to = Vector::point(3.0, -2.0, 5.0);
+
+        assert!(from.lerp(to, t).approx(&clamped) && from.lerp_unclamped(to, t).approx(&unclamped))
+    }
+
+    #[test_case(0.0, Vector::vector(1.0, 0.0, 0.0)                                     ; "start"   )]
+    #[test_case(0.5, Vector::vector(2.0f64.sqrt() / 2.0, 2.0f64.sqrt() / 2.0, 0.0) ; "bisector")]
+    #[test_case(1.0, Vector::vector(0.0, 1.0, 0.0)                                     ; "end"     )]
+    fn slerp(t: f64, expected: Vector) {
+        let from = Vector::vector(1.0, 0.0, 0.0);
+        let to = Vector::vector(0.0, 1.0, 0.0);
+
+        assert!(from.slerp(to, t).approx(&expected))
+    }
+
+    #[test_case(0.0  ; "start"  )]
+    #[test_case(0.25 ; "quarter")]
+    #[test_case(0.5  ; "halfway")]
+    #[test_case(1.0  ; "end"    )]
+    fn slerp_opposite(t: f64) {
+        let from = Vector::vector(0.0, 0.0, 1.0);
+        let to = Vector::vector(0.0, 0.0, -1.0);
+
+        let slerp = from.slerp(to, t);
+
+        assert!(slerp.magnitude().approx(&1.0) && slerp.dot(from).approx(&(t * PI).cos()))
+    }
 }
@@ -2,8 +2,11 @@ use crate::approx::Approx;
 use crate::color::Color;
 use crate::linalg::{Matrix, Vector};
 use crate::noise::Noise;
+use crate::texture::{ImageTexture, UvMapping};
 
+use std::collections::HashMap;
 use std::default::Default;
+use std::sync::Arc;
 
 pub mod consts {
     pub mod transparency {
@@ -13,6 +16,10 @@ pub mod consts {
         pub const GLASS: f64 = 1.52;
         pub const DIAMOND: f64 = 2.417;
     }
+
+    pub mod alpha {
+        pub const THRESHOLD: f64 = 0.5;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -20,11 +27,22 @@ pub struct Material {
     pub pattern: Pattern,
     pub ambient: f64,
     pub diffuse: f64,
+    pub wrap: f64,
     pub specular: f64,
+    pub specular_color: Color,
     pub shininess: f64,
     pub reflective: f64,
+    pub roughness: f64,
     pub transparency: f64,
     pub refractive_index: f64,
+    pub opacity: f64,
+    pub normalized_diffuse: bool,
+    pub shadow_catcher: bool,
+    pub dispersion: f64,
+    pub emission: Color,
+    pub alpha_mask: Option<Pattern>,
+    pub reflection_map: Option<Arc<ImageTexture>>,
+    pub backface: Option<Box<Material>>,
 }
 
 impl Default for Material {
@@ -33,11 +51,22 @@ impl Default for Material {
             pattern: Pattern::plain(Color::white()),
             ambient: 0.1,
             diffuse: 0.9,
+            wrap: 0.0,
             specular: 0.9,
+            specular_color: Color::white(),
             shininess: 200.0,
             reflective: 0.0,
+            roughness: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            opacity: 1.0,
+            normalized_diffuse: false,
+            shadow_catcher: false,
+            dispersion: 0.0,
+            emission: Color::black(),
+            alpha_mask: None,
+            reflection_map: None,
+            backface: None,
         }
     }
 }
@@ -47,11 +76,57 @@ impl Approx<Material> for Material {
         self.pattern.approx(&other.pattern)
             && self.ambient.approx(&other.ambient)
             && self.diffuse.approx(&other.diffuse)
+            && self.wrap.approx(&other.wrap)
             && self.specular.approx(&other.specular)
+            && self.specular_color.approx(&other.specular_color)
             && self.shininess.approx(&other.shininess)
             && self.reflective.approx(&other.reflective)
+            && self.roughness.approx(&other.roughness)
             && self.transparency.approx(&other.transparency)
             && self.refractive_index.approx(&other.refractive_index)
+            && self.opacity.approx(&other.opacity)
+            && self.normalized_diffuse.approx(&other.normalized_diffuse)
+            && self.shadow_catcher.approx(&other.shadow_catcher)
+            && self.dispersion.approx(&other.dispersion)
+            && self.emission.approx(&other.emission)
+            && self.alpha_mask.approx(&other.alpha_mask)
+            && self.backface.approx(&other.backface)
+            && match (&self.reflection_map, &other.reflection_map) {
+                (Some(map), Some(other)) => Arc::ptr_eq(map, other),
+                (map, other) => map.is_none() && other.is_none(),
+            }
+    }
+}
+
+impl Material {
+    pub fn named(library: &MaterialLibrary, name: &str) -> Option<Material> {
+        library.get(name).cloned()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MaterialLibrary {
+    materials: HashMap<String, Material>,
+}
+
+impl From<HashMap<String, Material>> for MaterialLibrary {
+    fn from(materials: HashMap<String, Material>) -> MaterialLibrary {
+        MaterialLibrary { materials }
+    }
+}
+
+impl MaterialLibrary {
+    pub fn new() -> MaterialLibrary {
+        MaterialLibrary::default()
+    }
+
+    // registering a taken name replaces the old material
+    pub fn register(&mut self, name: &str, material: Material) {
+        self.materials.insert(name.to_string(), material);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Material> {
+        self.materials.get(name)
     }
 }
 
@@ -62,6 +137,8 @@ pub enum Pattern {
     Plain   { color: Color },
     Jitter  { kind: JitterKind, noise: Noise, pattern: Box<Pattern> },
     Mixture { kind: MixtureKind, transform_inv: Matrix, left: Box<Pattern>, right: Box<Pattern> },
+    Image   { texture: Arc<ImageTexture>, mapping: UvMapping },
+    UvCheckers { u_squares: usize, v_squares: usize, mapping: UvMapping, left: Box<Pattern>, right: Box<Pattern> },
 }
 
 impl Approx<Pattern> for Pattern {
@@ -102,6 +179,38 @@ impl Approx<Pattern> for Pattern {
                     && sleft.approx(oleft)
                     && sright.approx(oright)
             }
+            (
+                Pattern::Image {
+                    texture: stexture,
+                    mapping: smapping,
+                },
+                Pattern::Image {
+                    texture: otexture,
+                    mapping: omapping,
+                },
+            ) => Arc::ptr_eq(stexture, otexture) && smapping.approx(omapping),
+            (
+                Pattern::UvCheckers {
+                    u_squares: su,
+                    v_squares: sv,
+                    mapping: smapping,
+                    left: sleft,
+                    right: sright,
+                },
+                Pattern::UvCheckers {
+                    u_squares: ou,
+                    v_squares: ov,
+                    mapping: omapping,
+                    left: oleft,
+                    right: oright,
+                },
+            ) => {
+                su == ou
+                    && sv == ov
+                    && smapping.approx(omapping)
+                    && sleft.approx(oleft)
+                    && sright.approx(oright)
+            }
             (_, _) => false,
         }
     }
@@ -138,7 +247,11 @@ impl Pattern {
     }
 
     pub fn blend(transform: Matrix, left: Pattern, right: Pattern) -> Pattern {
-        Pattern::new_mixture(MixtureKind::Blend, transform, left, right)
+        Pattern::blend_weighted(transform, 0.5, left, right)
+    }
+
+    pub fn blend_weighted(transform: Matrix, ratio: f64, left: Pattern, right: Pattern) -> Pattern {
+        Pattern::new_mixture(MixtureKind::Blend { ratio }, transform, left, right)
     }
 
     pub fn checkers(transform: Matrix, left: Pattern, right: Pattern) -> Pattern {
@@ -161,6 +274,35 @@ impl Pattern {
         Pattern::new_mixture(MixtureKind::Stripes, transform, left, right)
     }
 
+    // blends left into right by the noise value, e.g. marble veins over stripes
+    pub fn noise(noise: Noise, transform: Matrix, left: Pattern, right: Pattern) -> Pattern {
+        Pattern::new_mixture(MixtureKind::Noise { noise }, transform, left, right)
+    }
+
+    pub fn cellular(transform: Matrix, left: Pattern, right: Pattern) -> Pattern {
+        Pattern::noise(Noise::Cellular { scale: 1.0 }, transform, left, right)
+    }
+
+    pub fn image(texture: Arc<ImageTexture>, mapping: UvMapping) -> Pattern {
+        Pattern::Image { texture, mapping }
+    }
+
+    // checkers in spherical uv space, so the squares wrap a sphere evenly instead of slicing it
+    pub fn uv_checkers(
+        u_squares: usize,
+        v_squares: usize,
+        left: Pattern,
+        right: Pattern,
+    ) -> Pattern {
+        Pattern::UvCheckers {
+            u_squares,
+            v_squares,
+            mapping: UvMapping::Spherical,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
     pub fn color_at(&self, point: Vector) -> Color {
         match self {
             Pattern::Debug => Color {
@@ -183,6 +325,26 @@ impl Pattern {
                 let point = *transform_inv * point;
                 kind.color_at(point, left, right)
             }
+            Pattern::Image { texture, mapping } => {
+                let (u, v) = mapping.uv(point);
+                texture.sample(u, v)
+            }
+            Pattern::UvCheckers {
+                u_squares,
+                v_squares,
+                mapping,
+                left,
+                right,
+            } => {
+                let (u, v) = mapping.uv(point);
+                let cell = (u * *u_squares as f64).floor() + (v * *v_squares as f64).floor();
+
+                if cell.rem_euclid(2.0) == 0.0 {
+                    left.color_at(point)
+                } else {
+                    right.color_at(point)
+                }
+            }
         }
     }
 }
@@ -225,23 +387,29 @@ impl JitterKind {
 
 #[derive(Debug, Clone, Copy)]
 pub enum MixtureKind {
-    Blend,
+    Blend { ratio: f64 },
     Checkers,
     RingGradient,
     Ring,
     Gradient,
     Stripes,
+    Noise { noise: Noise },
 }
 
 impl Approx<MixtureKind> for MixtureKind {
     fn approx(&self, other: &MixtureKind) -> bool {
         match (self, other) {
-            (MixtureKind::Blend, MixtureKind::Blend) => true,
+            (MixtureKind::Blend { ratio: sratio }, MixtureKind::Blend { ratio: oratio }) => {
+                sratio.approx(oratio)
+            }
             (MixtureKind::Checkers, MixtureKind::Checkers) => true,
             (MixtureKind::RingGradient, MixtureKind::RingGradient) => true,
             (MixtureKind::Ring, MixtureKind::Ring) => true,
             (MixtureKind::Gradient, MixtureKind::Gradient) => true,
             (MixtureKind::Stripes, MixtureKind::Stripes) => true,
+            (MixtureKind::Noise { noise: snoise }, MixtureKind::Noise { noise: onoise }) => {
+                snoise.approx(onoise)
+            }
             (_, _) => false,
         }
     }
@@ -250,11 +418,9 @@ impl Approx<MixtureKind> for MixtureKind {
 impl MixtureKind {
     fn color_at(&self, point: Vector, left: &Pattern, right: &Pattern) -> Color {
         match self {
-            MixtureKind::Blend => {
-                let left = left.color_at(point);
-                let right = right.color_at(point);
-                left.avg(right)
-            }
+            MixtureKind::Blend { ratio } => left
+                .color_at(point)
+                .lerp_unclamped(right.color_at(point), *ratio),
             MixtureKind::Checkers => {
                 let x = point.x.floor() as i32;
                 let y = point.y.floor() as i32;
@@ -270,10 +436,7 @@ impl MixtureKind {
                 let distance = (point - Vector::point(0.0, 0.0, 0.0)).magnitude();
                 let fraction = distance - distance.floor();
 
-                let left = left.color_at(point);
-                let right = right.color_at(point);
-
-                left + ((right - left) * fraction)
+                left.color_at(point).lerp(right.color_at(point), fraction)
             }
             MixtureKind::Ring => {
                 if (point.x.powi(2) + point.z.powi(2)).sqrt().floor() as i32 % 2 == 0 {
@@ -285,10 +448,7 @@ impl MixtureKind {
             MixtureKind::Gradient => {
                 let fraction = point.x - point.x.floor();
 
-                let left = left.color_at(point);
-                let right = right.color_at(point);
-
-                left + ((right - left) * fraction)
+                left.color_at(point).lerp(right.color_at(point), fraction)
             }
             MixtureKind::Stripes => {
                 if point.x.floor() as i32 % 2 == 0 {
@@ -297,15 +457,23 @@ impl MixtureKind {
                     right.color_at(point)
                 }
             }
+            MixtureKind::Noise { noise } => {
+                let value = noise.value(point.x, point.y, point.z);
+                left.color_at(point).lerp(right.color_at(point), value)
+            }
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
     use crate::approx::Approx;
+    use crate::noise::worley;
+
+    use std::f64::consts::PI;
 
     use test_case::test_case;
 
@@ -394,4 +562,152 @@ mod tests {
 
         assert!(color.approx(&expected))
     }
+
+    // the inner stripes see the point in the outer pattern's space
+    #[test_case(Matrix::id()                  , Matrix::id()                  , Vector::point(0.5, 0.0, 0.5), Color::white() ; "no transforms"  )]
+    #[test_case(Matrix::id()                  , Matrix::rotation_y(PI / 2.0)  , Vector::point(0.5, 0.0, 0.5), Color::black() ; "rotated stripes")]
+    #[test_case(Matrix::scaling(2.0, 2.0, 2.0), Matrix::id()                  , Vector::point(1.5, 0.0, 0.5), Color::white() ; "scaled checkers")]
+    #[test_case(Matrix::scaling(2.0, 2.0, 2.0), Matrix::scaling(0.5, 1.0, 1.0), Vector::point(1.5, 0.0, 0.5), Color::black() ; "both scaled"    )]
+    fn nested_transforms(outer: Matrix, inner: Matrix, point: Vector, expected: Color) {
+        let pattern = Pattern::checkers(
+            outer,
+            Pattern::stripes(
+                inner,
+                Pattern::plain(Color::white()),
+                Pattern::plain(Color::black()),
+            ),
+            Pattern::plain(Color::new(1.0, 0.0, 0.0)),
+        );
+
+        assert!(pattern.color_at(point).approx(&expected))
+    }
+
+    #[test]
+    fn material_library() {
+        let brushed_steel = Material {
+            pattern: Pattern::plain(Color::new(0.6, 0.6, 0.65)),
+            reflective: 0.3,
+            roughness: 0.2,
+            ..Material::default()
+        };
+
+        let mut library = MaterialLibrary::new();
+        library.register("brushed_steel", brushed_steel.clone());
+        library.register("chalk", Material::default());
+
+        assert!(Material::named(&library, "brushed_steel")
+            .unwrap()
+            .approx(&brushed_steel));
+        assert!(Material::named(&library, "velvet").is_none())
+    }
+
+    #[test_case(0.5 , Color::new(0.5 , 0.25, 0.0) ; "even")]
+    #[test_case(0.25, Color::new(0.75, 0.125, 0.0) ; "quarter")]
+    #[test_case(0.0 , Color::new(1.0 , 0.0 , 0.0) ; "left only")]
+    fn blend_weighted(ratio: f64, expected: Color) {
+        let pattern = Pattern::blend_weighted(
+            Matrix::id(),
+            ratio,
+            Pattern::plain(Color::new(1.0, 0.0, 0.0)),
+            Pattern::plain(Color::new(0.0, 0.5, 0.0)),
+        );
+        let color = pattern.color_at(Vector::point(0.0, 0.0, 0.0));
+
+        assert!(color.approx(&expected))
+    }
+
+    #[test]
+    fn cellular() {
+        let pattern = Pattern::cellular(
+            Matrix::scaling(0.5, 0.5, 0.5),
+            Pattern::plain(Color::black()),
+            Pattern::plain(Color::white()),
+        );
+        let points: Vec<Vector> = (0..32)
+            .map(|i| Vector::point(i as f64 * 0.23, i as f64 * -0.11, i as f64 * 0.07))
+            .collect();
+
+        // the gray level is exactly the clamped distance to the nearest feature point
+        assert!(points.iter().all(|&point| {
+            let color = pattern.color_at(point);
+            let local = Matrix::scaling(2.0, 2.0, 2.0) * point;
+            let expected = worley(local.x, local.y, local.z);
+
+            color.approx(&Color::new(expected, expected, expected))
+                && color.approx(&pattern.color_at(point))
+        }))
+    }
+
+    #[test]
+    fn noise_marble() {
+        let veins = Pattern::noise(
+            Noise::Fractal {
+                scale: 1.0,
+                octaves: 3,
+            },
+            Matrix::id(),
+            Pattern::plain(Color::white()),
+            Pattern::stripes(
+                Matrix::id(),
+                Pattern::plain(Color::black()),
+                Pattern::plain(Color::new(0.2, 0.2, 0.2)),
+            ),
+        );
+        let colors: Vec<Color> = (0..16)
+            .map(|i| veins.color_at(Vector::point(i as f64 * 0.31, 0.5, 0.25)))
+            .collect();
+
+        assert!(colors
+            .iter()
+            .all(|color| color.r >= 0.0 && color.r <= 1.0 && color.r == color.g));
+        assert!(colors.iter().any(|color| !color.approx(&colors[0])))
+    }
+
+    #[test_case(Vector::point(0.0,  1.0,  0.0), Color::new(1.0, 0.0, 0.0) ; "north pole")]
+    #[test_case(Vector::point(0.0, -1.0,  0.0), Color::new(0.0, 0.0, 1.0) ; "south pole")]
+    #[test_case(Vector::point(0.0,  0.0, -1.0), Color::new(0.5, 0.0, 0.5) ; "seam")]
+    fn image_spherical(point: Vector, expected: Color) {
+        // north row red, south row blue
+        let texture = ImageTexture::new(
+            2,
+            2,
+            vec![
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(0.0, 0.0, 1.0),
+                Color::new(0.0, 0.0, 1.0),
+            ],
+        );
+        let pattern = Pattern::image(Arc::new(texture), UvMapping::Spherical);
+
+        assert!(pattern.color_at(point).approx(&expected))
+    }
+
+    // cell centers of a 4 x 2 grid, each neighbour of the first one flips the color
+    #[test_case(0.125, 0.25, true  ; "first cell"      )]
+    #[test_case(0.375, 0.25, false ; "next in u"       )]
+    #[test_case(0.125, 0.75, false ; "next in v"       )]
+    #[test_case(0.375, 0.75, true  ; "diagonal"        )]
+    #[test_case(0.875, 0.25, false ; "across the seam" )]
+    fn uv_checkers(u: f64, v: f64, white: bool) {
+        let pattern = Pattern::uv_checkers(
+            4,
+            2,
+            Pattern::plain(Color::white()),
+            Pattern::plain(Color::black()),
+        );
+
+        // inverts the spherical mapping of the unit sphere
+        let phi = (1.0 - v) * PI;
+        let theta = (0.5 - u) * 2.0 * PI;
+        let point = Vector::point(phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos());
+
+        let expected = if white {
+            Color::white()
+        } else {
+            Color::black()
+        };
+
+        assert!(pattern.color_at(point).approx(&expected))
+    }
 }
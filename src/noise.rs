@@ -4,6 +4,7 @@ use crate::approx::Approx;
 pub enum Noise {
     Simplex { scale: f64 },
     Fractal { scale: f64, octaves: usize },
+    Cellular { scale: f64 },
 }
 
 impl Approx<Noise> for Noise {
@@ -22,6 +23,9 @@ impl Approx<Noise> for Noise {
                     octaves: ooctaves,
                 },
             ) => sscale.approx(oscale) && soctaves.approx(ooctaves),
+            (Noise::Cellular { scale: sscale }, Noise::Cellular { scale: oscale }) => {
+                sscale.approx(oscale)
+            }
             (_, _) => false,
         }
     }
@@ -46,10 +50,32 @@ impl Noise {
                     fractal(x, y, z + 2.0, *octaves) * scale,
                 );
 
+                (x + nx, y + ny, z + nz)
+            }
+            Noise::Cellular { scale } => {
+                let (nx, ny, nz) = (
+                    worley(x, y, z) * scale,
+                    worley(x, y, z + 1.0) * scale,
+                    worley(x, y, z + 2.0) * scale,
+                );
+
                 (x + nx, y + ny, z + nz)
             }
         }
     }
+
+    // a scalar in [0, 1], the scale only stretches the noise in space
+    pub fn value(&self, x: f64, y: f64, z: f64) -> f64 {
+        let value = match self {
+            Noise::Simplex { scale } => (simplex(x / scale, y / scale, z / scale) + 1.0) / 2.0,
+            Noise::Fractal { scale, octaves } => {
+                (fractal(x / scale, y / scale, z / scale, *octaves) + 1.0) / 2.0
+            }
+            Noise::Cellular { scale } => worley(x / scale, y / scale, z / scale),
+        };
+
+        value.clamp(0.0, 1.0)
+    }
 }
 
 #[rustfmt::skip]
@@ -90,6 +116,8 @@ fn hash(i: usize) -> usize {
     P[i] as usize
 }
 
+// the twelve cube edge directions of improved Perlin noise, four of them repeated
+// so the hash can be masked instead of taken modulo 12
 #[rustfmt::skip]
 fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
     match hash & 0xF {
@@ -235,3 +263,108 @@ fn fractal(x: f64, y: f64, z: f64, octaves: usize) -> f64 {
 
     output / denom
 }
+
+// every unit cell holds one feature point, the result is the distance to the nearest one
+pub fn worley(x: f64, y: f64, z: f64) -> f64 {
+    let (i, j, k) = (x.floor() as i32, y.floor() as i32, z.floor() as i32);
+    let mut nearest = f64::INFINITY;
+
+    for di in -1..=1 {
+        for dj in -1..=1 {
+            for dk in -1..=1 {
+                let (ci, cj, ck) = (i + di, j + dj, k + dk);
+                let h = hash(hash(hash(modulus(ci, 256)) + modulus(cj, 256)) + modulus(ck, 256));
+                let feature = (
+                    ci as f64 + hash(h) as f64 / 255.0,
+                    cj as f64 + hash(h + 1) as f64 / 255.0,
+                    ck as f64 + hash(h + 2) as f64 / 255.0,
+                );
+
+                let distance =
+                    ((x - feature.0).powi(2) + (y - feature.1).powi(2) + (z - feature.2).powi(2))
+                        .sqrt();
+                nearest = nearest.min(distance);
+            }
+        }
+    }
+
+    nearest.min(1.0)
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_unit)]
+mod tests {
+    use super::*;
+
+    use test_case::test_case;
+
+    #[test_case(Noise::Simplex  { scale: 1.0 }             ; "simplex" )]
+    #[test_case(Noise::Fractal  { scale: 2.0, octaves: 4 } ; "fractal" )]
+    #[test_case(Noise::Cellular { scale: 0.5 }             ; "cellular")]
+    fn value_in_unit_interval(noise: Noise) {
+        let values: Vec<f64> = (0..64)
+            .map(|i| noise.value(i as f64 * 0.37, i as f64 * -0.21, i as f64 * 0.13))
+            .collect();
+
+        assert!(values.iter().all(|value| (0.0..=1.0).contains(value)));
+        assert!(values.iter().any(|value| !value.approx(&values[0])))
+    }
+
+    #[test_case(1.0, 0.0, 0.0 ; "x")]
+    #[test_case(0.0, 1.0, 0.0 ; "y")]
+    #[test_case(0.0, 0.0, 1.0 ; "z")]
+    fn grad_table_balanced(x: f64, y: f64, z: f64) {
+        let sum: f64 = (0..16).map(|hash| grad(hash, x, y, z)).sum();
+
+        assert!(sum.approx(&0.0))
+    }
+
+    #[test]
+    fn simplex_unbiased() {
+        let n = 20000;
+        let values: Vec<f64> = (0..n)
+            .map(|i| {
+                let f = i as f64;
+                simplex(f * 0.1731 + 0.3, f * 0.0917 - 7.1, f * 0.0533 + 2.9)
+            })
+            .collect();
+
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let positive = values.iter().filter(|&&value| value > 0.0).count() as f64 / n as f64;
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / n as f64;
+        let skew = values
+            .iter()
+            .map(|value| (value - mean).powi(3))
+            .sum::<f64>()
+            / n as f64
+            / variance.powf(1.5);
+
+        assert!(mean.abs() < 0.02 && (positive - 0.5).abs() < 0.03 && skew.abs() < 0.1)
+    }
+
+    #[test]
+    fn worley_deterministic() {
+        let points = [(0.3, 1.7, -2.2), (-5.5, 0.25, 3.0), (120.0, -64.5, 7.75)];
+
+        assert!(points
+            .iter()
+            .all(|&(x, y, z)| worley(x, y, z) == worley(x, y, z)))
+    }
+
+    #[test]
+    fn worley_vanishes_at_feature_point() {
+        // the feature point of cell (0, 0, 0)
+        let h = hash(hash(hash(0)));
+        let feature = (
+            hash(h) as f64 / 255.0,
+            hash(h + 1) as f64 / 255.0,
+            hash(h + 2) as f64 / 255.0,
+        );
+
+        assert!(worley(feature.0, feature.1, feature.2).approx(&0.0))
+    }
+}
@@ -1,20 +1,31 @@
+use crate::approx::Approx;
+use crate::color::Color;
 use crate::linalg::{Matrix, Vector};
-use crate::material::Material;
+use crate::material::{Material, Pattern};
 use crate::shape::*;
 
 use std::collections::HashMap;
 use std::fs;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
 
 use nom::branch::alt;
-use nom::character::complete::{alphanumeric1, anychar, digit1, space1};
+use nom::bytes::complete::{is_not, tag};
+use nom::character::complete::{alphanumeric1, digit1, space1};
+use nom::combinator::map;
 use nom::number::complete::double;
+use nom::sequence::{preceded, tuple};
+use nom::IResult;
 use nom::{
-    alt, char, complete, do_parse, many_till, map_opt, map_res, named, separated_list0, tag,
+    alt, char, complete, do_parse, map_opt, map_res, named, opt, preceded, recognize,
+    separated_list0, tag,
 };
 
-type VertexNormal = (usize, Option<usize>);
+// vertex, texture coordinate and normal index, negative ones count from the end
+type FaceVertex = (isize, Option<isize>, Option<isize>);
+type Face = (usize, usize, usize);
 
 #[derive(Debug, PartialEq)]
 enum Obj {
@@ -28,18 +39,44 @@ enum Obj {
         y: f64,
         z: f64,
     },
+    TextureCoord {
+        u: f64,
+        v: f64,
+    },
     Triangles {
-        indices: Vec<(VertexNormal, VertexNormal, VertexNormal)>,
+        n: u32,
+        line: String,
+        indices: Vec<(FaceVertex, FaceVertex, FaceVertex)>,
     },
     Group {
         name: String,
     },
+    MaterialLibrary {
+        n: u32,
+        line: String,
+        path: String,
+    },
+    UseMaterial {
+        name: String,
+    },
     Ignored {
         n: u32,
         line: String,
     },
 }
 
+#[derive(Debug)]
+enum Mtl {
+    New(String),
+    Ambient(Color),
+    Diffuse(Color),
+    Specular(Color),
+    Shininess(f64),
+    Dissolve(f64),
+    Transparency(f64),
+    RefractiveIndex(f64),
+}
+
 #[derive(Debug)]
 struct ObjParse {
     objs: Vec<Obj>,
@@ -48,12 +85,17 @@ struct ObjParse {
 #[derive(Debug)]
 pub struct ObjParser<'a> {
     path: &'a str,
+    merge_quads: bool,
+    flip_handedness: bool,
 }
 
 #[rustfmt::skip]
 named!(
-    parse_usize<&str, usize>,
-    map_res!(digit1, |s: &str| s.parse::<usize>())
+    parse_index<&str, isize>,
+    map_res!(
+        recognize!(preceded!(opt!(char!('-')), digit1)),
+        |s: &str| s.parse::<isize>()
+    )
 );
 
 #[rustfmt::skip]
@@ -92,30 +134,150 @@ named!(
 
 #[rustfmt::skip]
 named!(
-    parse_face_triplet<&str, (usize, Option<usize>)>,
+    parse_texture_coord<&str, Obj>,
+    do_parse!(
+           tag!("vt") >>
+           space1     >>
+        u: double     >>
+           space1     >>
+        v: double     >>
+        (
+            Obj::TextureCoord { u, v }
+        )
+    )
+);
+
+#[rustfmt::skip]
+named!(
+    parse_face_triplet<&str, FaceVertex>,
+    do_parse!(
+        v: parse_index >>
+           char!('/')  >>
+        t: parse_index >>
+           char!('/')  >>
+        n: parse_index >>
+        (
+            (v, Some(t), Some(n))
+        )
+    )
+);
+
+#[rustfmt::skip]
+named!(
+    parse_face_normal<&str, FaceVertex>,
+    do_parse!(
+        v: parse_index >>
+           tag!("//")  >>
+        n: parse_index >>
+        (
+            (v, None, Some(n))
+        )
+    )
+);
+
+#[rustfmt::skip]
+named!(
+    parse_face_texture<&str, FaceVertex>,
     do_parse!(
-        v: parse_usize                     >>
-           char!('/')                      >>
-           many_till!(anychar, char!('/')) >>
-        n: parse_usize                     >>
+        v: parse_index >>
+           char!('/')  >>
+        t: parse_index >>
         (
-            (v, Some(n))
+            (v, Some(t), None)
         )
     )
 );
 
 named!(
-    parse_face<&str, (usize, Option<usize>)>,
+    parse_face<&str, FaceVertex>,
     alt!(
         complete!(parse_face_triplet) |
-        complete!(map_opt!(parse_usize, |v| Some((v, None))))
+        complete!(parse_face_normal) |
+        complete!(parse_face_texture) |
+        complete!(map_opt!(parse_index, |v| Some((v, None, None))))
     )
 );
 
+fn is_convex(corners: [Vector; 4], n: Vector) -> bool {
+    (0..4).all(|i| {
+        let (a, b, c) = (corners[i], corners[(i + 1) % 4], corners[(i + 2) % 4]);
+        (b - a).cross(c - b).dot(n) < 0.0
+    })
+}
+
+fn merge_coplanar(vertices: &[Vector], triangles: Vec<Face>) -> (Vec<[usize; 4]>, Vec<Face>) {
+    let normal = |(p1, p2, p3): Face| {
+        let (p1, p2, p3) = (vertices[p1 - 1], vertices[p2 - 1], vertices[p3 - 1]);
+        (p3 - p1).cross(p2 - p1).normalize()
+    };
+
+    // a directed edge of one triangle shows up reversed in a consistently wound neighbour
+    let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+    for (i, &(p1, p2, p3)) in triangles.iter().enumerate() {
+        for edge in [(p1, p2), (p2, p3), (p3, p1)] {
+            edges.insert(edge, i);
+        }
+    }
+
+    let mut merged = vec![false; triangles.len()];
+    let mut quads = vec![];
+
+    for (i, &(p1, p2, p3)) in triangles.iter().enumerate() {
+        if merged[i] {
+            continue;
+        }
+
+        let n = normal(triangles[i]);
+        for (a, b, opposite) in [(p1, p2, p3), (p2, p3, p1), (p3, p1, p2)] {
+            let j = match edges.get(&(b, a)) {
+                Some(&j) if j != i && !merged[j] => j,
+                _ => continue,
+            };
+
+            if !normal(triangles[j]).approx(&n) {
+                continue;
+            }
+
+            let (q1, q2, q3) = triangles[j];
+            let other = q1 + q2 + q3 - a - b;
+            let quad = [opposite, a, other, b];
+
+            if is_convex(quad.map(|p| vertices[p - 1]), n) {
+                merged[i] = true;
+                merged[j] = true;
+                quads.push(quad);
+                break;
+            }
+        }
+    }
+
+    let remaining = triangles
+        .into_iter()
+        .zip(merged)
+        .filter(|(_, merged)| !merged)
+        .map(|(triangle, _)| triangle)
+        .collect();
+
+    (quads, remaining)
+}
+
+// obj indices are 1-based, a negative one refers back from the current end of the list
+fn resolve(index: isize, len: usize) -> Option<usize> {
+    let index = if index < 0 {
+        len as isize + index + 1
+    } else {
+        index
+    };
+
+    (1..=len as isize)
+        .contains(&index)
+        .then_some(index as usize)
+}
+
 fn triangulate<T: Copy>(indices: Vec<T>) -> Vec<(T, T, T)> {
     let mut triples = vec![];
 
-    for i in 1..(indices.len() - 1) {
+    for i in 1..indices.len().saturating_sub(1) {
         triples.push((indices[0], indices[i], indices[i + 1]));
     }
 
@@ -130,7 +292,8 @@ named!(
                  space1                               >>
         indices: separated_list0!(space1, parse_face) >>
         (
-            Obj::Triangles { indices: triangulate(indices) }
+            // the line itself is filled in by `parse_line`
+            Obj::Triangles { n: 0, line: String::new(), indices: triangulate(indices) }
         )
     )
 );
@@ -148,13 +311,163 @@ named!(
     )
 );
 
+// everything up to the next whitespace, names may contain dots and dashes
+fn parse_name(input: &str) -> IResult<&str, &str> {
+    is_not(" \t")(input)
+}
+
+#[rustfmt::skip]
+named!(
+    parse_material_library<&str, Obj>,
+    do_parse!(
+              tag!("mtllib") >>
+              space1         >>
+        path: parse_name     >>
+        (
+            // the line itself is filled in by `parse_line`
+            Obj::MaterialLibrary { n: 0, line: String::new(), path: path.to_string() }
+        )
+    )
+);
+
+#[rustfmt::skip]
+named!(
+    parse_use_material<&str, Obj>,
+    do_parse!(
+              tag!("usemtl") >>
+              space1         >>
+        name: parse_name     >>
+        (
+            Obj::UseMaterial { name: name.to_string() }
+        )
+    )
+);
+
+fn parse_mtl_color(input: &str) -> IResult<&str, Color> {
+    map(
+        tuple((space1, double, space1, double, space1, double)),
+        |(_, r, _, g, _, b)| Color::new(r, g, b),
+    )(input)
+}
+
+fn parse_mtl_line(line: &str) -> IResult<&str, Mtl> {
+    let name = |input| preceded(space1, parse_name)(input);
+    let scalar = |input| preceded(space1, double)(input);
+
+    alt((
+        map(preceded(tag("newmtl"), name), |name: &str| {
+            Mtl::New(name.to_string())
+        }),
+        map(preceded(tag("Ka"), parse_mtl_color), Mtl::Ambient),
+        map(preceded(tag("Kd"), parse_mtl_color), Mtl::Diffuse),
+        map(preceded(tag("Ks"), parse_mtl_color), Mtl::Specular),
+        map(preceded(tag("Ns"), scalar), Mtl::Shininess),
+        map(preceded(tag("d"), scalar), Mtl::Dissolve),
+        map(preceded(tag("Tr"), scalar), Mtl::Transparency),
+        map(preceded(tag("Ni"), scalar), Mtl::RefractiveIndex),
+    ))(line.trim())
+}
+
+// unknown statements are skipped, every material starts out as the caller supplied one
+fn parse_mtl(contents: &str, base: &Material) -> HashMap<String, Material> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Material)> = None;
+
+    for line in contents.lines() {
+        let mtl = match parse_mtl_line(line) {
+            Ok((_, mtl)) => mtl,
+            Err(_) => continue,
+        };
+
+        if let Mtl::New(name) = mtl {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((name, base.clone()));
+            continue;
+        }
+
+        let material = match &mut current {
+            Some((_, material)) => material,
+            None => continue,
+        };
+
+        match mtl {
+            // our ambient term is a scalar on top of the diffuse color
+            Mtl::Ambient(color) => material.ambient = (color.r + color.g + color.b) / 3.0,
+            Mtl::Diffuse(color) => {
+                material.pattern = Pattern::plain(color);
+                material.diffuse = 1.0;
+            }
+            Mtl::Specular(color) => {
+                material.specular_color = color;
+                material.specular = 1.0;
+            }
+            Mtl::Shininess(shininess) => material.shininess = shininess,
+            Mtl::Dissolve(dissolve) => material.transparency = 1.0 - dissolve,
+            Mtl::Transparency(transparency) => material.transparency = transparency,
+            Mtl::RefractiveIndex(index) => material.refractive_index = index,
+            Mtl::New(_) => unreachable!(),
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    materials
+}
+
 impl<'a> ObjParser<'a> {
     pub fn new(path: &'a str) -> ObjParser<'a> {
-        ObjParser { path }
+        ObjParser {
+            path,
+            merge_quads: false,
+            flip_handedness: false,
+        }
+    }
+
+    pub fn merge_quads(self, merge_quads: bool) -> ObjParser<'a> {
+        ObjParser {
+            merge_quads,
+            ..self
+        }
+    }
+
+    // mirroring z also reverses the apparent winding of every face, which turns
+    // counter-clockwise right-handed meshes into the clockwise faces we expect
+    pub fn flip_handedness(self, flip_handedness: bool) -> ObjParser<'a> {
+        ObjParser {
+            flip_handedness,
+            ..self
+        }
     }
 
     fn parse_line(n: u32, line: &str, obj_parse: &mut ObjParse) {
-        match alt((parse_vertex, parse_normal, parse_faces, parse_group))(line) {
+        match alt((
+            parse_vertex,
+            parse_normal,
+            parse_texture_coord,
+            parse_faces,
+            parse_group,
+            parse_material_library,
+            parse_use_material,
+        ))(line)
+        {
+            Ok((_, Obj::Triangles { indices, .. })) => {
+                obj_parse.objs.push(Obj::Triangles {
+                    n,
+                    line: line.to_string(),
+                    indices,
+                });
+            }
+            Ok((_, Obj::MaterialLibrary { path, .. })) => {
+                obj_parse.objs.push(Obj::MaterialLibrary {
+                    n,
+                    line: line.to_string(),
+                    path,
+                });
+            }
             Ok((_, obj)) => {
                 obj_parse.objs.push(obj);
             }
@@ -183,28 +496,75 @@ impl<'a> ObjParser<'a> {
         Ok(obj_parse)
     }
 
+    fn z(&self, z: f64) -> f64 {
+        if self.flip_handedness {
+            -z
+        } else {
+            z
+        }
+    }
+
     pub fn parse_obj(
         &self,
         transform: Matrix,
         material: Material,
     ) -> std::io::Result<(Vec<(u32, String)>, Element)> {
-        let mut group = "Default".to_string();
+        // triangles are grouped by their `g` name and their active `usemtl`
+        let mut group: (String, Option<String>) = ("Default".to_string(), None);
         let mut vertices = vec![];
         let mut normals = vec![];
+        let mut texture_coords = vec![];
+        let mut materials = HashMap::new();
         let mut ignored = vec![];
-        let mut groups: HashMap<String, Vec<Element>> = HashMap::new();
+        let mut groups: HashMap<(String, Option<String>), Vec<Element>> = HashMap::new();
         groups.insert(group.clone(), vec![]);
+        let mut flat: HashMap<(String, Option<String>), Vec<Face>> = HashMap::new();
 
         let obj_parse = self.parse_lines()?;
 
         for obj in obj_parse.objs {
             match obj {
-                Obj::Vertex { x, y, z } => vertices.push(Vector::point(x, y, z)),
-                Obj::Normal { x, y, z } => normals.push(Vector::vector(x, y, z)),
-                Obj::Triangles { indices } => {
-                    for ((p1, n1), (p2, n2), (p3, n3)) in indices {
+                Obj::Vertex { x, y, z } => vertices.push(Vector::point(x, y, self.z(z))),
+                Obj::Normal { x, y, z } => normals.push(Vector::vector(x, y, self.z(z))),
+                Obj::TextureCoord { u, v } => texture_coords.push((u, v)),
+                Obj::Triangles { n, line, indices } => {
+                    let face_vertex = |(p, t, n): FaceVertex| {
+                        let p = resolve(p, vertices.len())?;
+                        let n = match n {
+                            Some(n) => Some(resolve(n, normals.len())?),
+                            None => None,
+                        };
+                        // texture coordinates are optional, so bad indices just drop them
+                        let uv = t
+                            .and_then(|t| resolve(t, texture_coords.len()))
+                            .map(|t| texture_coords[t - 1]);
+
+                        Some((p, uv, n))
+                    };
+
+                    // a face pointing past the vertices or normals read so far is reported instead
+                    let faces = indices
+                        .into_iter()
+                        .map(|(v1, v2, v3)| {
+                            Some((face_vertex(v1)?, face_vertex(v2)?, face_vertex(v3)?))
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    let faces = match faces {
+                        Some(faces) => faces,
+                        None => {
+                            ignored.push((n, line));
+                            continue;
+                        }
+                    };
+
+                    for ((p1, uv1, n1), (p2, uv2, n2), (p3, uv3, n3)) in faces {
+                        let uvs = match (uv1, uv2, uv3) {
+                            (Some(uv1), Some(uv2), Some(uv3)) => Some([uv1, uv2, uv3]),
+                            _ => None,
+                        };
+
                         let triangle = match (n1, n2, n3) {
-                            (Some(n1), Some(n2), Some(n3)) => Element::smooth_triangle(
+                            (Some(n1), Some(n2), Some(n3)) => Shape::smooth_triangle(
                                 ShapeArgs::default(),
                                 vertices[p1 - 1],
                                 vertices[p2 - 1],
@@ -213,20 +573,47 @@ impl<'a> ObjParser<'a> {
                                 normals[n2 - 1],
                                 normals[n3 - 1],
                             ),
-                            _ => Element::triangle(
+                            // merged quads have no way to carry texture coordinates
+                            _ if self.merge_quads && uvs.is_none() => {
+                                flat.entry(group.clone()).or_default().push((p1, p2, p3));
+                                continue;
+                            }
+                            _ => Shape::triangle(
                                 ShapeArgs::default(),
                                 vertices[p1 - 1],
                                 vertices[p2 - 1],
                                 vertices[p3 - 1],
                             ),
                         };
+                        let triangle = match uvs {
+                            Some(uvs) => triangle.with_uvs(uvs),
+                            None => triangle,
+                        }
+                        .with_group(Arc::from(group.0.as_str()));
 
-                        groups.get_mut(&group).unwrap().push(triangle);
+                        groups
+                            .get_mut(&group)
+                            .unwrap()
+                            .push(Element::Primitive(triangle));
                     }
                 }
                 Obj::Group { name } => {
-                    group = name.clone();
-                    groups.entry(name).or_insert(vec![]);
+                    group.0 = name;
+                    groups.entry(group.clone()).or_insert(vec![]);
+                }
+                // a missing library leaves the mesh with the caller's material
+                Obj::MaterialLibrary { n, line, path } => {
+                    let dir = Path::new(self.path)
+                        .parent()
+                        .unwrap_or_else(|| Path::new(""));
+                    match fs::read_to_string(dir.join(path)) {
+                        Ok(contents) => materials.extend(parse_mtl(&contents, &material)),
+                        Err(_) => ignored.push((n, line)),
+                    }
+                }
+                Obj::UseMaterial { name } => {
+                    group.1 = Some(name);
+                    groups.entry(group.clone()).or_insert(vec![]);
                 }
                 Obj::Ignored { n, line } => {
                     ignored.push((n, line));
@@ -234,10 +621,40 @@ impl<'a> ObjParser<'a> {
             }
         }
 
+        for (group, triangles) in flat {
+            let (quads, triangles) = merge_coplanar(&vertices, triangles);
+            let name: Arc<str> = Arc::from(group.0.as_str());
+            let children = groups.get_mut(&group).unwrap();
+
+            for [p1, p2, p3, p4] in quads {
+                let quad = Shape::quad(
+                    ShapeArgs::default(),
+                    vertices[p1 - 1],
+                    vertices[p2 - 1],
+                    vertices[p3 - 1],
+                    vertices[p4 - 1],
+                );
+                children.push(Element::Primitive(quad.with_group(name.clone())));
+            }
+            for (p1, p2, p3) in triangles {
+                let triangle = Shape::triangle(
+                    ShapeArgs::default(),
+                    vertices[p1 - 1],
+                    vertices[p2 - 1],
+                    vertices[p3 - 1],
+                );
+                children.push(Element::Primitive(triangle.with_group(name.clone())));
+            }
+        }
+
         let mut elements = vec![];
 
-        for (_, children) in groups.drain() {
-            if children.len() != 0 {
+        for ((_, name), children) in groups.drain() {
+            if !children.is_empty() {
+                let material = name
+                    .and_then(|name| materials.get(&name))
+                    .unwrap_or(&material);
+
                 elements.push(Element::composite(
                     transform,
                     Some(material.clone()),
@@ -263,10 +680,12 @@ mod tests {
     use super::*;
 
     use crate::approx::Approx;
+    use crate::intersection::Intersection;
+    use crate::ray::Ray;
 
     fn parse_lines(path: &str, contents: &[u8]) -> ObjParse {
         let mut file = fs::File::create(path).unwrap();
-        file.write(contents).unwrap();
+        file.write_all(contents).unwrap();
 
         let obj_parse = ObjParser::new(path).parse_lines().unwrap();
         let _ = fs::remove_file(path);
@@ -276,7 +695,7 @@ mod tests {
 
     fn parse_obj(path: &str, contents: &[u8]) -> (Vec<(u32, String)>, Element) {
         let mut file = fs::File::create(path).unwrap();
-        file.write(contents).unwrap();
+        file.write_all(contents).unwrap();
 
         let result = ObjParser::new(path)
             .parse_obj(Matrix::id(), Material::default())
@@ -412,11 +831,15 @@ mod tests {
                     }
                 && obj_parse.objs[5]
                     == Obj::Triangles {
-                        indices: vec![((1, None), (2, None), (3, None))]
+                        n: 6,
+                        line: "f 1 2 3".to_string(),
+                        indices: vec![((1, None, None), (2, None, None), (3, None, None))]
                     }
                 && obj_parse.objs[6]
                     == Obj::Triangles {
-                        indices: vec![((1, None), (3, None), (4, None))]
+                        n: 7,
+                        line: "f 1 3 4".to_string(),
+                        indices: vec![((1, None, None), (3, None, None), (4, None, None))]
                     }
         )
     }
@@ -473,7 +896,9 @@ mod tests {
                     }
                 && obj_parse.objs[6]
                     == Obj::Triangles {
-                        indices: vec![((1, None), (2, None), (3, None))]
+                        n: 7,
+                        line: "f 1 2 3".to_string(),
+                        indices: vec![((1, None, None), (2, None, None), (3, None, None))]
                     }
                 && obj_parse.objs[7]
                     == Obj::Group {
@@ -481,11 +906,59 @@ mod tests {
                     }
                 && obj_parse.objs[8]
                     == Obj::Triangles {
-                        indices: vec![((1, None), (3, None), (4, None))]
+                        n: 9,
+                        line: "f 1 3 4".to_string(),
+                        indices: vec![((1, None, None), (3, None, None), (4, None, None))]
                     }
         )
     }
 
+    #[test]
+    fn triangle_group_names() {
+        let contents = b"v -1 1 0\n\
+            v -1 0 0\n\
+            v 1 0 0\n\
+            v 1 1 0\n\
+            g FirstGroup\n\
+            f 1 2 3\n\
+            g SecondGroup\n\
+            f 1 3 4\n";
+
+        let path = "triangle_group_names.obj";
+        let (_, element) = parse_obj(path, contents);
+
+        let groups = match element {
+            Element::Composite(group) => group.children,
+            _ => panic!("Expected composite, found primitive."),
+        };
+        assert_eq!(groups.len(), 2);
+
+        for group in groups {
+            let children = match group {
+                Element::Composite(group) => group.children,
+                _ => panic!("Expected composite, found primitive."),
+            };
+            let names: Vec<_> = children
+                .iter()
+                .map(|child| match child {
+                    Element::Primitive(shape) => shape.group.as_deref(),
+                    _ => None,
+                })
+                .collect();
+
+            // only the first group's triangle has (-1, 0, 0) as its second vertex
+            let first = match &children[0] {
+                Element::Primitive(shape) => match shape.geometry {
+                    Geometry::Triangle { p2, .. } => p2.approx(&Vector::point(-1.0, 0.0, 0.0)),
+                    _ => false,
+                },
+                _ => false,
+            };
+            let expected = if first { "FirstGroup" } else { "SecondGroup" };
+            assert_eq!(names, vec![Some(expected)]);
+        }
+    }
+
     #[test]
     fn vertex_normal_records() {
         let contents = b"vn 0 0 1\n\
@@ -584,15 +1057,240 @@ mod tests {
                     }
                 && obj_parse.objs[8]
                     == Obj::Triangles {
-                        indices: vec![((1, Some(3)), (2, Some(1)), (3, Some(2)))],
+                        n: 9,
+                        line: "f 1//3 2//1 3//2".to_string(),
+                        indices: vec![((1, None, Some(3)), (2, None, Some(1)), (3, None, Some(2)))],
                     }
                 && obj_parse.objs[9]
                     == Obj::Triangles {
-                        indices: vec![((1, Some(3)), (2, Some(1)), (3, Some(2)))],
+                        n: 10,
+                        line: "f 1/0/3 2/102/1 3/14/2".to_string(),
+                        indices: vec![(
+                            (1, Some(0), Some(3)),
+                            (2, Some(102), Some(1)),
+                            (3, Some(14), Some(2))
+                        )],
                     }
         )
     }
 
+    #[test]
+    fn texture_coord_records() {
+        let contents = b"vt 0 0\n\
+            vt 0.5 1 0\n\
+            f 1 2 3\n\
+            f 1/1 2/2 3/1\n\
+            f 1//1 2//2 3//3\n\
+            f 1/1/1 2/2/2 3/1/3\n";
+
+        let path = "texture_coord_records.obj";
+        let obj_parse = parse_lines(path, contents);
+
+        assert!(
+            obj_parse.objs.len() == 6
+                && obj_parse.objs[0] == Obj::TextureCoord { u: 0.0, v: 0.0 }
+                && obj_parse.objs[1] == Obj::TextureCoord { u: 0.5, v: 1.0 }
+                && obj_parse.objs[2..]
+                    .iter()
+                    .all(|obj| matches!(obj, Obj::Triangles { .. }))
+        )
+    }
+
+    #[test]
+    fn textured_triangle_faces() {
+        let contents = b"v 0 1 0\n\
+            v -1 0 0\n\
+            v 1 0 0\n\
+            vt 0.5 1\n\
+            vt 0 0\n\
+            vt 1 0\n\
+            f 1/1 2/2 3/3\n";
+
+        let path = "textured_triangle_faces.obj";
+        let (_, element) = parse_obj(path, contents);
+
+        let uv = match element {
+            Element::Composite(group) => match &group.children[0] {
+                Element::Primitive(shape) => shape.texture_uv(Some(0.5), Some(0.5)),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        // halfway between the second and third vertex
+        assert!(uv.approx(&Some((0.5, 0.0))))
+    }
+
+    #[test]
+    fn negative_indices() {
+        let vertices = b"v 0 1 0\n\
+            v -1 0 0\n\
+            v 1 0 0\n\
+            v 0 0 1\n\
+            vn -1 0 0\n\
+            vn 1 0 0\n\
+            vn 0 1 0\n";
+
+        let absolute = [&vertices[..], b"f 1//3 2//1 3//2\nf 2 3 4\n"].concat();
+        let relative = [&vertices[..], b"f -4//-1 -3//-3 -2//-2\nf -3 -2 -1\n"].concat();
+
+        let (absolute_ignored, absolute) = parse_obj("negative_indices_absolute.obj", &absolute);
+        let (relative_ignored, relative) = parse_obj("negative_indices_relative.obj", &relative);
+
+        assert!(absolute_ignored.is_empty() && relative_ignored.is_empty());
+        assert!(relative.approx(&absolute))
+    }
+
+    #[test]
+    fn unresolvable_indices() {
+        let contents = b"v 0 1 0\n\
+            v -1 0 0\n\
+            v 1 0 0\n\
+            vn 0 0 -1\n\
+            f -5 -6 -7\n\
+            f 1 2 4\n\
+            f 1//1 2//1 3//2\n\
+            f 1 2 3\n";
+
+        let (ignored, element) = parse_obj("unresolvable_indices.obj", contents);
+
+        assert!(
+            ignored
+                == vec![
+                    (5, "f -5 -6 -7".to_string()),
+                    (6, "f 1 2 4".to_string()),
+                    (7, "f 1//1 2//1 3//2".to_string()),
+                ]
+                && matches!(element, Element::Composite(group) if group.children.len() == 1)
+        )
+    }
+
+    #[test]
+    fn face_forms() {
+        assert_eq!(parse_face("1//3"), Ok(("", (1, None, Some(3)))));
+        assert_eq!(parse_face("1/2/3"), Ok(("", (1, Some(2), Some(3)))));
+        assert_eq!(parse_face("1/2"), Ok(("", (1, Some(2), None))));
+        assert_eq!(parse_face("1"), Ok(("", (1, None, None))));
+        assert_eq!(parse_face("-1/-2/-3"), Ok(("", (-1, Some(-2), Some(-3)))));
+        assert_eq!(
+            parse_faces("f 1/1 2/2 3/3"),
+            Ok((
+                "",
+                Obj::Triangles {
+                    n: 0,
+                    line: String::new(),
+                    indices: vec![((1, Some(1), None), (2, Some(2), None), (3, Some(3), None))]
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn merge_quads() {
+        let contents = b"v -1 0 -1\n\
+            v 1 0 -1\n\
+            v 1 0 1\n\
+            v -1 0 1\n\
+            f 1 2 3\n\
+            f 1 3 4\n";
+
+        let parse = |path: &str, merge_quads: bool| {
+            let mut file = fs::File::create(path).unwrap();
+            file.write_all(contents).unwrap();
+
+            let (_, element) = ObjParser::new(path)
+                .merge_quads(merge_quads)
+                .parse_obj(Matrix::id(), Material::default())
+                .unwrap();
+            let _ = fs::remove_file(path);
+
+            element
+        };
+
+        let triangles = parse("merge_quads_triangles.obj", false);
+        let quads = parse("merge_quads_quads.obj", true);
+
+        let children = |element: &Element| match element {
+            Element::Composite(group) => group.children.len(),
+            _ => 1,
+        };
+        assert!(children(&triangles) == 2 && children(&quads) == 1);
+
+        for (x, z) in [
+            (0.1, -0.2),
+            (0.5, -0.5),
+            (-0.9, 0.9),
+            (0.99, 0.2),
+            (1.5, 0.0),
+            (-0.2, -1.2),
+        ] {
+            let ray = Ray {
+                origin: Vector::point(x, 2.0, z),
+                direction: Vector::vector(0.0, -1.0, 0.0),
+                differentials: None,
+                time: 0.0,
+            };
+
+            let (mut expected, mut is) = (vec![], vec![]);
+            triangles.intersect(ray, &mut expected);
+            quads.intersect(ray, &mut is);
+
+            let ts = |is: &Vec<Intersection>| is.iter().map(|i| i.t).collect::<Vec<f64>>();
+            assert!(ts(&is).approx(&ts(&expected)));
+        }
+    }
+
+    #[test]
+    fn flip_handedness() {
+        // a tetrahedron wound counter-clockwise when seen from outside
+        let contents = b"v 0 0 0\n\
+            v 1 0 0\n\
+            v 0 1 0\n\
+            v 0 0 1\n\
+            f 1 3 2\n\
+            f 1 2 4\n\
+            f 1 4 3\n\
+            f 2 3 4\n";
+
+        let outward = |path: &str, flip: bool| {
+            let mut file = fs::File::create(path).unwrap();
+            file.write_all(contents).unwrap();
+
+            let (_, element) = ObjParser::new(path)
+                .flip_handedness(flip)
+                .parse_obj(Matrix::id(), Material::default())
+                .unwrap();
+            let _ = fs::remove_file(path);
+
+            let center = Vector::point(0.25, 0.25, if flip { -0.25 } else { 0.25 });
+            let children = match element {
+                Element::Composite(group) => group.children,
+                _ => vec![],
+            };
+
+            children.len() == 4
+                && children.iter().all(|child| match child {
+                    Element::Primitive(shape) => match shape.geometry {
+                        Geometry::Triangle { p1, p2, p3, .. } => {
+                            let n = shape.normal(p1, None, None);
+                            let centroid = Vector::point(
+                                (p1.x + p2.x + p3.x) / 3.0,
+                                (p1.y + p2.y + p3.y) / 3.0,
+                                (p1.z + p2.z + p3.z) / 3.0,
+                            );
+                            n.dot(centroid - center) > 0.0
+                        }
+                        _ => false,
+                    },
+                    _ => false,
+                })
+        };
+
+        assert!(
+            !outward("flip_handedness_off.obj", false) && outward("flip_handedness_on.obj", true)
+        )
+    }
+
     #[test]
     fn triangulate() {
         let indices = vec![1, 2, 3, 4, 5];
@@ -606,6 +1304,124 @@ mod tests {
         )
     }
 
+    #[test]
+    fn mtl_records() {
+        let contents = "# exported\n\
+            newmtl red\n\
+            Ka 0.3 0.3 0.3\n\
+            Kd 1 0 0\n\
+            Ks 0.5 0.5 0.5\n\
+            Ns 50\n\
+            \n\
+            newmtl glass\n\
+            \td 0.25\n\
+            \tNi 1.5\n\
+            illum 7\n";
+
+        let base = Material {
+            reflective: 0.5,
+            ..Material::default()
+        };
+        let materials = parse_mtl(contents, &base);
+
+        let red = Material {
+            pattern: Pattern::plain(Color::new(1.0, 0.0, 0.0)),
+            ambient: 0.3,
+            diffuse: 1.0,
+            specular: 1.0,
+            specular_color: Color::new(0.5, 0.5, 0.5),
+            shininess: 50.0,
+            ..base.clone()
+        };
+        let glass = Material {
+            transparency: 0.75,
+            refractive_index: 1.5,
+            ..base.clone()
+        };
+
+        assert!(
+            materials.len() == 2
+                && materials["red"].approx(&red)
+                && materials["glass"].approx(&glass)
+        )
+    }
+
+    #[test]
+    fn obj_materials() {
+        let mtl = b"newmtl red\n\
+            Kd 1 0 0\n\
+            newmtl blue\n\
+            Kd 0 0 1\n";
+        let obj = b"mtllib obj_materials.mtl\n\
+            v 0 0 0\n\
+            v 1 0 0\n\
+            v 0 1 0\n\
+            v 5 0 0\n\
+            v 6 0 0\n\
+            v 5 1 0\n\
+            usemtl red\n\
+            f 1 2 3\n\
+            usemtl blue\n\
+            f 4 5 6\n";
+
+        fs::File::create("obj_materials.mtl")
+            .unwrap()
+            .write_all(mtl)
+            .unwrap();
+        let (ignored, element) = parse_obj("obj_materials.obj", obj);
+        let _ = fs::remove_file("obj_materials.mtl");
+
+        let color_at = |x: f64| {
+            let ray = Ray {
+                origin: Vector::point(x + 0.25, 0.25, -1.0),
+                direction: Vector::vector(0.0, 0.0, 1.0),
+                differentials: None,
+                time: 0.0,
+            };
+            let mut is = vec![];
+            element.intersect(ray, &mut is);
+
+            is[0]
+                .shape
+                .material
+                .pattern
+                .color_at(Vector::point(0.0, 0.0, 0.0))
+        };
+
+        assert!(
+            ignored.is_empty()
+                && color_at(0.0).approx(&Color::new(1.0, 0.0, 0.0))
+                && color_at(5.0).approx(&Color::new(0.0, 0.0, 1.0))
+        )
+    }
+
+    #[test]
+    fn obj_missing_material_library() {
+        let obj = b"mtllib does_not_exist.mtl\n\
+            v 0 0 0\n\
+            v 1 0 0\n\
+            v 0 1 0\n\
+            usemtl red\n\
+            f 1 2 3\n";
+
+        let (ignored, element) = parse_obj("obj_missing_material_library.obj", obj);
+
+        let ray = Ray {
+            origin: Vector::point(0.25, 0.25, -1.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        element.intersect(ray, &mut is);
+
+        assert!(
+            ignored == vec![(1, "mtllib does_not_exist.mtl".to_string())]
+                && is.len() == 1
+                && is[0].shape.material.approx(&Material::default())
+        )
+    }
+
     #[test]
     fn obj() {
         let contents = b"v -1 1 0\n\
@@ -1,9 +1,20 @@
 use crate::linalg::{Matrix, Vector};
 
+#[derive(Debug, Clone, Copy)]
+pub struct RayDifferentials {
+    // where the neighbouring rays start, only parallel projections move these
+    pub origin_dx: Vector,
+    pub origin_dy: Vector,
+    pub dx: Vector,
+    pub dy: Vector,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Vector,
     pub direction: Vector,
+    pub differentials: Option<RayDifferentials>,
+    pub time: f64,
 }
 
 impl Ray {
@@ -15,11 +26,19 @@ impl Ray {
         Ray {
             origin: matrix * self.origin,
             direction: matrix * self.direction,
+            differentials: self.differentials.map(|differentials| RayDifferentials {
+                origin_dx: matrix * differentials.origin_dx,
+                origin_dy: matrix * differentials.origin_dy,
+                dx: matrix * differentials.dx,
+                dy: matrix * differentials.dy,
+            }),
+            time: self.time,
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
@@ -35,6 +54,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(2.0, 3.0, 4.0),
             direction: Vector::vector(1.0, 0.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
 
         assert!(ray.position(t).approx(&point))
@@ -46,6 +67,8 @@ mod tests {
         let ray1 = Ray {
             origin: Vector::point(1.0, 2.0, 3.0),
             direction: Vector::vector(0.0, 1.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
         let ray2 = ray1.transform(transform);
 
@@ -0,0 +1,390 @@
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::light::{Light, PointLight};
+use crate::linalg::{Matrix, Vector};
+use crate::material::{Material, Pattern};
+use crate::shape::{Element, GroupKind, ShapeArgs, SingularTransform};
+use crate::world::World;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(serde_yaml::Error),
+    UnknownMaterial(String),
+    SingularTransform,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "{}", error),
+            SceneError::Parse(error) => write!(f, "{}", error),
+            SceneError::UnknownMaterial(name) => write!(f, "unknown material '{}'", name),
+            SceneError::SingularTransform => write!(f, "{}", SingularTransform),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> SceneError {
+        SceneError::Io(error)
+    }
+}
+
+impl From<serde_yaml::Error> for SceneError {
+    fn from(error: serde_yaml::Error) -> SceneError {
+        SceneError::Parse(error)
+    }
+}
+
+impl From<SingularTransform> for SceneError {
+    fn from(_: SingularTransform) -> SceneError {
+        SceneError::SingularTransform
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    camera: CameraDescription,
+    #[serde(default)]
+    lights: Vec<LightDescription>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDescription>,
+    #[serde(default)]
+    shapes: Vec<ShapeDescription>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct CameraDescription {
+    width: usize,
+    height: usize,
+    field_of_view: f64,
+    from: [f64; 3],
+    to: [f64; 3],
+    up: [f64; 3],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDescription {
+    at: [f64; 3],
+    intensity: [f64; 3],
+}
+
+// every field left out keeps the value of `Material::default()`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct MaterialDescription {
+    color: Option<[f64; 3]>,
+    ambient: Option<f64>,
+    diffuse: Option<f64>,
+    specular: Option<f64>,
+    shininess: Option<f64>,
+    reflective: Option<f64>,
+    transparency: Option<f64>,
+    refractive_index: Option<f64>,
+    emission: Option<[f64; 3]>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MaterialReference {
+    Named(String),
+    Inline(MaterialDescription),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Transform {
+    Translate([f64; 3]),
+    Scale([f64; 3]),
+    RotateX(f64),
+    RotateY(f64),
+    RotateZ(f64),
+    Shear([f64; 6]),
+}
+
+#[derive(Debug, Deserialize)]
+struct ShapeDescription {
+    #[serde(flatten)]
+    kind: ShapeKind,
+    // `- translate: [x, y, z]` instead of serde_yaml's tagged `- !translate [x, y, z]`
+    #[serde(default, with = "serde_yaml::with::singleton_map_recursive")]
+    transform: Vec<Transform>,
+    material: Option<MaterialReference>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum ShapeKind {
+    Sphere,
+    Plane,
+    Cube,
+    Cylinder {
+        min: f64,
+        max: f64,
+        #[serde(default)]
+        closed: bool,
+    },
+    Cone {
+        min: f64,
+        max: f64,
+        #[serde(default)]
+        closed: bool,
+    },
+    Group {
+        children: Vec<ShapeDescription>,
+    },
+}
+
+fn vector(v: [f64; 3]) -> Vector {
+    Vector::vector(v[0], v[1], v[2])
+}
+
+fn point(p: [f64; 3]) -> Vector {
+    Vector::point(p[0], p[1], p[2])
+}
+
+fn color(c: [f64; 3]) -> Color {
+    Color::new(c[0], c[1], c[2])
+}
+
+// the first transform in the list is applied first
+fn transform(transforms: &[Transform]) -> Matrix {
+    transforms
+        .iter()
+        .fold(Matrix::id(), |matrix, transform| match *transform {
+            Transform::Translate([x, y, z]) => matrix.translate(x, y, z),
+            Transform::Scale([x, y, z]) => matrix.scale(x, y, z),
+            Transform::RotateX(r) => matrix.rotate_x(r),
+            Transform::RotateY(r) => matrix.rotate_y(r),
+            Transform::RotateZ(r) => matrix.rotate_z(r),
+            Transform::Shear([x_y, x_z, y_x, y_z, z_x, z_y]) => {
+                matrix.shear(x_y, x_z, y_x, y_z, z_x, z_y)
+            }
+        })
+}
+
+fn material(description: &MaterialDescription) -> Material {
+    let default = Material::default();
+
+    Material {
+        pattern: description
+            .color
+            .map(|c| Pattern::plain(color(c)))
+            .unwrap_or(default.pattern.clone()),
+        ambient: description.ambient.unwrap_or(default.ambient),
+        diffuse: description.diffuse.unwrap_or(default.diffuse),
+        specular: description.specular.unwrap_or(default.specular),
+        shininess: description.shininess.unwrap_or(default.shininess),
+        reflective: description.reflective.unwrap_or(default.reflective),
+        transparency: description.transparency.unwrap_or(default.transparency),
+        refractive_index: description
+            .refractive_index
+            .unwrap_or(default.refractive_index),
+        emission: description.emission.map(color).unwrap_or(default.emission),
+        ..default
+    }
+}
+
+fn element(
+    description: &ShapeDescription,
+    materials: &HashMap<String, Material>,
+) -> Result<Element, SceneError> {
+    let material = match &description.material {
+        Some(MaterialReference::Named(name)) => Some(
+            materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| SceneError::UnknownMaterial(name.clone()))?,
+        ),
+        Some(MaterialReference::Inline(description)) => Some(material(description)),
+        None => None,
+    };
+
+    let args = ShapeArgs {
+        transform: transform(&description.transform),
+        material: material.clone().unwrap_or_default(),
+        ..ShapeArgs::default()
+    };
+    args.validate()?;
+
+    Ok(match &description.kind {
+        ShapeKind::Sphere => Element::sphere(args),
+        ShapeKind::Plane => Element::plane(args),
+        ShapeKind::Cube => Element::cube(args),
+        ShapeKind::Cylinder { min, max, closed } => Element::cylinder(args, *min, *max, *closed),
+        ShapeKind::Cone { min, max, closed } => Element::cone(args, *min, *max, *closed),
+        ShapeKind::Group { children } => {
+            let children = children
+                .iter()
+                .map(|child| element(child, materials))
+                .collect::<Result<_, _>>()?;
+
+            Element::try_composite(args.transform, material, GroupKind::Aggregation, children)?
+        }
+    })
+}
+
+pub fn parse_scene(yaml: &str) -> Result<(Camera, World), SceneError> {
+    let description: SceneDescription = serde_yaml::from_str(yaml)?;
+
+    let camera = {
+        let c = &description.camera;
+        let transform = Camera::transform(point(c.from), point(c.to), vector(c.up));
+        Camera::new(c.width, c.height, c.field_of_view, transform)
+    };
+
+    let lights = description
+        .lights
+        .iter()
+        .map(|light| {
+            Light::from(PointLight {
+                intensity: color(light.intensity),
+                origin: point(light.at),
+            })
+        })
+        .collect();
+
+    let materials: HashMap<String, Material> = description
+        .materials
+        .iter()
+        .map(|(name, description)| (name.clone(), material(description)))
+        .collect();
+
+    let elements = description
+        .shapes
+        .iter()
+        .map(|shape| element(shape, &materials))
+        .collect::<Result<_, _>>()?;
+
+    let world = World {
+        lights,
+        elements,
+        ..World::default()
+    };
+
+    Ok((camera, world))
+}
+
+pub fn load_scene<P: AsRef<Path>>(path: P) -> Result<(Camera, World), SceneError> {
+    parse_scene(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::approx::Approx;
+
+    use std::f64::consts::PI;
+
+    #[test]
+    fn parse_small_scene() {
+        let yaml = "
+camera:
+  width: 100
+  height: 50
+  field-of-view: 1.0
+  from: [0, 1.5, -5]
+  to: [0, 1, 0]
+  up: [0, 1, 0]
+lights:
+  - at: [-10, 10, -10]
+    intensity: [1, 1, 1]
+materials:
+  glass:
+    transparency: 0.9
+    refractive-index: 1.5
+shapes:
+  - type: sphere
+    transform:
+      - scale: [0.5, 0.5, 0.5]
+      - translate: [1, 0, 0]
+    material: glass
+  - type: cylinder
+    min: 0
+    max: 2
+    closed: true
+    transform:
+      - rotate-x: 1.5707963267948966
+    material:
+      color: [1, 0, 0]
+";
+
+        let (camera, world) = parse_scene(yaml).unwrap();
+
+        let glass = Material {
+            transparency: 0.9,
+            refractive_index: 1.5,
+            ..Material::default()
+        };
+        let sphere = Element::sphere(ShapeArgs {
+            transform: Matrix::translation(1.0, 0.0, 0.0) * Matrix::scaling(0.5, 0.5, 0.5),
+            material: glass,
+            ..ShapeArgs::default()
+        });
+        let cylinder = Element::cylinder(
+            ShapeArgs {
+                transform: Matrix::rotation_x(PI / 2.0),
+                material: Material {
+                    pattern: Pattern::plain(Color::new(1.0, 0.0, 0.0)),
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            },
+            0.0,
+            2.0,
+            true,
+        );
+
+        assert!(
+            camera.hsize == 100
+                && camera.vsize == 50
+                && camera.field_of_view.approx(&1.0)
+                && world.lights.len() == 1
+                && world.lights[0]
+                    .origin()
+                    .approx(&Vector::point(-10.0, 10.0, -10.0))
+                && world.elements.len() == 2
+                && world.elements[0].approx(&sphere)
+                && world.elements[1].approx(&cylinder)
+        )
+    }
+
+    #[test]
+    fn parse_scene_errors() {
+        let camera = "
+camera:
+  width: 10
+  height: 10
+  field-of-view: 1.0
+  from: [0, 0, -5]
+  to: [0, 0, 0]
+  up: [0, 1, 0]
+";
+        let unknown = format!("{}shapes:\n  - type: sphere\n    material: gold\n", camera);
+        let singular = format!(
+            "{}shapes:\n  - type: cube\n    transform:\n      - scale: [0, 1, 1]\n",
+            camera
+        );
+
+        assert!(
+            matches!(parse_scene(&unknown), Err(SceneError::UnknownMaterial(name)) if name == "gold")
+                && matches!(parse_scene(&singular), Err(SceneError::SingularTransform))
+                && matches!(parse_scene("camera: 1"), Err(SceneError::Parse(_)))
+        )
+    }
+}
@@ -3,33 +3,36 @@
 use crate::approx::Approx;
 use crate::bounding_box::BoundingBox;
 use crate::color::Color;
-use crate::config::EPSILON;
+use crate::config::{EDGE_EPSILON, EPSILON};
 use crate::intersection::Intersection;
-use crate::light::PointLight;
-use crate::linalg::{Matrix, Vector};
-use crate::material::Material;
+use crate::light::Light;
+use crate::linalg::{polynomial, Matrix, Vector};
+use crate::material::{consts::alpha, Material};
 // use crate::material::Pattern;
 use crate::ray::Ray;
+#[cfg(feature = "stats")]
+use crate::stats::RenderStats;
 
-use std::cell::Cell;
 use std::default::Default;
+use std::f64::consts::PI;
+use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-thread_local! {
-    static ID: Cell<usize> = Cell::new(0);
-}
+use rayon::prelude::*;
+
+static ID: AtomicUsize = AtomicUsize::new(0);
 
 fn next_id() -> usize {
-    ID.with(|cell| {
-        let i = cell.get();
-        cell.set(i + 1);
-        i
-    })
+    ID.fetch_add(1, Ordering::Relaxed)
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum Element {
     Composite(Group),
+    Lazy(LazyGroup),
     Primitive(Shape),
 }
 
@@ -37,6 +40,7 @@ impl Approx<Element> for Element {
     fn approx(&self, other: &Element) -> bool {
         match (self, other) {
             (Element::Composite(sgroup), Element::Composite(ogroup)) => sgroup.approx(ogroup),
+            (Element::Lazy(slazy), Element::Lazy(olazy)) => slazy.approx(olazy),
             (Element::Primitive(sshape), Element::Primitive(oshape)) => sshape.approx(oshape),
             (_, _) => false,
         }
@@ -58,7 +62,14 @@ impl Element {
                 }
                 group.bbox = group.bbox.transform(transform);
             }
+            // the children may be shared with other instances, so they keep their own materials
+            Element::Lazy(lazy) => {
+                lazy.transform_inv = lazy.transform_inv * inv;
+                lazy.bbox = lazy.bbox.transform(transform);
+            }
             Element::Primitive(shape) => {
+                shape.bbox = shape.bbox.transform(transform);
+                shape.motion = transform * shape.motion;
                 shape.transform_inv = shape.transform_inv * inv;
                 shape.transform_inv_tsp = inv_tsp * shape.transform_inv_tsp;
                 if let Some(material) = material {
@@ -77,19 +88,141 @@ impl Element {
         kind: GroupKind,
         children: Vec<Element>,
     ) -> Element {
-        match kind {
-            GroupKind::Aggregation => (),
-            _ => assert!(children.len() == 2),
+        let mut bbox = BoundingBox::empty();
+        for child in &children {
+            bbox = bbox.union(&child.bbox());
         }
 
-        let inv = transform.inverse();
-        let inv_tsp = inv.transpose();
+        Element::composite_with_bbox(transform, material, kind, children, bbox)
+    }
+
+    pub fn try_composite(
+        transform: Matrix,
+        material: Option<Material>,
+        kind: GroupKind,
+        children: Vec<Element>,
+    ) -> Result<Element, SingularTransform> {
+        transform.try_inverse().ok_or(SingularTransform)?;
+
+        Ok(Element::composite(transform, material, kind, children))
+    }
+
+    pub fn union(
+        transform: Matrix,
+        material: Option<Material>,
+        left: Element,
+        right: Element,
+    ) -> Element {
+        Element::composite(transform, material, GroupKind::Union, vec![left, right])
+    }
+
+    pub fn intersection(
+        transform: Matrix,
+        material: Option<Material>,
+        left: Element,
+        right: Element,
+    ) -> Element {
+        Element::composite(
+            transform,
+            material,
+            GroupKind::Intersection,
+            vec![left, right],
+        )
+    }
+
+    pub fn difference(
+        transform: Matrix,
+        material: Option<Material>,
+        left: Element,
+        right: Element,
+    ) -> Element {
+        Element::composite(
+            transform,
+            material,
+            GroupKind::Difference,
+            vec![left, right],
+        )
+    }
+
+    pub fn union_all(children: Vec<Element>) -> Element {
+        Element::fold_csg(children, Element::union)
+    }
+
+    pub fn intersection_all(children: Vec<Element>) -> Element {
+        Element::fold_csg(children, Element::intersection)
+    }
+
+    pub fn difference_all(children: Vec<Element>) -> Element {
+        Element::fold_csg(children, Element::difference)
+    }
+
+    // ((a op b) op c) op ..., the first child is the one everything else is applied to
+    fn fold_csg(
+        children: Vec<Element>,
+        csg: fn(Matrix, Option<Material>, Element, Element) -> Element,
+    ) -> Element {
+        let mut children = children.into_iter();
+
+        match children.next() {
+            Some(first) => children.fold(first, |left, right| csg(Matrix::id(), None, left, right)),
+            None => Element::composite(Matrix::id(), None, GroupKind::Aggregation, vec![]),
+        }
+    }
+
+    pub fn lazy(transform: Matrix, children: Vec<Element>) -> Element {
+        Element::instance(transform, Arc::from(children))
+    }
 
+    // every instance of the same children only adds its own transform and bbox
+    pub fn instance(transform: Matrix, children: Arc<[Element]>) -> Element {
         let mut bbox = BoundingBox::empty();
-        for child in &children {
+        for child in children.iter() {
             bbox = bbox.union(&child.bbox());
         }
 
+        Element::Lazy(LazyGroup {
+            transform_inv: transform.inverse(),
+            bbox: bbox.transform(transform),
+            children,
+        })
+    }
+
+    pub fn composite_par<F>(
+        transform: Matrix,
+        material: Option<Material>,
+        kind: GroupKind,
+        children_fn: F,
+        n: usize,
+    ) -> Element
+    where
+        F: Fn(usize) -> Element + Sync + Send,
+    {
+        let children = (0..n).into_par_iter().map(children_fn).collect();
+
+        Element::composite(transform, material, kind, children)
+    }
+
+    pub fn composite_with_bbox(
+        transform: Matrix,
+        material: Option<Material>,
+        kind: GroupKind,
+        children: Vec<Element>,
+        bbox: BoundingBox,
+    ) -> Element {
+        match kind {
+            GroupKind::Aggregation => (),
+            _ => assert!(children.len() == 2),
+        }
+
+        debug_assert!(children
+            .iter()
+            .all(|child| child.bbox().is_empty() || bbox.encloses(&child.bbox())));
+
+        let inv = transform
+            .try_inverse()
+            .expect("group transform is not invertible");
+        let inv_tsp = inv.transpose();
+
         let mut composite = Element::Composite(Group {
             kind,
             bbox,
@@ -120,10 +253,40 @@ impl Element {
         Element::Primitive(Shape::cone(args, min, max, closed))
     }
 
+    pub fn frustum(
+        args: ShapeArgs,
+        min: f64,
+        max: f64,
+        bottom_radius: f64,
+        top_radius: f64,
+        closed: bool,
+    ) -> Element {
+        Element::Primitive(Shape::frustum(
+            args,
+            min,
+            max,
+            bottom_radius,
+            top_radius,
+            closed,
+        ))
+    }
+
+    pub fn disk(args: ShapeArgs, inner: f64, outer: f64) -> Element {
+        Element::Primitive(Shape::disk(args, inner, outer))
+    }
+
+    pub fn torus(args: ShapeArgs, major: f64, minor: f64) -> Element {
+        Element::Primitive(Shape::torus(args, major, minor))
+    }
+
     pub fn triangle(args: ShapeArgs, p1: Vector, p2: Vector, p3: Vector) -> Element {
         Element::Primitive(Shape::triangle(args, p1, p2, p3))
     }
 
+    pub fn quad(args: ShapeArgs, p1: Vector, p2: Vector, p3: Vector, p4: Vector) -> Element {
+        Element::Primitive(Shape::quad(args, p1, p2, p3, p4))
+    }
+
     pub fn smooth_triangle(
         args: ShapeArgs,
         p1: Vector,
@@ -136,26 +299,219 @@ impl Element {
         Element::Primitive(Shape::smooth_triangle(args, p1, p2, p3, n1, n2, n3))
     }
 
+    // heights[row][col] over the unit square in xz, uvs follow the grid position
+    pub fn heightfield(
+        transform: Matrix,
+        material: Option<Material>,
+        heights: &[Vec<f64>],
+    ) -> Element {
+        let rows = heights.len();
+        let cols = heights.first().map(|row| row.len()).unwrap_or(0);
+        assert!(rows >= 2 && cols >= 2 && heights.iter().all(|row| row.len() == cols));
+
+        let uv = |row: usize, col: usize| {
+            (
+                col as f64 / (cols - 1) as f64,
+                row as f64 / (rows - 1) as f64,
+            )
+        };
+        let vertex = |row: usize, col: usize| {
+            let (u, v) = uv(row, col);
+            Vector::point(u, heights[row][col], v)
+        };
+
+        let mut children = vec![];
+        for row in 0..(rows - 1) {
+            for col in 0..(cols - 1) {
+                let corners = [
+                    (row, col),
+                    (row, col + 1),
+                    (row + 1, col + 1),
+                    (row + 1, col),
+                ];
+                for [a, b, c] in [[0, 1, 2], [0, 2, 3]] {
+                    let (a, b, c) = (corners[a], corners[b], corners[c]);
+                    let triangle = Shape::triangle(
+                        ShapeArgs::default(),
+                        vertex(a.0, a.1),
+                        vertex(b.0, b.1),
+                        vertex(c.0, c.1),
+                    )
+                    .with_uvs([uv(a.0, a.1), uv(b.0, b.1), uv(c.0, c.1)]);
+
+                    children.push(Element::Primitive(triangle));
+                }
+            }
+        }
+
+        Element::composite(transform, material, GroupKind::Aggregation, children)
+    }
+
     pub fn intersect<'a>(&'a self, ray: Ray, intersections: &mut Vec<Intersection<'a>>) {
+        self.intersect_bounded(ray, f64::INFINITY, intersections)
+    }
+
+    pub fn intersect_bounded<'a>(
+        &'a self,
+        ray: Ray,
+        t_max: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
         match self {
-            Element::Composite(group) => group.intersect(ray, intersections),
-            Element::Primitive(shape) => shape.intersect(ray, intersections),
+            Element::Composite(group) => group.intersect_bounded(ray, t_max, intersections),
+            Element::Lazy(lazy) => lazy.intersect_bounded(ray, t_max, intersections),
+            Element::Primitive(shape) => shape.intersect_bounded(ray, t_max, intersections),
         }
     }
 
     pub fn bbox(&self) -> BoundingBox {
         match self {
             Element::Composite(group) => group.bbox,
+            Element::Lazy(lazy) => lazy.bbox,
             Element::Primitive(shape) => shape.bbox,
         }
     }
 
+    pub fn bbox_tree(&self) -> BBoxNode {
+        let children = match self {
+            Element::Composite(group) => group.children.iter().map(Element::bbox_tree).collect(),
+            Element::Lazy(lazy) => lazy.children.iter().map(Element::bbox_tree).collect(),
+            Element::Primitive(_) => vec![],
+        };
+
+        BBoxNode {
+            bbox: self.bbox(),
+            children,
+        }
+    }
+
+    pub fn centroid(&self) -> Vector {
+        self.bbox().center()
+    }
+
+    // each transform of a group grows its box around the already transformed one,
+    // refit bottom up from the primitives' own geometry instead
+    pub fn tighten_bounds(&mut self) {
+        match self {
+            Element::Composite(group) => {
+                let mut bbox = BoundingBox::empty();
+                for child in &mut group.children {
+                    child.tighten_bounds();
+                    bbox = bbox.union(&child.bbox());
+                }
+                group.bbox = bbox;
+            }
+            // its children live in object space, so only they are refit, and only if no
+            // other instance shares them, the group's own box then follows their boxes
+            Element::Lazy(lazy) => {
+                if let Some(children) = Arc::get_mut(&mut lazy.children) {
+                    for child in children {
+                        child.tighten_bounds();
+                    }
+                }
+                let mut bbox = BoundingBox::empty();
+                for child in lazy.children.iter() {
+                    bbox = bbox.union(&child.bbox());
+                }
+                lazy.bbox = bbox.transform(lazy.transform_inv.inverse());
+            }
+            Element::Primitive(shape) => {
+                let motion = shape.motion;
+                let bbox = shape
+                    .geometry
+                    .bbox()
+                    .transform(shape.transform_inv.inverse());
+                shape.bbox =
+                    bbox.union(&bbox.transform(Matrix::translation(motion.x, motion.y, motion.z)));
+            }
+        }
+    }
+
+    pub fn subdivide(&mut self, threshold: usize) {
+        match self {
+            Element::Composite(group) => group.subdivide(threshold),
+            Element::Lazy(lazy) => {
+                if let Some(children) = Arc::get_mut(&mut lazy.children) {
+                    for child in children {
+                        child.subdivide(threshold);
+                    }
+                }
+            }
+            Element::Primitive(_) => (),
+        }
+    }
+
     pub fn includes(&self, shape: &Shape) -> bool {
         match self {
             Element::Composite(group) => group.includes(shape),
+            Element::Lazy(lazy) => lazy.children.iter().any(|child| child.includes(shape)),
             Element::Primitive(s) => *s == *shape,
         }
     }
+
+    pub fn closest_surface(&self, point: Vector) -> Option<(f64, &Shape)> {
+        self.closest_point(point)
+            .map(|(closest, shape)| ((closest - point).magnitude(), shape))
+    }
+
+    // the closest point is returned in the same space as `point`
+    fn closest_point(&self, point: Vector) -> Option<(Vector, &Shape)> {
+        let nearest = |a: &(Vector, &Shape), b: &(Vector, &Shape)| {
+            let da = (a.0 - point).magnitude();
+            let db = (b.0 - point).magnitude();
+            da.total_cmp(&db)
+        };
+
+        match self {
+            Element::Composite(group) => group
+                .children
+                .iter()
+                .filter_map(|child| child.closest_point(point))
+                .min_by(nearest),
+            Element::Lazy(lazy) => {
+                let object_point = lazy.transform_inv * point;
+                let transform = lazy.transform_inv.inverse();
+                lazy.children
+                    .iter()
+                    .filter_map(|child| child.closest_point(object_point))
+                    .map(|(closest, shape)| (transform * closest, shape))
+                    .min_by(nearest)
+            }
+            Element::Primitive(shape) => shape.closest_point(point).map(|closest| (closest, shape)),
+        }
+    }
+
+    pub fn contains_point(&self, point: Vector) -> bool {
+        match self {
+            Element::Composite(group) => {
+                let mut contains = group
+                    .children
+                    .iter()
+                    .map(|child| child.contains_point(point));
+
+                match group.kind {
+                    GroupKind::Aggregation => contains.any(|inside| inside),
+                    _ => {
+                        let in_left = contains.next().unwrap();
+                        let in_right = contains.next().unwrap();
+
+                        match group.kind {
+                            GroupKind::Union => in_left || in_right,
+                            GroupKind::Intersection => in_left && in_right,
+                            _ => in_left && !in_right,
+                        }
+                    }
+                }
+            }
+            Element::Lazy(lazy) => {
+                let point = lazy.transform_inv * point;
+                lazy.children
+                    .iter()
+                    .any(|child| child.contains_point(point))
+            }
+            Element::Primitive(shape) => shape.contains_point(point),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -223,15 +579,88 @@ impl Approx<Group> for Group {
 // }
 
 impl Group {
+    fn aggregation(children: Vec<Element>) -> Element {
+        let mut bbox = BoundingBox::empty();
+        for child in &children {
+            bbox = bbox.union(&child.bbox());
+        }
+
+        Element::Composite(Group {
+            kind: GroupKind::Aggregation,
+            bbox,
+            children,
+        })
+    }
+
+    // children are assigned by the centroid of their bbox, so a shape straddling
+    // the split plane always ends up on exactly one side; unbounded children
+    // (planes) stay at this level
+    fn partition(&mut self) -> Option<(Vec<Element>, Vec<Element>)> {
+        let mut bounds = BoundingBox::empty();
+        for child in &self.children {
+            if child.bbox().is_bounded() {
+                bounds = bounds.union(&child.bbox());
+            }
+        }
+        if !bounds.is_bounded() {
+            return None;
+        }
+
+        let axis = bounds.longest_axis();
+        let split = bounds.center()[axis];
+
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = self
+            .children
+            .drain(..)
+            .partition(|child| child.bbox().is_bounded());
+        self.children = unbounded;
+
+        let (left, right): (Vec<_>, Vec<_>) = bounded
+            .into_iter()
+            .partition(|child| child.bbox().center()[axis] < split);
+
+        Some((left, right))
+    }
+
+    pub fn subdivide(&mut self, threshold: usize) {
+        if let GroupKind::Aggregation = self.kind {
+            if self.children.len() > threshold.max(2) {
+                if let Some((left, right)) = self.partition() {
+                    if left.is_empty() || right.is_empty() {
+                        self.children.extend(left);
+                        self.children.extend(right);
+                    } else {
+                        self.children.push(Group::aggregation(left));
+                        self.children.push(Group::aggregation(right));
+                    }
+                }
+            }
+        }
+
+        for child in &mut self.children {
+            child.subdivide(threshold);
+        }
+    }
+
     pub fn includes(&self, shape: &Shape) -> bool {
         self.children.iter().any(|element| element.includes(shape))
     }
 
     pub fn filter_by_group(&self, intersections: &mut Vec<Intersection>) {
+        self.filter_by_group_from(0, intersections)
+    }
+
+    fn filter_by_group_from(&self, start: usize, intersections: &mut Vec<Intersection>) {
         let mut in_left = false;
         let mut in_right = false;
+        let mut index = 0;
 
         intersections.retain(|intersection| {
+            index += 1;
+            if index <= start {
+                return true;
+            }
+
             let left_hit = self.children[0].includes(intersection.shape);
             let keep = self.kind.allows_intersection(left_hit, in_left, in_right);
 
@@ -246,33 +675,122 @@ impl Group {
     }
 
     pub fn intersect<'a>(&'a self, ray: Ray, intersections: &mut Vec<Intersection<'a>>) {
+        self.intersect_bounded(ray, f64::INFINITY, intersections)
+    }
+
+    pub fn intersect_bounded<'a>(
+        &'a self,
+        ray: Ray,
+        t_max: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
         // intersect_bbox(&self.bbox, &DEBUG, ray, intersections);
 
-        if self.bbox.intersects(ray) {
+        let hit = self
+            .bbox
+            .intersects_t(ray)
+            .filter(|&(t_enter, _)| t_enter <= t_max);
+        #[cfg(feature = "stats")]
+        RenderStats::count_bbox(hit.is_some());
+
+        if hit.is_some() {
             match self.kind {
                 GroupKind::Aggregation => {
                     for child in &self.children {
-                        child.intersect(ray, intersections);
+                        child.intersect_bounded(ray, t_max, intersections);
                     }
                 }
                 _ => {
-                    let mut tmp = vec![];
+                    // children append behind whatever is already buffered, so the
+                    // CSG filter only has to run over that tail
+                    let start = intersections.len();
                     for child in &self.children {
-                        child.intersect(ray, &mut tmp);
+                        child.intersect_bounded(ray, t_max, intersections);
                     }
-                    Intersection::sort(&mut tmp);
-                    self.filter_by_group(&mut tmp);
-                    intersections.append(&mut tmp);
+                    intersections[start..].sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+                    self.filter_by_group_from(start, intersections);
                 }
             }
         }
     }
 }
 
+// children of a lazy group report their bboxes in its object space
+#[derive(Debug, Clone)]
+pub struct BBoxNode {
+    pub bbox: BoundingBox,
+    pub children: Vec<BBoxNode>,
+}
+
+#[derive(Debug)]
+pub struct LazyGroup {
+    pub transform_inv: Matrix,
+    pub bbox: BoundingBox,
+    pub children: Arc<[Element]>,
+}
+
+impl Approx<LazyGroup> for LazyGroup {
+    fn approx(&self, other: &LazyGroup) -> bool {
+        self.transform_inv.approx(&other.transform_inv)
+            && self.bbox.approx(&other.bbox)
+            && self.children.len() == other.children.len()
+            && self
+                .children
+                .iter()
+                .zip(other.children.iter())
+                .all(|(a, b)| a.approx(b))
+    }
+}
+
+impl LazyGroup {
+    pub fn intersect_bounded<'a>(
+        &'a self,
+        ray: Ray,
+        t_max: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
+        let hit = self
+            .bbox
+            .intersects_t(ray)
+            .filter(|&(t_enter, _)| t_enter <= t_max);
+        #[cfg(feature = "stats")]
+        RenderStats::count_bbox(hit.is_some());
+
+        if hit.is_some() {
+            // the direction is not renormalized, so t carries over unchanged
+            let object_ray = ray.transform(self.transform_inv);
+            let start = intersections.len();
+            for child in self.children.iter() {
+                child.intersect_bounded(object_ray, t_max, intersections);
+            }
+            // the children are shared, so the chain back to world space is composed on the way out
+            for intersection in &mut intersections[start..] {
+                intersection.frame = Some(match intersection.frame {
+                    Some(frame) => frame * self.transform_inv,
+                    None => self.transform_inv,
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SingularTransform;
+
+impl fmt::Display for SingularTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "transform is not invertible")
+    }
+}
+
+impl std::error::Error for SingularTransform {}
+
 pub struct ShapeArgs {
     pub transform: Matrix,
     pub material: Material,
     pub casts_shadow: bool,
+    pub layer: u32,
+    pub motion: Vector,
 }
 
 impl Default for ShapeArgs {
@@ -281,15 +799,28 @@ impl Default for ShapeArgs {
             transform: Matrix::id(),
             material: Material::default(),
             casts_shadow: true,
+            layer: 1,
+            motion: Vector::vector(0.0, 0.0, 0.0),
         }
     }
 }
 
+impl ShapeArgs {
+    pub fn validate(&self) -> Result<(), SingularTransform> {
+        self.transform
+            .try_inverse()
+            .map(|_| ())
+            .ok_or(SingularTransform)
+    }
+}
+
 impl Approx<ShapeArgs> for ShapeArgs {
     fn approx(&self, other: &ShapeArgs) -> bool {
         self.transform.approx(&other.transform)
             && self.material.approx(&other.material)
             && self.casts_shadow.approx(&other.casts_shadow)
+            && self.layer.approx(&other.layer)
+            && self.motion.approx(&other.motion)
     }
 }
 
@@ -302,6 +833,10 @@ pub struct Shape {
     pub material: Material,
     pub geometry: Geometry,
     pub casts_shadow: bool,
+    pub layer: u32,
+    pub motion: Vector,
+    // name of the OBJ group the shape was imported from
+    pub group: Option<Arc<str>>,
     id: usize,
 }
 
@@ -328,20 +863,31 @@ impl Approx<Shape> for Shape {
             && self.material.approx(&other.material)
             && self.geometry.approx(&other.geometry)
             && self.casts_shadow.approx(&other.casts_shadow)
+            && self.layer.approx(&other.layer)
+            && self.motion.approx(&other.motion)
     }
 }
 
 impl Shape {
+    #[allow(clippy::self_named_constructors)]
     fn shape(args: ShapeArgs, geometry: Geometry) -> Shape {
-        let inv = args.transform.inverse();
+        let inv = args
+            .transform
+            .try_inverse()
+            .expect("shape transform is not invertible");
+        let bbox = geometry.bbox().transform(args.transform);
+        let motion = args.motion;
         Shape {
             transform_inv: inv,
             transform_inv_tsp: inv.transpose(),
-            bbox: geometry.bbox().transform(args.transform),
+            bbox: bbox.union(&bbox.transform(Matrix::translation(motion.x, motion.y, motion.z))),
             material_inv: inv,
             material: args.material,
             geometry,
             casts_shadow: args.casts_shadow,
+            layer: args.layer,
+            motion,
+            group: None,
             id: next_id(),
         }
     }
@@ -366,10 +912,41 @@ impl Shape {
         Shape::shape(args, Geometry::Cone { min, max, closed })
     }
 
+    // the radius runs linearly from bottom_radius at min to top_radius at max
+    pub fn frustum(
+        args: ShapeArgs,
+        min: f64,
+        max: f64,
+        bottom_radius: f64,
+        top_radius: f64,
+        closed: bool,
+    ) -> Shape {
+        assert!(min < max && min.is_finite() && max.is_finite());
+        assert!(bottom_radius >= 0.0 && top_radius >= 0.0);
+
+        Shape::shape(
+            args,
+            Geometry::Frustum {
+                min,
+                max,
+                bottom_radius,
+                top_radius,
+                closed,
+            },
+        )
+    }
+
+    pub fn disk(args: ShapeArgs, inner: f64, outer: f64) -> Shape {
+        Shape::shape(args, Geometry::Disk { inner, outer })
+    }
+
+    pub fn torus(args: ShapeArgs, major: f64, minor: f64) -> Shape {
+        Shape::shape(args, Geometry::Torus { major, minor })
+    }
+
     pub fn triangle(args: ShapeArgs, p1: Vector, p2: Vector, p3: Vector) -> Shape {
         let e1 = p2 - p1;
         let e2 = p3 - p1;
-        let n = e2.cross(e1).normalize();
 
         Shape::shape(
             args,
@@ -379,11 +956,17 @@ impl Shape {
                 p3,
                 e1,
                 e2,
-                n,
+                uvs: None,
             },
         )
     }
 
+    pub fn quad(args: ShapeArgs, p1: Vector, p2: Vector, p3: Vector, p4: Vector) -> Shape {
+        let n = (p3 - p1).cross(p2 - p1).normalize();
+
+        Shape::shape(args, Geometry::Quad { p1, p2, p3, p4, n })
+    }
+
     pub fn smooth_triangle(
         args: ShapeArgs,
         p1: Vector,
@@ -398,7 +981,7 @@ impl Shape {
 
         Shape::shape(
             args,
-            Geometry::SmoothTriangle {
+            Geometry::SmoothTriangle(Box::new(SmoothTriangle {
                 p1,
                 p2,
                 p3,
@@ -407,62 +990,170 @@ impl Shape {
                 n1,
                 n2,
                 n3,
-            },
+                uvs: None,
+            })),
         )
     }
 
     pub fn intersect<'a>(&'a self, ray: Ray, intersections: &mut Vec<Intersection<'a>>) {
-        let ray = ray.transform(self.transform_inv);
-        self.geometry.intersect(self, ray, intersections)
+        self.intersect_bounded(ray, f64::INFINITY, intersections)
     }
 
-    pub fn normal(&self, point: Vector, u: Option<f64>, v: Option<f64>) -> Vector {
-        let shape_point = self.transform_inv * point;
-        let shape_normal = self.geometry.normal(shape_point, u, v);
+    pub fn intersect_bounded<'a>(
+        &'a self,
+        ray: Ray,
+        t_max: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
+        #[cfg(feature = "stats")]
+        RenderStats::count_primitive();
 
-        let mut world_normal = self.transform_inv_tsp * shape_normal;
-        world_normal.w = 0.0;
+        let start = intersections.len();
+        let ray = Ray {
+            origin: self.at_rest(ray.origin, ray.time),
+            ..ray
+        };
 
-        world_normal.normalize()
-    }
+        self.geometry
+            .intersect(self, ray.transform(self.transform_inv), intersections);
 
-    pub fn lighting(
+        let mut index = start;
+        while index < intersections.len() {
+            let t = intersections[index].t;
+            if t > t_max || self.is_masked(ray.position(t)) {
+                intersections.swap_remove(index);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    // a moving shape sweeps along `motion` over the shutter interval [0, 1)
+    pub fn at_rest(&self, point: Vector, time: f64) -> Vector {
+        point - self.motion * time
+    }
+
+    fn is_masked(&self, point: Vector) -> bool {
+        match &self.material.alpha_mask {
+            Some(mask) => {
+                let alpha = mask.color_at(self.material_inv * point);
+                (alpha.r + alpha.g + alpha.b) / 3.0 < alpha::THRESHOLD
+            }
+            None => false,
+        }
+    }
+
+    pub fn normal(&self, point: Vector, u: Option<f64>, v: Option<f64>) -> Vector {
+        let shape_point = self.transform_inv * point;
+        let shape_normal = self.geometry.normal(shape_point, u, v);
+
+        let mut world_normal = self.transform_inv_tsp * shape_normal;
+        world_normal.w = 0.0;
+
+        world_normal.normalize()
+    }
+
+    // only triangles carry texture coordinates, other shapes are left untouched
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Shape {
+        match &mut self.geometry {
+            Geometry::Triangle { uvs: slot, .. } => *slot = Some(uvs),
+            Geometry::SmoothTriangle(triangle) => triangle.uvs = Some(uvs),
+            _ => (),
+        }
+
+        self
+    }
+
+    pub fn with_group(mut self, group: Arc<str>) -> Shape {
+        self.group = Some(group);
+        self
+    }
+
+    pub fn texture_uv(&self, u: Option<f64>, v: Option<f64>) -> Option<(f64, f64)> {
+        self.geometry.texture_uv(u, v)
+    }
+
+    pub fn contains_point(&self, point: Vector) -> bool {
+        self.geometry.contains_point(self.transform_inv * point)
+    }
+
+    pub fn closest_point(&self, point: Vector) -> Option<Vector> {
+        let shape_point = self.transform_inv * point;
+        let closest = self.geometry.closest_point(shape_point)?;
+
+        Some(self.transform_inv.inverse() * closest)
+    }
+
+    pub fn approx_distance(&self, point: Vector) -> Option<f64> {
+        Some((self.closest_point(point)? - point).magnitude())
+    }
+
+    pub fn lighting(
         &self,
-        light: PointLight,
+        light: Light,
         point: Vector,
         eye: Vector,
         normal: Vector,
-        shadowed: bool,
+        intensity: f64,
     ) -> Color {
-        let color = self.material.pattern.color_at(self.material_inv * point);
+        self.lighting_in(&self.material, light, point, point, eye, normal, intensity)
+    }
 
-        let effective_color = color * light.intensity;
-        let intensity = light.intensity;
+    #[allow(clippy::too_many_arguments)]
+    pub fn lighting_in(
+        &self,
+        material: &Material,
+        light: Light,
+        point: Vector,
+        pattern_point: Vector,
+        eye: Vector,
+        normal: Vector,
+        intensity: f64,
+    ) -> Color {
+        let color = material.pattern.color_at(self.material_inv * pattern_point);
 
-        let light = (light.origin - point).normalize();
+        let effective_color = color * light.intensity();
 
-        let ambient = effective_color * self.material.ambient;
+        let ambient = effective_color * material.ambient;
         let mut diffuse = Color::black();
         let mut specular = Color::black();
 
-        let light_dot_normal = light.dot(normal);
-        if !shadowed && light_dot_normal >= 0.0 {
-            diffuse = effective_color * self.material.diffuse * light_dot_normal;
+        let albedo = if material.normalized_diffuse {
+            material.diffuse / PI
+        } else {
+            material.diffuse
+        };
+
+        let samples = light.samples();
+        for index in 0..samples {
+            let light_vector = (light.sample(index) - point).normalize();
+
+            // wrap lighting lets the diffuse term bleed past the terminator
+            let light_dot_normal = light_vector.dot(normal);
+            let wrapped = ((light_dot_normal + material.wrap) / (1.0 + material.wrap)).max(0.0);
+            diffuse += effective_color * albedo * wrapped;
 
-            let reflect = (-light).reflect(normal);
+            if light_dot_normal < 0.0 {
+                continue;
+            }
+
+            let reflect = (-light_vector).reflect(normal);
             let reflect_dot_eye = reflect.dot(eye);
             if reflect_dot_eye > 0.0 {
-                specular = intensity
-                    * self.material.specular
-                    * reflect_dot_eye.powf(self.material.shininess);
+                specular += light.intensity()
+                    * material.specular_color
+                    * material.specular
+                    * reflect_dot_eye.powf(material.shininess);
             }
         }
 
-        ambient + diffuse + specular
+        // a single sample of a fully lit point light reduces to the plain Phong sum
+        let attenuation = light.attenuation(point);
+        ambient + (diffuse + specular) * (intensity * attenuation / samples as f64)
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Geometry {
     Sphere,
     Plane,
@@ -477,24 +1168,65 @@ pub enum Geometry {
         max: f64,
         closed: bool,
     },
+    Frustum {
+        min: f64,
+        max: f64,
+        bottom_radius: f64,
+        top_radius: f64,
+        closed: bool,
+    },
+    Disk {
+        inner: f64,
+        outer: f64,
+    },
+    Torus {
+        major: f64,
+        minor: f64,
+    },
     Triangle {
         p1: Vector,
         p2: Vector,
         p3: Vector,
         e1: Vector,
         e2: Vector,
-        n: Vector,
+        uvs: Option<[(f64, f64); 3]>,
     },
-    SmoothTriangle {
+    Quad {
         p1: Vector,
         p2: Vector,
         p3: Vector,
-        e1: Vector,
-        e2: Vector,
-        n1: Vector,
-        n2: Vector,
-        n3: Vector,
+        p4: Vector,
+        n: Vector,
     },
+    // boxed so the flat triangle is the largest variant that every shape pays for
+    SmoothTriangle(Box<SmoothTriangle>),
+}
+
+#[derive(Debug, Clone)]
+pub struct SmoothTriangle {
+    pub p1: Vector,
+    pub p2: Vector,
+    pub p3: Vector,
+    pub e1: Vector,
+    pub e2: Vector,
+    pub n1: Vector,
+    pub n2: Vector,
+    pub n3: Vector,
+    pub uvs: Option<[(f64, f64); 3]>,
+}
+
+impl Approx<SmoothTriangle> for SmoothTriangle {
+    fn approx(&self, other: &SmoothTriangle) -> bool {
+        self.p1.approx(&other.p1)
+            && self.p2.approx(&other.p2)
+            && self.p3.approx(&other.p3)
+            && self.e1.approx(&other.e1)
+            && self.e2.approx(&other.e2)
+            && self.n1.approx(&other.n1)
+            && self.n2.approx(&other.n2)
+            && self.n3.approx(&other.n3)
+            && self.uvs.approx(&other.uvs)
+    }
 }
 
 impl Approx<Geometry> for Geometry {
@@ -527,6 +1259,48 @@ impl Approx<Geometry> for Geometry {
                     closed: oclosed,
                 },
             ) => smin.approx(omin) && smax.approx(omax) && sclosed.approx(oclosed),
+            (
+                Geometry::Frustum {
+                    min: smin,
+                    max: smax,
+                    bottom_radius: sbottom,
+                    top_radius: stop,
+                    closed: sclosed,
+                },
+                Geometry::Frustum {
+                    min: omin,
+                    max: omax,
+                    bottom_radius: obottom,
+                    top_radius: otop,
+                    closed: oclosed,
+                },
+            ) => {
+                smin.approx(omin)
+                    && smax.approx(omax)
+                    && sbottom.approx(obottom)
+                    && stop.approx(otop)
+                    && sclosed.approx(oclosed)
+            }
+            (
+                Geometry::Disk {
+                    inner: sinner,
+                    outer: souter,
+                },
+                Geometry::Disk {
+                    inner: oinner,
+                    outer: oouter,
+                },
+            ) => sinner.approx(oinner) && souter.approx(oouter),
+            (
+                Geometry::Torus {
+                    major: smajor,
+                    minor: sminor,
+                },
+                Geometry::Torus {
+                    major: omajor,
+                    minor: ominor,
+                },
+            ) => smajor.approx(omajor) && sminor.approx(ominor),
             (
                 Geometry::Triangle {
                     p1: sp1,
@@ -534,7 +1308,7 @@ impl Approx<Geometry> for Geometry {
                     p3: sp3,
                     e1: se1,
                     e2: se2,
-                    n: sn,
+                    uvs: suvs,
                 },
                 Geometry::Triangle {
                     p1: op1,
@@ -542,7 +1316,7 @@ impl Approx<Geometry> for Geometry {
                     p3: op3,
                     e1: oe1,
                     e2: oe2,
-                    n: on,
+                    uvs: ouvs,
                 },
             ) => {
                 sp1.approx(op1)
@@ -550,39 +1324,31 @@ impl Approx<Geometry> for Geometry {
                     && sp3.approx(op3)
                     && se1.approx(oe1)
                     && se2.approx(oe2)
-                    && sn.approx(on)
+                    && suvs.approx(ouvs)
             }
             (
-                Geometry::SmoothTriangle {
+                Geometry::Quad {
                     p1: sp1,
                     p2: sp2,
                     p3: sp3,
-                    e1: se1,
-                    e2: se2,
-                    n1: sn1,
-                    n2: sn2,
-                    n3: sn3,
+                    p4: sp4,
+                    n: sn,
                 },
-                Geometry::SmoothTriangle {
+                Geometry::Quad {
                     p1: op1,
                     p2: op2,
                     p3: op3,
-                    e1: oe1,
-                    e2: oe2,
-                    n1: on1,
-                    n2: on2,
-                    n3: on3,
+                    p4: op4,
+                    n: on,
                 },
             ) => {
                 sp1.approx(op1)
                     && sp2.approx(op2)
                     && sp3.approx(op3)
-                    && se1.approx(oe1)
-                    && se2.approx(oe2)
-                    && sn1.approx(on1)
-                    && sn2.approx(on2)
-                    && sn3.approx(on3)
+                    && sp4.approx(op4)
+                    && sn.approx(on)
             }
+            (Geometry::SmoothTriangle(s), Geometry::SmoothTriangle(o)) => s.approx(o),
             (_, _) => false,
         }
     }
@@ -610,17 +1376,20 @@ impl Geometry {
             shape,
             u: None,
             v: None,
+            frame: None,
         });
         intersections.push(Intersection {
             t: t1,
             shape,
             u: None,
             v: None,
+            frame: None,
         });
     }
 
     fn intersect_plane<'a>(shape: &'a Shape, ray: Ray, intersections: &mut Vec<Intersection<'a>>) {
-        if ray.direction.y.approx(&0.0) {
+        // a ray starting on the plane would only ever hit itself at t = 0
+        if ray.direction.y.approx(&0.0) || ray.origin.y.approx(&0.0) {
             return;
         }
 
@@ -629,9 +1398,36 @@ impl Geometry {
             shape,
             u: None,
             v: None,
+            frame: None,
         });
     }
 
+    fn intersect_disk<'a>(
+        shape: &'a Shape,
+        ray: Ray,
+        inner: f64,
+        outer: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
+        if ray.direction.y.approx(&0.0) {
+            return;
+        }
+
+        let t = -ray.origin.y / ray.direction.y;
+        let point = ray.position(t);
+        let distance = point.x.powi(2) + point.z.powi(2);
+
+        if inner.powi(2) <= distance && distance <= outer.powi(2) {
+            intersections.push(Intersection {
+                t,
+                shape,
+                u: None,
+                v: None,
+                frame: None,
+            });
+        }
+    }
+
     pub fn intersect_cube_axis(origin: f64, direction: f64, min: f64, max: f64) -> (f64, f64) {
         let t_min_numerator = min - origin;
         let t_max_numerator = max - origin;
@@ -669,12 +1465,14 @@ impl Geometry {
                 shape,
                 u: None,
                 v: None,
+                frame: None,
             });
             intersections.push(Intersection {
                 t: t_max,
                 shape,
                 u: None,
                 v: None,
+                frame: None,
             });
         }
     }
@@ -686,6 +1484,7 @@ impl Geometry {
         (x.powi(2) + z.powi(2)) <= radius.powi(2)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn intersect_cap<'a>(
         shape: &'a Shape,
         ray: Ray,
@@ -707,6 +1506,7 @@ impl Geometry {
                 shape,
                 u: None,
                 v: None,
+                frame: None,
             });
         }
 
@@ -717,6 +1517,7 @@ impl Geometry {
                 shape,
                 u: None,
                 v: None,
+                frame: None,
             });
         }
     }
@@ -748,6 +1549,7 @@ impl Geometry {
                         shape,
                         u: None,
                         v: None,
+                        frame: None,
                     });
                 }
 
@@ -759,6 +1561,7 @@ impl Geometry {
                         shape,
                         u: None,
                         v: None,
+                        frame: None,
                     })
                 }
             }
@@ -794,6 +1597,7 @@ impl Geometry {
                             shape,
                             u: None,
                             v: None,
+                            frame: None,
                         });
                     }
 
@@ -805,6 +1609,7 @@ impl Geometry {
                             shape,
                             u: None,
                             v: None,
+                            frame: None,
                         })
                     }
                 }
@@ -814,6 +1619,7 @@ impl Geometry {
                     shape,
                     u: None,
                     v: None,
+                    frame: None,
                 })
             }
         }
@@ -821,6 +1627,105 @@ impl Geometry {
         Geometry::intersect_cap(shape, ray, min, max, min, max, closed, intersections);
     }
 
+    // the side is x^2 + z^2 = (k * y + c)^2 with the radius k * y + c
+    #[allow(clippy::too_many_arguments)]
+    fn intersect_frustum<'a>(
+        shape: &'a Shape,
+        ray: Ray,
+        min: f64,
+        max: f64,
+        bottom_radius: f64,
+        top_radius: f64,
+        closed: bool,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
+        let o = ray.origin;
+        let d = ray.direction;
+
+        let k = (top_radius - bottom_radius) / (max - min);
+        let r = k * o.y + bottom_radius - k * min;
+
+        let a = d.x.powi(2) + d.z.powi(2) - (k * d.y).powi(2);
+        let b = 2.0 * (o.x * d.x + o.z * d.z) - 2.0 * k * d.y * r;
+        let c = o.x.powi(2) + o.z.powi(2) - r.powi(2);
+
+        let roots = if !a.approx(&0.0) {
+            let discriminant = b.powi(2) - 4.0 * a * c;
+            if discriminant >= 0.0 {
+                vec![
+                    (-b - discriminant.sqrt()) / (2.0 * a),
+                    (-b + discriminant.sqrt()) / (2.0 * a),
+                ]
+            } else {
+                vec![]
+            }
+        } else if !b.approx(&0.0) {
+            // parallel to the side, it only crosses it once
+            vec![-c / b]
+        } else {
+            vec![]
+        };
+
+        for t in roots {
+            let y = o.y + t * d.y;
+            if min < y && y < max {
+                intersections.push(Intersection {
+                    t,
+                    shape,
+                    u: None,
+                    v: None,
+                    frame: None,
+                });
+            }
+        }
+
+        Geometry::intersect_cap(
+            shape,
+            ray,
+            min,
+            max,
+            bottom_radius,
+            top_radius,
+            closed,
+            intersections,
+        );
+    }
+
+    fn intersect_torus<'a>(
+        shape: &'a Shape,
+        ray: Ray,
+        major: f64,
+        minor: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
+        let origin = ray.origin - Vector::point(0.0, 0.0, 0.0);
+        let direction = ray.direction;
+
+        let dd = direction.dot(direction);
+        let od = origin.dot(direction);
+        let k = origin.dot(origin) + major.powi(2) - minor.powi(2);
+        let r2 = 4.0 * major.powi(2);
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) with p = origin + t * direction
+        let coefficients = [
+            dd.powi(2),
+            4.0 * dd * od,
+            4.0 * od.powi(2) + 2.0 * dd * k - r2 * (direction.x.powi(2) + direction.z.powi(2)),
+            4.0 * od * k - 2.0 * r2 * (origin.x * direction.x + origin.z * direction.z),
+            k.powi(2) - r2 * (origin.x.powi(2) + origin.z.powi(2)),
+        ];
+
+        for t in polynomial::real_roots(&coefficients) {
+            intersections.push(Intersection {
+                t,
+                shape,
+                u: None,
+                v: None,
+                frame: None,
+            });
+        }
+    }
+
     fn intersect_triangle<'a>(
         shape: &'a Shape,
         ray: Ray,
@@ -840,14 +1745,14 @@ impl Geometry {
         let p1_to_origin = ray.origin - p1;
         let u = f * p1_to_origin.dot(dir_cross_e2);
 
-        if u < 0.0 || u > 1.0 {
+        if !(-EDGE_EPSILON..=1.0 + EDGE_EPSILON).contains(&u) {
             return;
         }
 
         let origin_cross_e1 = p1_to_origin.cross(e1);
         let v = f * ray.direction.dot(origin_cross_e1);
 
-        if v < 0.0 || u + v > 1.0 {
+        if v < -EDGE_EPSILON || u + v > 1.0 + EDGE_EPSILON {
             return;
         }
 
@@ -856,7 +1761,41 @@ impl Geometry {
             shape,
             u: Some(u),
             v: Some(v),
+            frame: None,
+        });
+    }
+
+    fn intersect_quad<'a>(
+        shape: &'a Shape,
+        ray: Ray,
+        corners: [Vector; 4],
+        n: Vector,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
+        let denominator = ray.direction.dot(n);
+
+        if denominator.abs() < EPSILON {
+            return;
+        }
+
+        let t = (corners[0] - ray.origin).dot(n) / denominator;
+        let point = ray.position(t);
+
+        // the quad is convex, so the hit has to lie on the inner side of every edge
+        let inside = (0..4).all(|i| {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            (b - a).cross(point - a).dot(n) <= EPSILON
         });
+
+        if inside {
+            intersections.push(Intersection {
+                t,
+                shape,
+                u: None,
+                v: None,
+                frame: None,
+            });
+        }
     }
 
     pub fn intersect<'a>(
@@ -875,12 +1814,42 @@ impl Geometry {
             Geometry::Cone { min, max, closed } => {
                 Geometry::intersect_cone(shape, ray, *min, *max, *closed, intersections)
             }
+            Geometry::Frustum {
+                min,
+                max,
+                bottom_radius,
+                top_radius,
+                closed,
+            } => Geometry::intersect_frustum(
+                shape,
+                ray,
+                *min,
+                *max,
+                *bottom_radius,
+                *top_radius,
+                *closed,
+                intersections,
+            ),
+            Geometry::Disk { inner, outer } => {
+                Geometry::intersect_disk(shape, ray, *inner, *outer, intersections)
+            }
+            Geometry::Torus { major, minor } => {
+                Geometry::intersect_torus(shape, ray, *major, *minor, intersections)
+            }
             Geometry::Triangle { p1, e1, e2, .. } => {
                 Geometry::intersect_triangle(shape, ray, *p1, *e1, *e2, intersections)
             }
-            Geometry::SmoothTriangle { p1, e1, e2, .. } => {
-                Geometry::intersect_triangle(shape, ray, *p1, *e1, *e2, intersections)
+            Geometry::Quad { p1, p2, p3, p4, n } => {
+                Geometry::intersect_quad(shape, ray, [*p1, *p2, *p3, *p4], *n, intersections)
             }
+            Geometry::SmoothTriangle(triangle) => Geometry::intersect_triangle(
+                shape,
+                ray,
+                triangle.p1,
+                triangle.e1,
+                triangle.e2,
+                intersections,
+            ),
         }
     }
 
@@ -928,20 +1897,79 @@ impl Geometry {
         }
     }
 
+    fn normal_frustum(
+        point: Vector,
+        min: f64,
+        max: f64,
+        bottom_radius: f64,
+        top_radius: f64,
+    ) -> Vector {
+        let distance = point.x.powi(2) + point.z.powi(2);
+        let k = (top_radius - bottom_radius) / (max - min);
+
+        if distance < top_radius.powi(2) && point.y >= max - EPSILON {
+            Vector::vector(0.0, 1.0, 0.0)
+        } else if distance < bottom_radius.powi(2) && point.y <= min + EPSILON {
+            Vector::vector(0.0, -1.0, 0.0)
+        } else {
+            let radius = bottom_radius + k * (point.y - min);
+            Vector::vector(point.x, -k * radius, point.z)
+        }
+    }
+
+    fn normal_torus(point: Vector, major: f64, minor: f64) -> Vector {
+        let p = Vector::vector(point.x, point.y, point.z);
+        let k = p.dot(p) + major.powi(2) - minor.powi(2);
+
+        p * k - Vector::vector(point.x, 0.0, point.z) * (2.0 * major.powi(2))
+    }
+
     pub fn normal(&self, point: Vector, u: Option<f64>, v: Option<f64>) -> Vector {
         match self {
             Geometry::Sphere => Vector::vector(point.x, point.y, point.z),
-            Geometry::Plane => Vector::vector(0.0, 1.0, 0.0),
+            Geometry::Plane | Geometry::Disk { .. } => Vector::vector(0.0, 1.0, 0.0),
             Geometry::Cube => Geometry::normal_cube(point),
             Geometry::Cylinder { min, max, .. } => Geometry::normal_cylinder(point, *min, *max),
             Geometry::Cone { min, max, .. } => Geometry::normal_cone(point, *min, *max),
-            Geometry::Triangle { n, .. } => *n,
-            Geometry::SmoothTriangle { n1, n2, n3, .. } => {
+            Geometry::Frustum {
+                min,
+                max,
+                bottom_radius,
+                top_radius,
+                ..
+            } => Geometry::normal_frustum(point, *min, *max, *bottom_radius, *top_radius),
+            Geometry::Torus { major, minor } => Geometry::normal_torus(point, *major, *minor),
+            // cheaper to redo the cross product per shade than to store it for huge meshes
+            Geometry::Triangle { e1, e2, .. } => e2.cross(*e1).normalize(),
+            Geometry::Quad { n, .. } => *n,
+            Geometry::SmoothTriangle(triangle) => {
                 let u = u.unwrap();
                 let v = v.unwrap();
 
-                *n2 * u + *n3 * v + *n1 * (1.0 - u - v)
+                triangle.n2 * u + triangle.n3 * v + triangle.n1 * (1.0 - u - v)
+            }
+        }
+    }
+
+    pub fn texture_uv(&self, u: Option<f64>, v: Option<f64>) -> Option<(f64, f64)> {
+        let uvs = match self {
+            Geometry::Triangle { uvs, .. } => uvs,
+            Geometry::SmoothTriangle(triangle) => &triangle.uvs,
+            _ => &None,
+        };
+
+        match uvs {
+            Some([uv1, uv2, uv3]) => {
+                let u = u?;
+                let v = v?;
+                let w = 1.0 - u - v;
+
+                Some((
+                    uv2.0 * u + uv3.0 * v + uv1.0 * w,
+                    uv2.1 * u + uv3.1 * v + uv1.1 * w,
+                ))
             }
+            _ => None,
         }
     }
 
@@ -986,40 +2014,236 @@ impl Geometry {
                     )
                 }
             }
-            Geometry::Triangle { p1, p2, p3, .. } => {
-                BoundingBox::empty().insert(*p1).insert(*p2).insert(*p3)
+            Geometry::Frustum {
+                min,
+                max,
+                bottom_radius,
+                top_radius,
+                ..
+            } => {
+                let limit = bottom_radius.max(*top_radius);
+                BoundingBox::new(
+                    Vector::point(-limit, *min, -limit),
+                    Vector::point(limit, *max, limit),
+                )
+            }
+            Geometry::Disk { outer, .. } => BoundingBox::new(
+                Vector::point(-outer, 0.0, -outer),
+                Vector::point(*outer, 0.0, *outer),
+            ),
+            Geometry::Torus { major, minor } => {
+                let extent = major + minor;
+                BoundingBox::new(
+                    Vector::point(-extent, -minor, -extent),
+                    Vector::point(extent, *minor, extent),
+                )
             }
-            Geometry::SmoothTriangle { p1, p2, p3, .. } => {
+            Geometry::Triangle { p1, p2, p3, .. } => {
                 BoundingBox::empty().insert(*p1).insert(*p2).insert(*p3)
             }
+            Geometry::Quad { p1, p2, p3, p4, .. } => BoundingBox::empty()
+                .insert(*p1)
+                .insert(*p2)
+                .insert(*p3)
+                .insert(*p4),
+            Geometry::SmoothTriangle(triangle) => BoundingBox::empty()
+                .insert(triangle.p1)
+                .insert(triangle.p2)
+                .insert(triangle.p3),
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn closest_point(&self, point: Vector) -> Option<Vector> {
+        match self {
+            Geometry::Sphere => {
+                let direction = Vector::vector(point.x, point.y, point.z);
+                if direction.magnitude().approx(&0.0) {
+                    Some(Vector::point(0.0, 1.0, 0.0))
+                } else {
+                    let direction = direction.normalize();
+                    Some(Vector::point(direction.x, direction.y, direction.z))
+                }
+            }
+            Geometry::Plane => Some(Vector::point(point.x, 0.0, point.z)),
+            Geometry::Cube => {
+                let clamp = |x: f64| x.clamp(-1.0, 1.0);
+                let mut closest = Vector::point(clamp(point.x), clamp(point.y), clamp(point.z));
+
+                if point.x.abs().max(point.y.abs()).max(point.z.abs()) < 1.0 {
+                    let (x, y, z) = (point.x.abs(), point.y.abs(), point.z.abs());
+                    if x >= y && x >= z {
+                        closest.x = point.x.signum();
+                    } else if y >= z {
+                        closest.y = point.y.signum();
+                    } else {
+                        closest.z = point.z.signum();
+                    }
+                }
 
-    use crate::approx::Approx;
-    use crate::material::Pattern;
+                Some(closest)
+            }
+            _ => None,
+        }
+    }
 
-    use test_case::test_case;
+    // a ray through an open surface may cross it only once, without entering or leaving a volume
+    pub fn is_closed(&self) -> bool {
+        match *self {
+            Geometry::Sphere | Geometry::Cube | Geometry::Torus { .. } => true,
+            Geometry::Cylinder { min, max, closed } | Geometry::Cone { min, max, closed } => {
+                closed || (min == f64::NEG_INFINITY && max == f64::INFINITY)
+            }
+            Geometry::Frustum { closed, .. } => closed,
+            _ => false,
+        }
+    }
 
-    // Sphere Tests
+    pub fn contains_point(&self, point: Vector) -> bool {
+        let radius2 = point.x.powi(2) + point.z.powi(2);
 
-    #[test_case(Vector::point(0.0, 0.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  ,  4.0,  6.0 ; "before" )]
-    #[test_case(Vector::point(0.0, 0.0,  0.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  , -1.0,  1.0 ; "inside" )]
-    #[test_case(Vector::point(0.0, 0.0,  5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  , -6.0, -4.0 ; "behind" )]
-    #[test_case(Vector::point(0.0, 0.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::scaling(2.0, 2.0, 2.0),  3.0,  7.0 ; "scaled" )]
-    #[test_case(Vector::point(0.0, 1.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  ,  5.0,  5.0 ; "tangent")]
-    fn ray_sphere_hit(origin: Vector, direction: Vector, transform: Matrix, t1: f64, t2: f64) {
-        let ray = Ray { origin, direction };
-        let sphere = Shape::sphere(ShapeArgs {
-            transform,
-            ..ShapeArgs::default()
-        });
-        let mut is = vec![];
-        sphere.intersect(ray, &mut is);
+        match *self {
+            Geometry::Sphere => Vector::vector(point.x, point.y, point.z).magnitude() <= 1.0,
+            Geometry::Cube => point.x.abs().max(point.y.abs()).max(point.z.abs()) <= 1.0,
+            Geometry::Cylinder { min, max, .. } => {
+                radius2 <= 1.0 && min <= point.y && point.y <= max
+            }
+            Geometry::Cone { min, max, .. } => {
+                radius2 <= point.y.powi(2) && min <= point.y && point.y <= max
+            }
+            Geometry::Frustum {
+                min,
+                max,
+                bottom_radius,
+                top_radius,
+                ..
+            } => {
+                let radius =
+                    bottom_radius + (top_radius - bottom_radius) * (point.y - min) / (max - min);
+                radius2 <= radius.powi(2) && min <= point.y && point.y <= max
+            }
+            Geometry::Torus { major, minor } => {
+                let p = Vector::vector(point.x, point.y, point.z);
+                (p.dot(p) + major.powi(2) - minor.powi(2)).powi(2) <= 4.0 * major.powi(2) * radius2
+            }
+            _ => false,
+        }
+    }
+
+    pub fn surface_area(&self) -> Option<f64> {
+        match *self {
+            Geometry::Sphere => Some(4.0 * PI),
+            Geometry::Plane => None,
+            Geometry::Cube => Some(24.0),
+            Geometry::Cylinder { min, max, closed } => {
+                if !min.is_finite() || !max.is_finite() {
+                    return None;
+                }
+
+                let caps = if closed { 2.0 * PI } else { 0.0 };
+                Some(2.0 * PI * (max - min) + caps)
+            }
+            Geometry::Cone { min, max, closed } => {
+                if !min.is_finite() || !max.is_finite() {
+                    return None;
+                }
+
+                let lateral = |y: f64| 2.0_f64.sqrt() * PI * y * y.abs();
+                let caps = if closed {
+                    PI * (min.powi(2) + max.powi(2))
+                } else {
+                    0.0
+                };
+                Some(lateral(max) - lateral(min) + caps)
+            }
+            Geometry::Frustum {
+                min,
+                max,
+                bottom_radius,
+                top_radius,
+                closed,
+            } => {
+                let slant = ((top_radius - bottom_radius).powi(2) + (max - min).powi(2)).sqrt();
+                let caps = if closed {
+                    PI * (bottom_radius.powi(2) + top_radius.powi(2))
+                } else {
+                    0.0
+                };
+                Some(PI * (bottom_radius + top_radius) * slant + caps)
+            }
+            Geometry::Disk { inner, outer } => Some(PI * (outer.powi(2) - inner.powi(2))),
+            Geometry::Torus { major, minor } if minor < major => {
+                Some(4.0 * PI.powi(2) * major * minor)
+            }
+            Geometry::Torus { .. } => None,
+            Geometry::Triangle { e1, e2, .. } => Some(e1.cross(e2).magnitude() / 2.0),
+            Geometry::SmoothTriangle(ref triangle) => {
+                Some(triangle.e1.cross(triangle.e2).magnitude() / 2.0)
+            }
+            Geometry::Quad { p1, p2, p3, p4, .. } => {
+                Some((p3 - p1).cross(p4 - p2).magnitude() / 2.0)
+            }
+        }
+    }
+
+    pub fn volume(&self) -> Option<f64> {
+        match *self {
+            Geometry::Sphere => Some(4.0 / 3.0 * PI),
+            Geometry::Cube => Some(8.0),
+            Geometry::Cylinder { min, max, closed }
+                if closed && min.is_finite() && max.is_finite() =>
+            {
+                Some(PI * (max - min))
+            }
+            Geometry::Cone { min, max, closed } if closed && min.is_finite() && max.is_finite() => {
+                Some(PI * (max.powi(3) - min.powi(3)) / 3.0)
+            }
+            Geometry::Frustum {
+                min,
+                max,
+                bottom_radius: r1,
+                top_radius: r2,
+                closed: true,
+            } => Some(PI * (max - min) * (r1.powi(2) + r1 * r2 + r2.powi(2)) / 3.0),
+            Geometry::Torus { major, minor } if minor < major => {
+                Some(2.0 * PI.powi(2) * major * minor.powi(2))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_unit, clippy::approx_constant)]
+mod tests {
+    use super::*;
+
+    use crate::approx::Approx;
+    use crate::light::{AreaLight, PointLight, SpotLight};
+    use crate::material::Pattern;
+
+    use std::collections::HashSet;
+    use test_case::test_case;
+
+    // Sphere Tests
+
+    #[test_case(Vector::point(0.0, 0.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  ,  4.0,  6.0 ; "before" )]
+    #[test_case(Vector::point(0.0, 0.0,  0.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  , -1.0,  1.0 ; "inside" )]
+    #[test_case(Vector::point(0.0, 0.0,  5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  , -6.0, -4.0 ; "behind" )]
+    #[test_case(Vector::point(0.0, 0.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::scaling(2.0, 2.0, 2.0),  3.0,  7.0 ; "scaled" )]
+    #[test_case(Vector::point(0.0, 1.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                  ,  5.0,  5.0 ; "tangent")]
+    fn ray_sphere_hit(origin: Vector, direction: Vector, transform: Matrix, t1: f64, t2: f64) {
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
+        let sphere = Shape::sphere(ShapeArgs {
+            transform,
+            ..ShapeArgs::default()
+        });
+        let mut is = vec![];
+        sphere.intersect(ray, &mut is);
 
         assert!(
             is.len() == 2
@@ -1033,7 +2257,12 @@ mod tests {
     #[test_case(Vector::point(0.0, 2.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::id()                       ; "no transform")]
     #[test_case(Vector::point(0.0, 0.0, -5.0), Vector::vector(0.0, 0.0, 1.0), Matrix::translation(5.0, 0.0, 0.0) ; "translated"  )]
     fn ray_sphere_miss(origin: Vector, direction: Vector, transform: Matrix) {
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
         let sphere = Element::sphere(ShapeArgs {
             transform,
             ..ShapeArgs::default()
@@ -1041,7 +2270,7 @@ mod tests {
         let mut is = vec![];
         sphere.intersect(ray, &mut is);
 
-        assert!(is.len() == 0)
+        assert!(is.is_empty())
     }
 
     #[test_case(Vector::point(1.0, 0.0, 0.0), Vector::vector(1.0, 0.0, 0.0), Matrix::id(); "x axis"    )]
@@ -1065,6 +2294,18 @@ mod tests {
         Matrix::scaling(1.0, 0.5, 1.0) * Matrix::rotation_z(std::f64::consts::PI / 5.0);
         "scaling and rotation"
     )]
+    #[test_case(
+        Vector::point(0.6, 0.8, 0.0),
+        Vector::vector(0.6, 0.8, 0.0),
+        Matrix::scaling(-1.0, 1.0, 1.0) ;
+        "mirrored"
+    )]
+    #[test_case(
+        Vector::point(3.0, 0.0, 0.0),
+        Vector::vector(1.0, 0.0, 0.0),
+        Matrix::translation(2.0, 0.0, 0.0) * Matrix::scaling(-1.0, 1.0, 1.0) ;
+        "translated mirrored"
+    )]
     fn sphere_normal(point: Vector, expected: Vector, transform: Matrix) {
         let sphere = Shape::sphere(ShapeArgs {
             transform,
@@ -1091,6 +2332,25 @@ mod tests {
         assert!(normal.approx(&normal.normalize()))
     }
 
+    #[test_case(0.0, 0 ; "at rest")]
+    #[test_case(1.0, 2 ; "moved")]
+    fn moving_sphere(time: f64, hits: usize) {
+        let sphere = Shape::sphere(ShapeArgs {
+            motion: Vector::vector(2.0, 0.0, 0.0),
+            ..ShapeArgs::default()
+        });
+        let ray = Ray {
+            origin: Vector::point(2.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time,
+        };
+        let mut is = vec![];
+        sphere.intersect(ray, &mut is);
+
+        assert!(is.len() == hits && sphere.bbox.max.x.approx(&3.0))
+    }
+
     #[test]
     fn sphere_bbox() {
         let sphere = Element::sphere(ShapeArgs {
@@ -1105,13 +2365,52 @@ mod tests {
         )
     }
 
+    #[test_case(Shape::sphere(ShapeArgs::default()), Vector::point(3.0, 0.0, 0.0), 2.0 ; "sphere outside")]
+    #[test_case(Shape::sphere(ShapeArgs::default()), Vector::point(0.0, 0.5, 0.0), 0.5 ; "sphere inside")]
+    #[test_case(
+        Shape::sphere(ShapeArgs {
+            transform: Matrix::translation(0.0, 0.0, 5.0) * Matrix::scaling(2.0, 2.0, 2.0),
+            ..ShapeArgs::default()
+        }),
+        Vector::point(0.0, 0.0, 0.0),
+        3.0 ;
+        "sphere transformed"
+    )]
+    #[test_case(Shape::plane(ShapeArgs::default()), Vector::point(4.0, -2.5, 1.0), 2.5 ; "plane")]
+    #[test_case(Shape::cube(ShapeArgs::default()), Vector::point(4.0, 5.0, 0.0), 5.0 ; "cube corner")]
+    #[test_case(Shape::cube(ShapeArgs::default()), Vector::point(0.2, 0.7, 0.0), 0.3 ; "cube inside")]
+    fn approx_distance(shape: Shape, point: Vector, expected: f64) {
+        assert!(shape.approx_distance(point).unwrap().approx(&expected))
+    }
+
+    #[test_case(Geometry::Sphere, Some(4.0 * PI), Some(4.0 / 3.0 * PI) ; "sphere")]
+    #[test_case(Geometry::Plane, None, None ; "plane")]
+    #[test_case(Geometry::Cube, Some(24.0), Some(8.0) ; "cube")]
+    #[test_case(Geometry::Cylinder { min: 0.0, max: 2.0, closed: true }, Some(6.0 * PI), Some(2.0 * PI) ; "closed cylinder")]
+    #[test_case(Geometry::Cylinder { min: 0.0, max: 2.0, closed: false }, Some(4.0 * PI), None ; "open cylinder")]
+    #[test_case(Geometry::Cylinder { min: f64::NEG_INFINITY, max: f64::INFINITY, closed: false }, None, None ; "infinite cylinder")]
+    #[test_case(Geometry::Cone { min: -1.0, max: 0.0, closed: true }, Some((1.0 + 2.0_f64.sqrt()) * PI), Some(PI / 3.0) ; "closed cone")]
+    #[test_case(Geometry::Cone { min: -1.0, max: 1.0, closed: false }, Some(2.0 * 2.0_f64.sqrt() * PI), None ; "open double cone")]
+    #[test_case(Geometry::Frustum { min: 0.0, max: 1.0, bottom_radius: 1.0, top_radius: 0.5, closed: true }, Some((1.5 * 1.25_f64.sqrt() + 1.25) * PI), Some(1.75 / 3.0 * PI) ; "closed frustum")]
+    #[test_case(Geometry::Frustum { min: 0.0, max: 2.0, bottom_radius: 1.0, top_radius: 1.0, closed: true }, Some(6.0 * PI), Some(2.0 * PI) ; "frustum as cylinder")]
+    #[test_case(Geometry::Frustum { min: 0.0, max: 1.0, bottom_radius: 1.0, top_radius: 0.5, closed: false }, Some(1.5 * 1.25_f64.sqrt() * PI), None ; "open frustum")]
+    #[test_case(Geometry::Torus { major: 2.0, minor: 0.5 }, Some(4.0 * PI.powi(2)), Some(PI.powi(2)) ; "torus")]
+    fn surface_area_volume(geometry: Geometry, area: Option<f64>, volume: Option<f64>) {
+        assert!(geometry.surface_area().approx(&area) && geometry.volume().approx(&volume))
+    }
+
     // Plane Tests
 
     #[test_case(Vector::point(0.0,  1.0, 0.0), Vector::vector(0.0, -1.0, 0.0), 1.0 ; "above")]
     #[test_case(Vector::point(0.0, -1.0, 0.0), Vector::vector(0.0,  1.0, 0.0), 1.0 ; "below")]
     fn ray_plane_hit(origin: Vector, direction: Vector, t: f64) {
         let plane = Shape::plane(ShapeArgs::default());
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
         let mut is = vec![];
         plane.intersect(ray, &mut is);
 
@@ -1120,13 +2419,19 @@ mod tests {
 
     #[test_case(Vector::point(0.0, 10.0, 0.0), Vector::vector(0.0, 0.0, 1.0) ; "parallel")]
     #[test_case(Vector::point(0.0,  0.0, 0.0), Vector::vector(0.0, 0.0, 1.0) ; "coplanar")]
+    #[test_case(Vector::point(0.0,  0.0, 0.0), Vector::vector(1.0, 0.1, 0.0) ; "starting on plane")]
     fn ray_plane_miss(origin: Vector, direction: Vector) {
         let plane = Shape::plane(ShapeArgs::default());
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
         let mut is = vec![];
         plane.intersect(ray, &mut is);
 
-        assert!(is.len() == 0)
+        assert!(is.is_empty())
     }
 
     #[test_case(Vector::point(  0.0, 0.0,    0.0), Vector::vector(0.0, 1.0, 0.0) ; "example 1")]
@@ -1139,6 +2444,42 @@ mod tests {
         assert!(normal.approx(&expected))
     }
 
+    // Disk Tests
+
+    #[test_case(Vector::point(0.0, 1.0, 0.0), 0.0, 1.0, Some(1.0) ; "full disk center")]
+    #[test_case(Vector::point(0.9, 1.0, 0.0), 0.0, 1.0, Some(1.0) ; "full disk edge")]
+    #[test_case(Vector::point(1.1, 1.0, 0.0), 0.0, 1.0, None      ; "full disk outside")]
+    #[test_case(Vector::point(0.7, 1.0, 0.3), 0.5, 1.0, Some(1.0) ; "annulus ring")]
+    #[test_case(Vector::point(0.0, 1.0, 0.0), 0.5, 1.0, None      ; "annulus hole center")]
+    #[test_case(Vector::point(0.3, 1.0, 0.3), 0.5, 1.0, None      ; "annulus hole offset")]
+    fn ray_disk(origin: Vector, inner: f64, outer: f64, expected: Option<f64>) {
+        let disk = Shape::disk(ShapeArgs::default(), inner, outer);
+        let ray = Ray {
+            origin,
+            direction: Vector::vector(0.0, -1.0, 0.0),
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        disk.intersect(ray, &mut is);
+
+        assert!(is.first().map(|i| i.t).approx(&expected) && is.len() <= 1)
+    }
+
+    #[test]
+    fn disk_normal_bbox() {
+        let disk = Shape::disk(ShapeArgs::default(), 0.5, 2.0);
+
+        assert!(
+            disk.normal(Vector::point(1.0, 0.0, 0.0), None, None)
+                .approx(&Vector::vector(0.0, 1.0, 0.0))
+                && disk.bbox.approx(&BoundingBox::new(
+                    Vector::point(-2.0, 0.0, -2.0),
+                    Vector::point(2.0, 0.0, 2.0)
+                ))
+        )
+    }
+
     // Cube Tests
 
     #[test_case(Vector::point( 5.0,  0.5,  0.0), Vector::vector(-1.0,  0.0,  0.0),  4.0, 6.0 ; "positive x")]
@@ -1150,7 +2491,12 @@ mod tests {
     #[test_case(Vector::point( 0.0,  0.5,  0.0), Vector::vector( 0.0,  0.0,  1.0), -1.0, 1.0 ; "inside")]
     fn ray_cube_hit(origin: Vector, direction: Vector, t1: f64, t2: f64) {
         let cube = Shape::cube(ShapeArgs::default());
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
         let mut is = vec![];
         cube.intersect(ray, &mut is);
 
@@ -1171,11 +2517,16 @@ mod tests {
     #[test_case(Vector::point( 2.0,  2.0,  0.0), Vector::vector(-1.0   ,  0.0   ,  0.0   ) ; "6")]
     fn ray_cube_miss(origin: Vector, direction: Vector) {
         let cube = Shape::cube(ShapeArgs::default());
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
         let mut is = vec![];
         cube.intersect(ray, &mut is);
 
-        assert!(is.len() == 0)
+        assert!(is.is_empty())
     }
 
     #[test_case(Vector::point( 1.0,  0.5, -0.8), Vector::vector( 1.0,  0.0,  0.0); "example 1")]
@@ -1208,6 +2559,8 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         cylinder.intersect(ray, &mut is);
@@ -1234,11 +2587,13 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         cylinder.intersect(ray, &mut is);
 
-        assert!(is.len() == 0)
+        assert!(is.is_empty())
     }
 
     #[test_case(Vector::point(0.0, 1.5,  0.0), Vector::vector(0.1, 1.0, 0.0), 0 ; "example 1")]
@@ -1252,6 +2607,8 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         cylinder.intersect(ray, &mut is);
@@ -1269,6 +2626,8 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         cylinder.intersect(ray, &mut is);
@@ -1320,6 +2679,8 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         cone.intersect(ray, &mut is);
@@ -1344,6 +2705,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -1.0),
             direction: Vector::vector(0.0, 1.0, 1.0).normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         cone.intersect(ray, &mut is);
@@ -1359,6 +2722,8 @@ mod tests {
         let ray = Ray {
             origin,
             direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         cone.intersect(ray, &mut is);
@@ -1376,6 +2741,117 @@ mod tests {
         assert!(normal.approx(&expected))
     }
 
+    // Frustum Tests
+
+    #[test_case(Vector::point(0.0 , 0.5, -5.0), Vector::vector(0.0,  0.0,  1.0), vec![4.25, 5.75] ; "through the side"    )]
+    #[test_case(Vector::point(0.0 , 2.0,  0.0), Vector::vector(0.0, -1.0,  0.0), vec![1.0 , 2.0 ] ; "down the axis"       )]
+    #[test_case(Vector::point(0.75, 2.0,  0.0), Vector::vector(0.0, -1.0,  0.0), vec![1.5 , 2.0 ] ; "side and bottom cap" )]
+    #[test_case(Vector::point(0.0 , 0.0, -0.5), Vector::vector(0.0,  2.0, -1.0), vec![0.0 , 0.25] ; "parallel to the side")]
+    fn ray_frustum_hit(origin: Vector, direction: Vector, expected: Vec<f64>) {
+        let frustum = Shape::frustum(ShapeArgs::default(), 0.0, 1.0, 1.0, 0.5, true);
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        frustum.intersect(ray, &mut is);
+        Intersection::sort(&mut is);
+
+        let ts: Vec<f64> = is.iter().map(|i| i.t).collect();
+
+        assert!(ts.approx(&expected))
+    }
+
+    #[test_case(Vector::point(0.0, 0.5, -5.0 ), Vector::vector(0.0,  1.0, 0.0), true , 0 ; "outside parallel to axis")]
+    #[test_case(Vector::point(0.0, 1.5, -5.0 ), Vector::vector(0.0,  0.0, 1.0), true , 0 ; "above"                   )]
+    #[test_case(Vector::point(0.0, 0.5, -0.25), Vector::vector(0.0,  1.0, 1.0), true , 2 ; "out through the side"    )]
+    #[test_case(Vector::point(0.0, 2.0, -0.25), Vector::vector(0.0, -1.0, 0.0), true , 2 ; "both caps"               )]
+    #[test_case(Vector::point(0.0, 2.0, -0.25), Vector::vector(0.0, -1.0, 0.0), false, 0 ; "open ends"               )]
+    fn ray_frustum_constrained(origin: Vector, direction: Vector, closed: bool, count: usize) {
+        let frustum = Shape::frustum(ShapeArgs::default(), 0.0, 1.0, 1.0, 0.5, closed);
+        let ray = Ray {
+            origin,
+            direction: direction.normalize(),
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        frustum.intersect(ray, &mut is);
+
+        assert!(is.len() == count)
+    }
+
+    #[test_case(Vector::point(0.75, 0.5,  0.0 ), Vector::vector(0.75,  0.375,  0.0 ) ; "side"          )]
+    #[test_case(Vector::point(0.0 , 0.5, -0.75), Vector::vector(0.0 ,  0.375, -0.75) ; "side facing -z")]
+    #[test_case(Vector::point(0.2 , 1.0,  0.0 ), Vector::vector(0.0 ,  1.0  ,  0.0 ) ; "top cap"       )]
+    #[test_case(Vector::point(0.2 , 0.0,  0.3 ), Vector::vector(0.0 , -1.0  ,  0.0 ) ; "bottom cap"    )]
+    fn frustum_normal(point: Vector, expected: Vector) {
+        let frustum = Shape::frustum(ShapeArgs::default(), 0.0, 1.0, 1.0, 0.5, true);
+        let normal = frustum.geometry.normal(point, None, None);
+
+        assert!(normal.approx(&expected))
+    }
+
+    // Torus Tests
+
+    #[test_case(Vector::point(0.0 , 0.0, -5.0), Vector::vector(0.0, 0.0, 1.0), vec![3.75, 4.25, 5.75, 6.25] ; "through the hole")]
+    #[test_case(Vector::point(1.0 , 5.0,  0.0), Vector::vector(0.0, -1.0, 0.0), vec![4.75, 5.25]           ; "through the tube")]
+    #[test_case(Vector::point(0.0 , 5.0,  0.0), Vector::vector(0.0, -1.0, 0.0), vec![]                     ; "down the axis")]
+    #[test_case(Vector::point(-5.0, 0.5,  0.0), Vector::vector(1.0, 0.0, 0.0), vec![]                      ; "above")]
+    fn ray_torus(origin: Vector, direction: Vector, expected: Vec<f64>) {
+        let torus = Shape::torus(ShapeArgs::default(), 1.0, 0.25);
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        torus.intersect(ray, &mut is);
+        Intersection::sort(&mut is);
+
+        let ts: Vec<f64> = is.iter().map(|i| i.t).collect();
+
+        assert!(ts.approx(&expected))
+    }
+
+    #[test]
+    fn ray_torus_self_intersecting() {
+        let torus = Shape::torus(ShapeArgs::default(), 0.5, 1.0);
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        torus.intersect(ray, &mut is);
+        Intersection::sort(&mut is);
+
+        assert!(!is.is_empty() && is[0].t.approx(&3.5) && is[is.len() - 1].t.approx(&6.5))
+    }
+
+    #[test_case(Vector::point(1.25, 0.0 ,  0.0), Vector::vector(1.0, 0.0, 0.0)  ; "outer equator")]
+    #[test_case(Vector::point(0.75, 0.0 ,  0.0), Vector::vector(-1.0, 0.0, 0.0) ; "inner equator")]
+    #[test_case(Vector::point(0.0 , 0.25, -1.0), Vector::vector(0.0, 1.0, 0.0)  ; "top")]
+    fn torus_normal(point: Vector, expected: Vector) {
+        let torus = Shape::torus(ShapeArgs::default(), 1.0, 0.25);
+
+        assert!(torus.normal(point, None, None).approx(&expected))
+    }
+
+    #[test]
+    fn torus_bbox() {
+        let torus = Shape::torus(ShapeArgs::default(), 2.0, 0.5);
+
+        assert!(torus.bbox.approx(&BoundingBox::new(
+            Vector::point(-2.5, -0.5, -2.5),
+            Vector::point(2.5, 0.5, 2.5)
+        )))
+    }
+
     // Triangle Tests
 
     #[test]
@@ -1389,6 +2865,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.5, -2.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         triangle.intersect(ray, &mut is);
@@ -1407,11 +2885,66 @@ mod tests {
             Vector::point(-1.0, 0.0, 0.0),
             Vector::point(1.0, 0.0, 0.0),
         );
-        let ray = Ray { origin, direction };
+        let ray = Ray {
+            origin,
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
         let mut is = vec![];
         triangle.intersect(ray, &mut is);
 
-        assert!(is.len() == 0)
+        assert!(is.is_empty())
+    }
+
+    #[test]
+    fn ray_shared_triangle_edge() {
+        // two tilted triangles sharing the edge from `a` to `b`, every ray aims exactly at that edge
+        let a = Vector::point(0.1, 0.7, 0.3);
+        let b = Vector::point(0.9, -0.3, 0.7);
+        let left = Shape::triangle(ShapeArgs::default(), a, b, Vector::point(-0.7, -0.9, 0.1));
+        let right = Shape::triangle(ShapeArgs::default(), b, a, Vector::point(1.3, 0.9, 0.6));
+
+        let cracks = (1..1000)
+            .filter(|i| {
+                let target = a + (b - a) * (*i as f64 * 0.001);
+                let ray = Ray {
+                    origin: target + Vector::vector(0.0, 0.0, -2.0),
+                    direction: Vector::vector(0.0, 0.0, 1.0),
+                    differentials: None,
+                    time: 0.0,
+                };
+                let mut is = vec![];
+                left.intersect(ray, &mut is);
+                right.intersect(ray, &mut is);
+
+                is.is_empty()
+            })
+            .count();
+
+        assert!(cracks == 0)
+    }
+
+    #[test_case(Vector::point(0.0, 1.0, 0.0), Vector::point(-1.0, 0.0, 0.0), Vector::point(1.0, 0.0, 0.0), Vector::vector(0.0, 0.0, -1.0) ; "upright")]
+    #[test_case(Vector::point(0.0, 0.0, 0.0), Vector::point( 1.0, 0.0, 0.0), Vector::point(0.0, 0.0, 1.0), Vector::vector(0.0, 1.0,  0.0) ; "floor"  )]
+    #[test_case(Vector::point(0.0, 0.0, 0.0), Vector::point( 3.0, 0.0, 0.0), Vector::point(0.0, 4.0, 3.0), Vector::vector(0.0, 0.6, -0.8) ; "tilted" )]
+    fn triangle_normal(p1: Vector, p2: Vector, p3: Vector, stored: Vector) {
+        let triangle = Shape::triangle(ShapeArgs::default(), p1, p2, p3);
+
+        assert!(
+            triangle.normal(p1, None, None).approx(&stored)
+                && triangle.normal(p2, None, None).approx(&stored)
+                && triangle.normal(p3, None, None).approx(&stored)
+        )
+    }
+
+    #[test]
+    fn triangle_geometry_size() {
+        // a flat triangle without a stored normal is the largest unboxed variant
+        assert_eq!(
+            std::mem::size_of::<Geometry>(),
+            std::mem::size_of::<([Vector; 5], Option<[(f64, f64); 3]>)>()
+        )
     }
 
     // Smooth Triangle Tests
@@ -1433,6 +2966,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(-0.2, 0.3, -2.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
         let triangle = smooth_triangle();
         let mut is = vec![];
@@ -1457,22 +2992,42 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         group.intersect(ray, &mut is);
 
-        assert!(is.len() == 0)
+        assert!(is.is_empty())
     }
 
     fn shape(element: &Element) -> &Shape {
         match element {
-            Element::Composite(_) => {
+            Element::Primitive(shape) => shape,
+            _ => {
                 panic!("Expected primitive shape, found group.")
             }
-            Element::Primitive(shape) => shape,
         }
     }
 
+    #[test]
+    fn singular_transform() {
+        let args = ShapeArgs {
+            transform: Matrix::scaling(0.0, 1.0, 1.0),
+            ..ShapeArgs::default()
+        };
+        assert_eq!(args.validate(), Err(SingularTransform));
+        assert_eq!(ShapeArgs::default().validate(), Ok(()));
+
+        let group = Element::try_composite(
+            Matrix::scaling(1.0, 0.0, 1.0),
+            None,
+            GroupKind::Aggregation,
+            vec![Element::sphere(ShapeArgs::default())],
+        );
+        assert!(matches!(group, Err(SingularTransform)));
+    }
+
     #[test]
     fn ray_group_hit() {
         let sphere1 = Shape::sphere(ShapeArgs::default());
@@ -1499,6 +3054,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let mut is = vec![];
@@ -1531,6 +3088,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(10.0, 0.0, -10.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         group.intersect(ray, &mut is);
@@ -1598,6 +3157,73 @@ mod tests {
         )
     }
 
+    #[test]
+    fn heightfield_uvs() {
+        let heights = vec![vec![0.0, 0.5, 0.2], vec![0.1, 0.3, 0.0]];
+        let terrain = Element::heightfield(Matrix::id(), None, &heights);
+
+        let children = match &terrain {
+            Element::Composite(group) => &group.children,
+            _ => panic!("Expected group, found primitive."),
+        };
+        let corner = |child: &Element, i: usize| match shape(child).geometry {
+            Geometry::Triangle { p1, p3, uvs, .. } => {
+                (if i == 0 { p1 } else { p3 }, uvs.unwrap()[i])
+            }
+            _ => panic!("Expected triangle."),
+        };
+
+        let (first, first_uv) = corner(&children[0], 0);
+        let (last, last_uv) = corner(&children[children.len() - 2], 2);
+
+        assert!(
+            children.len() == 4
+                && first.approx(&Vector::point(0.0, 0.0, 0.0))
+                && first_uv.approx(&(0.0, 0.0))
+                && last.approx(&Vector::point(1.0, 0.0, 1.0))
+                && last_uv.approx(&(1.0, 1.0))
+        )
+    }
+
+    #[test]
+    fn tighten_bounds() {
+        let nested = || {
+            let inner = Element::composite(
+                Matrix::rotation_y(PI / 4.0),
+                None,
+                GroupKind::Aggregation,
+                vec![Element::sphere(ShapeArgs::default())],
+            );
+
+            Element::composite(
+                Matrix::rotation_y(PI / 4.0),
+                None,
+                GroupKind::Aggregation,
+                vec![inner],
+            )
+        };
+
+        let loose = nested();
+        let mut tight = nested();
+        tight.tighten_bounds();
+        let mut lazy = Element::lazy(Matrix::translation(5.0, 0.0, 0.0), vec![nested()]);
+        lazy.tighten_bounds();
+
+        let unit = BoundingBox::new(
+            Vector::point(-1.0, -1.0, -1.0),
+            Vector::point(1.0, 1.0, 1.0),
+        );
+
+        assert!(
+            tight.bbox().approx(&unit)
+                && loose.bbox().encloses(&tight.bbox())
+                && loose.bbox().max.x.approx(&2.0)
+                && lazy
+                    .bbox()
+                    .approx(&unit.transform(Matrix::translation(5.0, 0.0, 0.0)))
+        )
+    }
+
     #[test_case(GroupKind::Union, true , true , true , false ; "union 1")]
     #[test_case(GroupKind::Union, true , true , false, true  ; "union 2")]
     #[test_case(GroupKind::Union, true , false, true , false ; "union 3")]
@@ -1655,24 +3281,28 @@ mod tests {
                 shape: &sphere,
                 u: None,
                 v: None,
+                frame: None,
             },
             Intersection {
                 t: 2.0,
                 shape: &cube,
                 u: None,
                 v: None,
+                frame: None,
             },
             Intersection {
                 t: 3.0,
                 shape: &sphere,
                 u: None,
                 v: None,
+                frame: None,
             },
             Intersection {
                 t: 4.0,
                 shape: &cube,
                 u: None,
                 v: None,
+                frame: None,
             },
         ];
 
@@ -1690,11 +3320,13 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 2.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         group.intersect(ray, &mut is);
 
-        assert!(is.len() == 0)
+        assert!(is.is_empty())
     }
 
     #[test]
@@ -1716,6 +3348,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
         let mut is = vec![];
         group.intersect(ray, &mut is);
@@ -1729,73 +3363,739 @@ mod tests {
         )
     }
 
-    // Lighting Tests
-
-    #[test_case(
-        Vector::vector(0.0, 0.0, -1.0),
-        Vector::point(0.0, 0.0, -10.0),
-        false,
-        Color::new(1.9, 1.9, 1.9) ;
-        "light eye surface"
-    )]
-    #[test_case(
-        Vector::vector(0.0, 2.0f64.sqrt() / 2.0,2.0f64.sqrt() / -2.0),
-        Vector::point(0.0, 0.0, -10.0),
-        false,
-        Color::white() ;
-        "light eye surface (eye offset)"
-    )]
-    #[test_case(
-        Vector::vector(0.0, 0.0, -1.0),
-        Vector::point(0.0, 10.0, -10.0),
-        false,
-        Color::new(0.7364, 0.7364, 0.7364) ;
-        "eye light surface (light offset)"
-    )]
-    #[test_case(
-        Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / -2.0),
-        Vector::point(0.0, 10.0, -10.0),
-        false,
-        Color::new(1.6364, 1.6364, 1.6364) ;
-        "eye light surface (eye and light offset)"
-    )]
-    #[test_case(
-        Vector::vector(0.0, 0.0, -1.0),
-        Vector::point(0.0, 0.0, 10.0),
-        false,
-        Color::new(0.1, 0.1, 0.1) ;
-        "eye surface light"
-    )]
-    #[test_case(
-        Vector::vector(0.0, 0.0, -1.0),
-        Vector::point(0.0, 0.0, -10.0),
-        true,
-        Color::new(0.1, 0.1, 0.1) ;
-        "shadowed"
-    )]
-    fn lightning(eye: Vector, light_origin: Vector, shadowed: bool, expected: Color) {
-        let shape = Shape::sphere(ShapeArgs::default());
-        let position = Vector::point(0.0, 0.0, 0.0);
-        let normal = Vector::vector(0.0, 0.0, -1.0);
-        let light = PointLight {
-            intensity: Color::white(),
-            origin: light_origin,
+    #[test_case(GroupKind::Union       , Element::union        ; "union"       )]
+    #[test_case(GroupKind::Intersection, Element::intersection ; "intersection")]
+    #[test_case(GroupKind::Difference  , Element::difference   ; "difference"  )]
+    fn csg_constructors(
+        kind: GroupKind,
+        csg: fn(Matrix, Option<Material>, Element, Element) -> Element,
+    ) {
+        let transform = Matrix::translation(1.0, 0.0, 0.0);
+        let material = Some(Material {
+            ambient: 1.0,
+            ..Material::default()
+        });
+        let sphere = || Element::sphere(ShapeArgs::default());
+        let cube = || {
+            Element::cube(ShapeArgs {
+                transform: Matrix::scaling(0.5, 0.5, 0.5),
+                ..ShapeArgs::default()
+            })
         };
 
-        let lighting = shape.lighting(light, position, eye, normal, shadowed);
+        let expected =
+            Element::composite(transform, material.clone(), kind, vec![sphere(), cube()]);
 
-        assert!(lighting.approx(&expected))
+        assert!(csg(transform, material, sphere(), cube()).approx(&expected))
     }
 
     #[test]
-    fn lighting_with_stripe_pattern() {
-        let shape = Shape::sphere(ShapeArgs {
-            material: Material {
-                pattern: Pattern::stripes(
-                    Matrix::id(),
-                    Pattern::plain(Color::white()),
-                    Pattern::plain(Color::black()),
-                ),
+    fn csg_union_all() {
+        let sphere = |x: f64| {
+            Element::sphere(ShapeArgs {
+                transform: Matrix::translation(x, 0.0, 0.0),
+                ..ShapeArgs::default()
+            })
+        };
+
+        let folded = Element::union_all(vec![sphere(-1.0), sphere(0.0), sphere(1.0)]);
+        let nested = Element::union(
+            Matrix::id(),
+            None,
+            Element::union(Matrix::id(), None, sphere(-1.0), sphere(0.0)),
+            sphere(1.0),
+        );
+
+        let ts = |element: &Element, ray: Ray| {
+            let mut is = vec![];
+            element.intersect(ray, &mut is);
+            Intersection::sort(&mut is);
+            is.iter().map(|i| i.t).collect::<Vec<f64>>()
+        };
+
+        let mut rays = (0..8).map(|i| Ray {
+            origin: Vector::point(-5.0, 0.3 * i as f64 - 1.0, -0.5),
+            direction: Vector::vector(1.0, 0.0, 0.1),
+            differentials: None,
+            time: 0.0,
+        });
+
+        assert!(
+            folded.approx(&nested) && rays.all(|ray| ts(&folded, ray).approx(&ts(&nested, ray)))
+        )
+    }
+
+    #[test]
+    fn csg_all_degenerate() {
+        let single = Element::difference_all(vec![Element::sphere(ShapeArgs::default())]);
+        let empty = Element::intersection_all(vec![]);
+
+        assert!(
+            matches!(single, Element::Primitive(_))
+                && matches!(empty, Element::Composite(group) if group.children.is_empty())
+        )
+    }
+
+    fn collect_ids(element: &Element, ids: &mut Vec<usize>) {
+        match element {
+            Element::Composite(group) => group
+                .children
+                .iter()
+                .for_each(|child| collect_ids(child, ids)),
+            Element::Lazy(lazy) => lazy
+                .children
+                .iter()
+                .for_each(|child| collect_ids(child, ids)),
+            Element::Primitive(shape) => ids.push(shape.id),
+        }
+    }
+
+    #[test_case(Vector::point(0.9, 0.9, 0.9), true  ; "remaining corner")]
+    #[test_case(Vector::point(0.0, 0.0, 0.0), false ; "carved out center")]
+    #[test_case(Vector::point(0.0, 0.0, 0.9), false ; "carved out face")]
+    #[test_case(Vector::point(2.0, 0.0, 0.0), false ; "outside")]
+    fn contains_point_difference(point: Vector, expected: bool) {
+        let csg = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Difference,
+            vec![
+                Element::cube(ShapeArgs::default()),
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::scaling(1.2, 1.2, 1.2),
+                    ..ShapeArgs::default()
+                }),
+            ],
+        );
+
+        assert_eq!(csg.contains_point(point), expected)
+    }
+
+    #[test]
+    fn ids_across_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                std::thread::spawn(|| {
+                    (0..100)
+                        .map(|_| Shape::sphere(ShapeArgs::default()).id)
+                        .collect::<Vec<usize>>()
+                })
+            })
+            .collect();
+
+        let ids: HashSet<usize> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert_eq!(ids.len(), 8 * 100)
+    }
+
+    #[test]
+    fn composite_par() {
+        let n = 1000;
+        let child = |i: usize| {
+            Element::sphere(ShapeArgs {
+                transform: Matrix::translation(i as f64, 0.0, 0.0),
+                ..ShapeArgs::default()
+            })
+        };
+
+        let serial = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            (0..n).map(child).collect(),
+        );
+        let parallel = Element::composite_par(Matrix::id(), None, GroupKind::Aggregation, child, n);
+
+        let mut ids = vec![];
+        collect_ids(&serial, &mut ids);
+        let serial_ids: HashSet<usize> = ids.drain(..).collect();
+        collect_ids(&parallel, &mut ids);
+        let parallel_ids: HashSet<usize> = ids.drain(..).collect();
+
+        assert!(
+            serial_ids.len() == n
+                && parallel_ids.len() == n
+                && serial_ids.is_disjoint(&parallel_ids)
+                && serial.bbox().approx(&parallel.bbox())
+        )
+    }
+
+    #[test]
+    fn bbox_tree() {
+        let sphere = |x: f64| {
+            Element::sphere(ShapeArgs {
+                transform: Matrix::translation(x, 0.0, 0.0),
+                ..ShapeArgs::default()
+            })
+        };
+        let group = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![sphere(-2.0), sphere(2.0)],
+        );
+        let tree = group.bbox_tree();
+
+        if let Element::Composite(Group { children, .. }) = &group {
+            assert!(
+                tree.bbox.approx(&group.bbox())
+                    && tree.children.len() == 2
+                    && tree.children[0].bbox.approx(&children[0].bbox())
+                    && tree.children[1].bbox.approx(&children[1].bbox())
+                    && tree.children.iter().all(|node| node.children.is_empty())
+            )
+        }
+    }
+
+    #[test]
+    fn centroid() {
+        let group = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(-2.0, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(2.0, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+            ],
+        );
+
+        assert!(group.centroid().approx(&Vector::point(0.0, 0.0, 0.0)))
+    }
+
+    #[test]
+    fn subdivide() {
+        let children = (0..4)
+            .map(|i| {
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(3.0 * i as f64, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                })
+            })
+            .chain(std::iter::once(Element::plane(ShapeArgs::default())))
+            .collect();
+        let mut group = Element::composite(
+            Matrix::translation(0.0, 0.0, 5.0),
+            None,
+            GroupKind::Aggregation,
+            children,
+        );
+        group.subdivide(1);
+
+        if let Element::Composite(Group { children, .. }) = &group {
+            assert!(children.len() == 3 && matches!(children[0], Element::Primitive(_)));
+            if let Element::Composite(left) = &children[1] {
+                assert!(left.bbox.approx(&BoundingBox::new(
+                    Vector::point(-1.0, -1.0, 4.0),
+                    Vector::point(4.0, 1.0, 6.0),
+                )));
+                assert_eq!(left.children.len(), 2);
+            } else {
+                panic!("expected a sub-group")
+            }
+        }
+    }
+
+    #[test]
+    fn subdivide_straddling() {
+        let triangle = |x: f64| {
+            Element::triangle(
+                ShapeArgs::default(),
+                Vector::point(x - 1.0, 0.0, 0.0),
+                Vector::point(x + 1.0, 0.0, 0.0),
+                Vector::point(x, 1.0, 0.0),
+            )
+        };
+        let mut group = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![triangle(-2.0), triangle(0.0), triangle(2.0)],
+        );
+        group.subdivide(2);
+
+        // the middle triangle straddles x = 0 and goes right with its centroid
+        if let Element::Composite(Group { children, .. }) = &group {
+            let counts: Vec<usize> = children
+                .iter()
+                .map(|child| match child {
+                    Element::Composite(group) => group.children.len(),
+                    _ => 1,
+                })
+                .collect();
+            assert_eq!(counts, vec![1, 2])
+        }
+    }
+
+    #[test]
+    fn subdivide_csg() {
+        let mut group = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Difference,
+            vec![
+                Element::sphere(ShapeArgs::default()),
+                Element::cube(ShapeArgs {
+                    transform: Matrix::translation(5.0, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+            ],
+        );
+        group.subdivide(1);
+
+        if let Element::Composite(Group { children, .. }) = &group {
+            assert!(children
+                .iter()
+                .all(|child| matches!(child, Element::Primitive(_))))
+        }
+    }
+
+    #[test]
+    fn subdivide_intersections() {
+        let children = || {
+            (0..64)
+                .map(|i| {
+                    Element::sphere(ShapeArgs {
+                        transform: Matrix::translation(
+                            (i % 4) as f64 * 2.5,
+                            (i / 4 % 4) as f64 * 2.5,
+                            (i / 16) as f64 * 2.5,
+                        ) * Matrix::scaling(0.8, 0.8, 0.8),
+                        ..ShapeArgs::default()
+                    })
+                })
+                .collect()
+        };
+        let transform = Matrix::rotation_y(0.3) * Matrix::scaling(1.0, 2.0, 1.0);
+        let flat = Element::composite(transform, None, GroupKind::Aggregation, children());
+        let mut bvh = Element::composite(transform, None, GroupKind::Aggregation, children());
+        bvh.subdivide(4);
+
+        let mut hits = 0;
+        for i in 0..20 {
+            let origin = Vector::point(-10.0, i as f64 - 2.0, -10.0);
+            let ray = Ray {
+                origin,
+                direction: (Vector::point(4.0, 6.0, 4.0) - origin).normalize(),
+                differentials: None,
+                time: 0.0,
+            };
+            let mut is1 = vec![];
+            let mut is2 = vec![];
+            flat.intersect(ray, &mut is1);
+            bvh.intersect(ray, &mut is2);
+            let mut ts1: Vec<f64> = is1.iter().map(|is| is.t).collect();
+            let mut ts2: Vec<f64> = is2.iter().map(|is| is.t).collect();
+            ts1.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            ts2.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            assert!(ts1.approx(&ts2));
+            hits += ts1.len();
+        }
+
+        assert!(hits > 0)
+    }
+
+    #[derive(Debug)]
+    enum Nesting {
+        Flat,
+        Lazy,
+        Composite,
+    }
+
+    #[test_case(Nesting::Flat      ; "flat")]
+    #[test_case(Nesting::Lazy      ; "nested")]
+    #[test_case(Nesting::Composite ; "inside composite")]
+    fn lazy_group(nesting: Nesting) {
+        let children = || {
+            vec![
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(-2.0, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+                Element::cube(ShapeArgs {
+                    transform: Matrix::translation(2.0, 0.0, 0.0) * Matrix::rotation_y(0.5),
+                    ..ShapeArgs::default()
+                }),
+            ]
+        };
+        let outer = Matrix::translation(0.0, 1.0, 3.0) * Matrix::rotation_z(0.4);
+        let inner = Matrix::scaling(1.0, 2.0, 0.5);
+
+        let baked = || {
+            Element::composite(
+                outer,
+                None,
+                GroupKind::Aggregation,
+                vec![Element::composite(
+                    inner,
+                    None,
+                    GroupKind::Aggregation,
+                    children(),
+                )],
+            )
+        };
+
+        let (baked, lazy) = match nesting {
+            Nesting::Flat => (
+                Element::composite(outer * inner, None, GroupKind::Aggregation, children()),
+                Element::lazy(outer * inner, children()),
+            ),
+            Nesting::Lazy => (
+                baked(),
+                Element::lazy(outer, vec![Element::lazy(inner, children())]),
+            ),
+            Nesting::Composite => (
+                baked(),
+                Element::composite(
+                    outer,
+                    None,
+                    GroupKind::Aggregation,
+                    vec![Element::lazy(inner, children())],
+                ),
+            ),
+        };
+
+        assert!(baked.bbox().approx(&lazy.bbox()));
+
+        let mut hits = 0;
+        for i in 0..40 {
+            let origin = Vector::point(0.0, 1.0, -10.0);
+            let ray = Ray {
+                origin,
+                direction: (Vector::point(-4.0 + 0.2 * i as f64, 1.5, 3.0) - origin).normalize(),
+                differentials: None,
+                time: 0.0,
+            };
+            let mut is1 = vec![];
+            let mut is2 = vec![];
+            baked.intersect(ray, &mut is1);
+            lazy.intersect(ray, &mut is2);
+            Intersection::sort(&mut is1);
+            Intersection::sort(&mut is2);
+
+            assert_eq!(is1.len(), is2.len());
+            for (i1, i2) in is1.iter().zip(&is2) {
+                let point = ray.position(i1.t);
+                assert!(i1.t.approx(&i2.t) && i1.normal(point, 0.0).approx(&i2.normal(point, 0.0)));
+            }
+            hits += is1.len();
+        }
+
+        assert!(hits > 0)
+    }
+
+    #[test]
+    fn lazy_shared_instances() {
+        let children = || {
+            vec![
+                Element::sphere(ShapeArgs::default()),
+                Element::cube(ShapeArgs {
+                    transform: Matrix::translation(0.0, 2.0, 0.0) * Matrix::rotation_y(0.5),
+                    ..ShapeArgs::default()
+                }),
+            ]
+        };
+        let left = Matrix::translation(-3.0, 0.0, 0.0) * Matrix::rotation_z(0.4);
+        let right = Matrix::translation(3.0, 1.0, 2.0) * Matrix::scaling(1.0, 2.0, 0.5);
+
+        let baked = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![
+                Element::composite(left, None, GroupKind::Aggregation, children()),
+                Element::composite(right, None, GroupKind::Aggregation, children()),
+            ],
+        );
+        let shared: Arc<[Element]> = Arc::from(children());
+        let instances = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![
+                Element::instance(left, shared.clone()),
+                Element::instance(right, shared.clone()),
+            ],
+        );
+
+        // both instances and the local handle point at the same children
+        assert_eq!(Arc::strong_count(&shared), 3);
+        assert!(baked.bbox().approx(&instances.bbox()));
+
+        let mut hits = 0;
+        for i in 0..40 {
+            let origin = Vector::point(0.0, 1.0, -10.0);
+            let ray = Ray {
+                origin,
+                direction: (Vector::point(-6.0 + 0.3 * i as f64, 1.0, 0.0) - origin).normalize(),
+                differentials: None,
+                time: 0.0,
+            };
+            let mut is1 = vec![];
+            let mut is2 = vec![];
+            baked.intersect(ray, &mut is1);
+            instances.intersect(ray, &mut is2);
+            Intersection::sort(&mut is1);
+            Intersection::sort(&mut is2);
+
+            assert_eq!(is1.len(), is2.len());
+            for (i1, i2) in is1.iter().zip(&is2) {
+                let point = ray.position(i1.t);
+                assert!(i1.t.approx(&i2.t) && i1.normal(point, 0.0).approx(&i2.normal(point, 0.0)));
+            }
+            hits += is1.len();
+        }
+
+        assert!(hits > 0)
+    }
+
+    #[test_case(Matrix::scaling(2.0, 2.0, 2.0), Matrix::id()                        ; "scaled lazy"      )]
+    #[test_case(Matrix::id(),                   Matrix::translation(0.0, 0.0, 10.0) ; "translated parent")]
+    #[test_case(Matrix::scaling(1.0, 1.0, 2.0), Matrix::scaling(3.0, 1.0, 1.0)      ; "both scaled"      )]
+    fn lazy_closest_surface(transform: Matrix, parent: Matrix) {
+        let sphere = || Element::sphere(ShapeArgs::default());
+        let baked = Element::composite(
+            parent,
+            None,
+            GroupKind::Aggregation,
+            vec![Element::composite(
+                transform,
+                None,
+                GroupKind::Aggregation,
+                vec![sphere()],
+            )],
+        );
+        let lazy = Element::composite(
+            parent,
+            None,
+            GroupKind::Aggregation,
+            vec![Element::lazy(transform, vec![sphere()])],
+        );
+
+        let point = Vector::point(0.0, 0.0, -5.0);
+        let (expected, _) = baked.closest_surface(point).unwrap();
+        let (distance, _) = lazy.closest_surface(point).unwrap();
+
+        assert!(distance.approx(&expected))
+    }
+
+    #[test]
+    fn composite_with_bbox() {
+        let children = || {
+            vec![
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(-2.0, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+                Element::cube(ShapeArgs {
+                    transform: Matrix::translation(2.0, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+            ]
+        };
+        let transform = Matrix::scaling(1.0, 2.0, 1.0);
+        let computed = Element::composite(transform, None, GroupKind::Aggregation, children());
+        let explicit = Element::composite_with_bbox(
+            transform,
+            None,
+            GroupKind::Aggregation,
+            children(),
+            BoundingBox::new(
+                Vector::point(-3.0, -1.0, -1.0),
+                Vector::point(3.0, 1.0, 1.0),
+            ),
+        );
+
+        for (origin, direction) in [
+            (
+                Vector::point(-2.0, 0.0, -5.0),
+                Vector::vector(0.0, 0.0, 1.0),
+            ),
+            (Vector::point(2.0, 0.5, -5.0), Vector::vector(0.0, 0.0, 1.0)),
+            (Vector::point(-5.0, 1.5, 0.0), Vector::vector(1.0, 0.0, 0.0)),
+            (Vector::point(0.0, 5.0, -5.0), Vector::vector(0.0, 0.0, 1.0)),
+        ] {
+            let ray = Ray {
+                origin,
+                direction,
+                differentials: None,
+                time: 0.0,
+            };
+            let mut is1 = vec![];
+            let mut is2 = vec![];
+            computed.intersect(ray, &mut is1);
+            explicit.intersect(ray, &mut is2);
+
+            let ts1: Vec<f64> = is1.iter().map(|i| i.t).collect();
+            let ts2: Vec<f64> = is2.iter().map(|i| i.t).collect();
+
+            assert!(ts1.approx(&ts2))
+        }
+
+        assert!(computed.bbox().approx(&explicit.bbox()))
+    }
+
+    #[test]
+    fn intersect_bounded() {
+        let group = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![
+                Element::sphere(ShapeArgs::default()),
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(0.0, 0.0, 10.0),
+                    ..ShapeArgs::default()
+                }),
+            ],
+        );
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        group.intersect_bounded(ray, 8.0, &mut is);
+        Intersection::sort(&mut is);
+
+        let ts: Vec<f64> = is.iter().map(|i| i.t).collect();
+
+        assert!(ts.approx(&vec![4.0, 6.0]))
+    }
+
+    #[test]
+    fn ray_csg_nested() {
+        let union = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Union,
+            vec![
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(-0.5, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(0.5, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+            ],
+        );
+        let difference = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Difference,
+            vec![
+                union,
+                Element::cube(ShapeArgs {
+                    transform: Matrix::scaling(0.25, 0.25, 0.25),
+                    ..ShapeArgs::default()
+                }),
+            ],
+        );
+        let group = Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(10.0, 0.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+                difference,
+            ],
+        );
+        let ray = Ray {
+            origin: Vector::point(-5.0, 0.0, 0.0),
+            direction: Vector::vector(1.0, 0.0, 0.0),
+            differentials: None,
+            time: 0.0,
+        };
+        let mut is = vec![];
+        group.intersect(ray, &mut is);
+        Intersection::sort(&mut is);
+
+        let ts: Vec<f64> = is.iter().map(|i| i.t).collect();
+
+        assert!(ts.approx(&vec![3.5, 4.75, 5.25, 6.5, 14.0, 16.0]))
+    }
+
+    // Lighting Tests
+
+    #[test_case(
+        Vector::vector(0.0, 0.0, -1.0),
+        Vector::point(0.0, 0.0, -10.0),
+        1.0,
+        Color::new(1.9, 1.9, 1.9) ;
+        "light eye surface"
+    )]
+    #[test_case(
+        Vector::vector(0.0, 2.0f64.sqrt() / 2.0,2.0f64.sqrt() / -2.0),
+        Vector::point(0.0, 0.0, -10.0),
+        1.0,
+        Color::white() ;
+        "light eye surface (eye offset)"
+    )]
+    #[test_case(
+        Vector::vector(0.0, 0.0, -1.0),
+        Vector::point(0.0, 10.0, -10.0),
+        1.0,
+        Color::new(0.7364, 0.7364, 0.7364) ;
+        "eye light surface (light offset)"
+    )]
+    #[test_case(
+        Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / -2.0),
+        Vector::point(0.0, 10.0, -10.0),
+        1.0,
+        Color::new(1.6364, 1.6364, 1.6364) ;
+        "eye light surface (eye and light offset)"
+    )]
+    #[test_case(
+        Vector::vector(0.0, 0.0, -1.0),
+        Vector::point(0.0, 0.0, 10.0),
+        1.0,
+        Color::new(0.1, 0.1, 0.1) ;
+        "eye surface light"
+    )]
+    #[test_case(
+        Vector::vector(0.0, 0.0, -1.0),
+        Vector::point(0.0, 0.0, -10.0),
+        0.0,
+        Color::new(0.1, 0.1, 0.1) ;
+        "shadowed"
+    )]
+    fn lightning(eye: Vector, light_origin: Vector, intensity: f64, expected: Color) {
+        let shape = Shape::sphere(ShapeArgs::default());
+        let position = Vector::point(0.0, 0.0, 0.0);
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+        let light = Light::Point(PointLight {
+            intensity: Color::white(),
+            origin: light_origin,
+        });
+
+        let lighting = shape.lighting(light, position, eye, normal, intensity);
+
+        assert!(lighting.approx(&expected))
+    }
+
+    #[test]
+    fn lighting_with_stripe_pattern() {
+        let shape = Shape::sphere(ShapeArgs {
+            material: Material {
+                pattern: Pattern::stripes(
+                    Matrix::id(),
+                    Pattern::plain(Color::white()),
+                    Pattern::plain(Color::black()),
+                ),
                 ambient: 1.0,
                 diffuse: 0.0,
                 specular: 0.0,
@@ -1806,14 +4106,169 @@ mod tests {
 
         let eye = Vector::vector(0.0, 0.0, -1.0);
         let normal = Vector::vector(0.0, 0.0, -1.0);
-        let light = PointLight {
+        let light = Light::Point(PointLight {
             intensity: Color::white(),
             origin: Vector::point(0.0, 0.0, -10.0),
-        };
+        });
 
-        let color1 = shape.lighting(light, Vector::point(0.9, 0.0, 0.0), eye, normal, false);
-        let color2 = shape.lighting(light, Vector::point(1.1, 0.0, 0.0), eye, normal, false);
+        let color1 = shape.lighting(light, Vector::point(0.9, 0.0, 0.0), eye, normal, 1.0);
+        let color2 = shape.lighting(light, Vector::point(1.1, 0.0, 0.0), eye, normal, 1.0);
 
         assert!(color1.approx(&Color::white()) && color2.approx(&Color::black()))
     }
+
+    #[test_case(false, 0.9 ; "lambertian")]
+    #[test_case(true, 0.9 / std::f64::consts::PI ; "normalized lambertian")]
+    fn lighting_normalized_diffuse(normalized_diffuse: bool, expected: f64) {
+        let shape = Shape::sphere(ShapeArgs {
+            material: Material {
+                ambient: 0.0,
+                specular: 0.0,
+                normalized_diffuse,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        });
+
+        let eye = Vector::vector(0.0, 0.0, -1.0);
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+        let light = Light::Point(PointLight {
+            intensity: Color::white(),
+            origin: Vector::point(0.0, 0.0, -10.0),
+        });
+
+        let color = shape.lighting(light, Vector::point(0.0, 0.0, 0.0), eye, normal, 1.0);
+
+        assert!(color.approx(&Color::new(expected, expected, expected)))
+    }
+
+    #[test_case(1.0, Color::new(1.0 , 1.0 , 1.0 ) ; "lit")]
+    #[test_case(0.5, Color::new(0.55, 0.55, 0.55) ; "half")]
+    #[test_case(0.0, Color::new(0.1 , 0.1 , 0.1 ) ; "shadowed")]
+    fn lighting_intensity(intensity: f64, expected: Color) {
+        let shape = Shape::sphere(ShapeArgs {
+            material: Material {
+                specular: 0.0,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        });
+        let light = Light::Point(PointLight {
+            intensity: Color::white(),
+            origin: Vector::point(0.0, 0.0, -10.0),
+        });
+        let point = Vector::point(0.0, 0.0, -1.0);
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+
+        let lighting = shape.lighting(light, point, normal, normal, intensity);
+
+        assert!(lighting.approx(&expected))
+    }
+
+    // the light sits slightly behind the terminator, light_dot_normal = -0.2
+    #[test_case(0.0, Color::new(0.1 , 0.1 , 0.1 ) ; "no wrap")]
+    #[test_case(0.5, Color::new(0.28, 0.28, 0.28) ; "half wrap")]
+    fn lighting_wrap(wrap: f64, expected: Color) {
+        let shape = Shape::sphere(ShapeArgs {
+            material: Material {
+                wrap,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        });
+        let light = Light::Point(PointLight {
+            intensity: Color::white(),
+            origin: Vector::point(24.0_f64.sqrt() * 2.0, 0.0, 2.0),
+        });
+        let point = Vector::point(0.0, 0.0, 0.0);
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+
+        let lighting = shape.lighting(light, point, normal, normal, 1.0);
+
+        assert!(lighting.approx(&expected))
+    }
+
+    #[test_case(Vector::point(0.0, 0.0, 0.0), Color::new(1.9, 1.9, 1.9) ; "dead center")]
+    #[test_case(Vector::point(5.0, 0.0, 0.0), Color::new(0.1, 0.1, 0.1) ; "outside")]
+    fn lighting_spot_light(point: Vector, expected: Color) {
+        let shape = Shape::sphere(ShapeArgs::default());
+        let light = Light::Spot(SpotLight {
+            intensity: Color::white(),
+            origin: Vector::point(0.0, 0.0, -10.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            inner_angle: 0.2,
+            outer_angle: 0.4,
+        });
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+
+        let lighting = shape.lighting(light, point, normal, normal, 1.0);
+
+        assert!(lighting.approx(&expected))
+    }
+
+    #[test]
+    fn lighting_area_light() {
+        let shape = Shape::sphere(ShapeArgs {
+            material: Material {
+                ambient: 0.1,
+                diffuse: 0.9,
+                specular: 0.0,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        });
+        let light = Light::Area(AreaLight {
+            intensity: Color::white(),
+            origin: Vector::point(-0.5, -0.5, -5.0),
+            uvec: Vector::vector(1.0, 0.0, 0.0),
+            vvec: Vector::vector(0.0, 1.0, 0.0),
+            usteps: 2,
+            vsteps: 2,
+        });
+        let point = Vector::point(0.0, 0.0, -1.0);
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+
+        // slightly off-axis samples spread the diffuse term just below a head-on light
+        let lighting = shape.lighting(light, point, normal, normal, 1.0);
+
+        assert!(
+            lighting.r < 1.0
+                && lighting.r > 0.95
+                && lighting.approx(&Color::new(lighting.r, lighting.r, lighting.r))
+        )
+    }
+
+    #[test]
+    fn lighting_specular_color() {
+        let base = Color::new(0.2, 0.4, 0.6);
+        let shape = |specular_color: Color| {
+            Shape::sphere(ShapeArgs {
+                material: Material {
+                    pattern: Pattern::plain(base),
+                    ambient: 0.0,
+                    specular_color,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            })
+        };
+
+        let eye = Vector::vector(0.0, 0.0, -1.0);
+        let normal = Vector::vector(0.0, 0.0, -1.0);
+        let light = Light::Point(PointLight {
+            intensity: Color::white(),
+            origin: Vector::point(0.0, 0.0, -10.0),
+        });
+        let lighting =
+            |shape: Shape| shape.lighting(light, Vector::point(0.0, 0.0, 0.0), eye, normal, 1.0);
+
+        let diffuse = base * 0.9;
+        let white = lighting(shape(Color::white()));
+        let tinted = lighting(shape(Color::new(1.0, 0.0, 0.0)));
+
+        assert!(
+            white.approx(&(diffuse + Color::new(0.9, 0.9, 0.9)))
+                && tinted.approx(&(diffuse + Color::new(0.9, 0.0, 0.0)))
+        )
+    }
 }
@@ -0,0 +1,101 @@
+use std::cell::Cell;
+use std::iter::Sum;
+use std::ops::{Add, Sub};
+
+// kept per thread so that concurrent renders never see each other's counts and the
+// hot paths don't fight over a shared cache line
+thread_local! {
+    static COUNTERS: Cell<RenderStats> = Cell::new(RenderStats::default());
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub bbox_tests: u64,
+    pub bbox_hits: u64,
+    pub primitive_tests: u64,
+}
+
+impl RenderStats {
+    // running totals of the calling thread, only differences between two reads are meaningful
+    pub fn current() -> RenderStats {
+        COUNTERS.with(Cell::get)
+    }
+
+    pub fn culled(&self) -> u64 {
+        self.bbox_tests - self.bbox_hits
+    }
+
+    pub fn count_bbox(hit: bool) {
+        COUNTERS.with(|counters| {
+            let mut stats = counters.get();
+            stats.bbox_tests += 1;
+            stats.bbox_hits += hit as u64;
+            counters.set(stats);
+        })
+    }
+
+    pub fn count_primitive() {
+        COUNTERS.with(|counters| {
+            let mut stats = counters.get();
+            stats.primitive_tests += 1;
+            counters.set(stats);
+        })
+    }
+}
+
+impl Add for RenderStats {
+    type Output = RenderStats;
+
+    fn add(self, other: RenderStats) -> RenderStats {
+        RenderStats {
+            bbox_tests: self.bbox_tests + other.bbox_tests,
+            bbox_hits: self.bbox_hits + other.bbox_hits,
+            primitive_tests: self.primitive_tests + other.primitive_tests,
+        }
+    }
+}
+
+impl Sub for RenderStats {
+    type Output = RenderStats;
+
+    fn sub(self, other: RenderStats) -> RenderStats {
+        RenderStats {
+            bbox_tests: self.bbox_tests - other.bbox_tests,
+            bbox_hits: self.bbox_hits - other.bbox_hits,
+            primitive_tests: self.primitive_tests - other.primitive_tests,
+        }
+    }
+}
+
+impl Sum for RenderStats {
+    fn sum<I: Iterator<Item = RenderStats>>(iter: I) -> RenderStats {
+        iter.fold(RenderStats::default(), Add::add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counting() {
+        let before = RenderStats::current();
+
+        RenderStats::count_bbox(true);
+        RenderStats::count_bbox(false);
+        RenderStats::count_bbox(false);
+        RenderStats::count_primitive();
+
+        let stats = RenderStats::current() - before;
+
+        assert!(
+            stats
+                == RenderStats {
+                    bbox_tests: 3,
+                    bbox_hits: 1,
+                    primitive_tests: 1,
+                }
+                && stats.culled() == 2
+        )
+    }
+}
@@ -0,0 +1,370 @@
+use crate::approx::Approx;
+use crate::color::Color;
+use crate::linalg::Vector;
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// color textures are usually authored in sRGB, data textures (normals, roughness)
+// store raw values that must not be linearized
+#[derive(Debug, Clone, Copy)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    fn decode(self, channel: f64) -> f64 {
+        match self {
+            ColorSpace::Linear => channel,
+            ColorSpace::Srgb if channel <= 0.04045 => channel / 12.92,
+            ColorSpace::Srgb => ((channel + 0.055) / 1.055).powf(2.4),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> ImageTexture {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height);
+
+        ImageTexture {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn from_ppm<P: AsRef<Path>>(path: P) -> io::Result<ImageTexture> {
+        ImageTexture::parse_ppm(&fs::read(path)?)
+    }
+
+    // understands both the plain (P3) and the raw (P6) flavour
+    pub fn parse_ppm(bytes: &[u8]) -> io::Result<ImageTexture> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let magic = match bytes.get(0..2) {
+            Some(b"P3") => 3,
+            Some(b"P6") => 6,
+            _ => return Err(invalid("not a P3 or P6 ppm")),
+        };
+        let mut pos = 2;
+        let number = |pos: &mut usize| {
+            ppm_token(bytes, pos)
+                .and_then(|token| std::str::from_utf8(token).ok())
+                .and_then(|token| token.parse::<usize>().ok())
+                .ok_or_else(|| invalid("expected a number"))
+        };
+
+        let width = number(&mut pos)?;
+        let height = number(&mut pos)?;
+        let max = number(&mut pos)?;
+        if width == 0 || height == 0 || max == 0 || max > 255 {
+            return Err(invalid("unsupported ppm dimensions or depth"));
+        }
+
+        let channels: Vec<usize> = if magic == 3 {
+            (0..width * height * 3)
+                .map(|_| number(&mut pos))
+                .collect::<io::Result<_>>()?
+        } else {
+            // exactly one whitespace byte separates the header from the raster
+            let start = pos + 1;
+            bytes
+                .get(start..start + width * height * 3)
+                .ok_or_else(|| invalid("truncated ppm raster"))?
+                .iter()
+                .map(|&byte| byte as usize)
+                .collect()
+        };
+
+        let pixels = channels
+            .chunks(3)
+            .map(|rgb| {
+                Color::new(
+                    rgb[0] as f64 / max as f64,
+                    rgb[1] as f64 / max as f64,
+                    rgb[2] as f64 / max as f64,
+                )
+            })
+            .collect();
+
+        Ok(ImageTexture::new(width, height, pixels))
+    }
+
+    pub fn from_png<P: AsRef<Path>>(path: P, space: ColorSpace) -> io::Result<ImageTexture> {
+        let mut decoder = png::Decoder::new(fs::File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            png::ColorType::Indexed => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpanded indexed png",
+                ))
+            }
+        };
+
+        let channel = |byte: u8| space.decode(byte as f64 / 255.0);
+        let pixels = data[..info.buffer_size()]
+            .chunks(channels)
+            .map(|pixel| {
+                if channels < 3 {
+                    let gray = channel(pixel[0]);
+                    Color::new(gray, gray, gray)
+                } else {
+                    Color::new(channel(pixel[0]), channel(pixel[1]), channel(pixel[2]))
+                }
+            })
+            .collect();
+
+        Ok(ImageTexture::new(
+            info.width as usize,
+            info.height as usize,
+            pixels,
+        ))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // v = 1 is the top row of the image
+    pub fn sample(&self, u: f64, v: f64) -> Color {
+        let x = u.clamp(0.0, 1.0) * (self.width - 1) as f64;
+        let y = (1.0 - v.clamp(0.0, 1.0)) * (self.height - 1) as f64;
+
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+
+        let top = self.read(x0, y0) * (1.0 - fx) + self.read(x1, y0) * fx;
+        let bottom = self.read(x0, y1) * (1.0 - fx) + self.read(x1, y1) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    fn read(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+}
+
+fn ppm_token<'a>(bytes: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+        } else {
+            break;
+        }
+    }
+
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+
+    if start < *pos {
+        Some(&bytes[start..*pos])
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum UvMapping {
+    Planar,
+    Spherical,
+    Cylindrical,
+}
+
+impl Approx<UvMapping> for UvMapping {
+    fn approx(&self, other: &UvMapping) -> bool {
+        matches!(
+            (self, other),
+            (UvMapping::Planar, UvMapping::Planar)
+                | (UvMapping::Spherical, UvMapping::Spherical)
+                | (UvMapping::Cylindrical, UvMapping::Cylindrical)
+        )
+    }
+}
+
+impl UvMapping {
+    pub fn uv(&self, point: Vector) -> (f64, f64) {
+        match self {
+            UvMapping::Planar => (point.x.rem_euclid(1.0), point.z.rem_euclid(1.0)),
+            UvMapping::Spherical => {
+                let radius = (point - Vector::point(0.0, 0.0, 0.0)).magnitude();
+                if radius == 0.0 {
+                    return (0.5, 0.5);
+                }
+
+                // rounding can push y / radius just past 1 at the poles
+                let phi = (point.y / radius).clamp(-1.0, 1.0).acos();
+
+                (UvMapping::azimuth(point), 1.0 - phi / PI)
+            }
+            UvMapping::Cylindrical => (UvMapping::azimuth(point), point.y.rem_euclid(1.0)),
+        }
+    }
+
+    // the direction from the center of the unit sphere that maps to (u, v)
+    pub fn spherical_direction(u: f64, v: f64) -> Vector {
+        let phi = (1.0 - v) * PI;
+        let theta = (0.5 - u) * 2.0 * PI;
+
+        Vector::vector(phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos())
+    }
+
+    // 0 and 1 meet at -z, u increases counter-clockwise seen from above
+    fn azimuth(point: Vector) -> f64 {
+        let theta = point.x.atan2(point.z);
+
+        1.0 - (theta / (2.0 * PI) + 0.5)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unused_unit, clippy::approx_constant)]
+mod tests {
+    use super::*;
+
+    use crate::image::Image;
+
+    use test_case::test_case;
+
+    fn texture() -> ImageTexture {
+        ImageTexture::new(
+            2,
+            2,
+            vec![
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(0.0, 1.0, 0.0),
+                Color::new(0.0, 0.0, 1.0),
+                Color::white(),
+            ],
+        )
+    }
+
+    #[test_case(0.0, 1.0, Color::new(1.0, 0.0, 0.0) ; "top left")]
+    #[test_case(1.0, 1.0, Color::new(0.0, 1.0, 0.0) ; "top right")]
+    #[test_case(0.0, 0.0, Color::new(0.0, 0.0, 1.0) ; "bottom left")]
+    #[test_case(0.5, 1.0, Color::new(0.5, 0.5, 0.0) ; "top edge")]
+    #[test_case(0.5, 0.5, Color::new(0.5, 0.5, 0.5) ; "center")]
+    #[test_case(1.0, 0.5, Color::new(0.5, 1.0, 0.5) ; "right edge")]
+    fn texture_sample(u: f64, v: f64, expected: Color) {
+        assert!(texture().sample(u, v).approx(&expected))
+    }
+
+    #[test_case(Vector::point( 0.0     ,  0.0     , -1.0), (0.0 , 0.5 ) ; "back")]
+    #[test_case(Vector::point( 1.0     ,  0.0     ,  0.0), (0.25, 0.5 ) ; "right")]
+    #[test_case(Vector::point( 0.0     ,  0.0     ,  1.0), (0.5 , 0.5 ) ; "front")]
+    #[test_case(Vector::point(-1.0     ,  0.0     ,  0.0), (0.75, 0.5 ) ; "left")]
+    #[test_case(Vector::point( 0.0     ,  1.0     ,  0.0), (0.5 , 1.0 ) ; "north pole")]
+    #[test_case(Vector::point( 0.0     , -1.0     ,  0.0), (0.5 , 0.0 ) ; "south pole")]
+    #[test_case(Vector::point( 0.0     ,  2.0     ,  0.0), (0.5 , 1.0 ) ; "scaled pole")]
+    #[test_case(Vector::point( 0.707107,  0.707107,  0.0), (0.25, 0.75) ; "tilted")]
+    fn spherical_mapping(point: Vector, expected: (f64, f64)) {
+        let (u, v) = UvMapping::Spherical.uv(point);
+
+        assert!(u.approx(&expected.0) && v.approx(&expected.1))
+    }
+
+    #[test_case(Vector::point( 0.25, 0.0,  0.5 ), (0.25, 0.5 ) ; "inside unit square")]
+    #[test_case(Vector::point( 1.25, 0.0, -0.25), (0.25, 0.75) ; "wraps around")]
+    fn planar_mapping(point: Vector, expected: (f64, f64)) {
+        let (u, v) = UvMapping::Planar.uv(point);
+
+        assert!(u.approx(&expected.0) && v.approx(&expected.1))
+    }
+
+    #[test_case(Vector::point(0.0, 0.0 , -1.0), (0.0 , 0.0 ) ; "back")]
+    #[test_case(Vector::point(0.0, 0.5 ,  1.0), (0.5 , 0.5 ) ; "front")]
+    #[test_case(Vector::point(1.0, 1.25,  0.0), (0.25, 0.25) ; "wraps height")]
+    fn cylindrical_mapping(point: Vector, expected: (f64, f64)) {
+        let (u, v) = UvMapping::Cylindrical.uv(point);
+
+        assert!(u.approx(&expected.0) && v.approx(&expected.1))
+    }
+
+    #[test]
+    fn parse_ppm_round_trip() {
+        let mut image = Image::new(3, 2);
+        image.write(0, 0, Color::new(1.0, 0.0, 0.0));
+        image.write(2, 1, Color::new(0.0, 0.2, 1.0));
+
+        let plain = ImageTexture::parse_ppm(image.ppm().as_bytes()).unwrap();
+        let raw = ImageTexture::parse_ppm(&image.ppm_binary()).unwrap();
+
+        assert!(
+            plain.width() == 3
+                && plain.height() == 2
+                && plain.pixels.approx(&raw.pixels)
+                && plain.read(0, 0).approx(&Color::new(1.0, 0.0, 0.0))
+                && plain.read(2, 1).approx(&Color::new(0.0, 51.0 / 255.0, 1.0))
+        )
+    }
+
+    #[test]
+    fn parse_ppm_comments() {
+        let ppm = "P3\n# a comment\n1 1 # trailing\n255\n0 128 255\n";
+        let texture = ImageTexture::parse_ppm(ppm.as_bytes()).unwrap();
+
+        assert!(texture
+            .read(0, 0)
+            .approx(&Color::new(0.0, 128.0 / 255.0, 1.0)))
+    }
+
+    #[test]
+    fn from_png_color_space() {
+        let path = "from_png_color_space.png";
+        let mut image = Image::new(2, 1);
+        image.write(0, 0, Color::new(0.5, 0.5, 0.5));
+        image.write(1, 0, Color::white());
+        image.png(path).unwrap();
+
+        let linear = ImageTexture::from_png(path, ColorSpace::Linear).unwrap();
+        let srgb = ImageTexture::from_png(path, ColorSpace::Srgb).unwrap();
+        let _ = fs::remove_file(path);
+
+        let gray = 128.0 / 255.0;
+
+        assert!(
+            linear.read(0, 0).approx(&Color::new(gray, gray, gray))
+                && srgb
+                    .read(0, 0)
+                    .approx(&Color::new(0.21586, 0.21586, 0.21586))
+                && linear.read(1, 0).approx(&srgb.read(1, 0))
+        )
+    }
+
+    #[test_case("P5\n1 1\n255\n0"    ; "wrong magic")]
+    #[test_case("P3\n2 1\n255\n0 0 0" ; "truncated")]
+    fn parse_ppm_invalid(ppm: &str) {
+        assert!(ImageTexture::parse_ppm(ppm.as_bytes()).is_err())
+    }
+}
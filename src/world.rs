@@ -1,76 +1,420 @@
+use crate::camera::Camera;
+use crate::config::Settings;
+use crate::image::{DepthImage, Image, Passes};
 use crate::intersection::{Intersection, State};
-use crate::light::PointLight;
+use crate::light::{AreaLight, Light, PointLight};
 use crate::linalg::{Matrix, Vector};
 use crate::material::{Material, Pattern};
 use crate::ray::Ray;
-use crate::shape::Element;
+use crate::shape::{Element, Shape};
+use crate::texture::{ImageTexture, UvMapping};
 use crate::{color::Color, shape::ShapeArgs};
 
+use rayon::prelude::*;
+
 use std::default::Default;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// shapes default to layer 1, but a plain render sees every layer
+const ALL_LAYERS: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy)]
+pub enum NormalSpace {
+    World,
+    Object,
+}
+
+#[derive(Debug, Clone)]
+pub enum Background {
+    Solid(Color),
+    Gradient {
+        top: Color,
+        bottom: Color,
+    },
+    Environment {
+        texture: Arc<ImageTexture>,
+        transform_inv: Matrix,
+    },
+}
+
+impl From<Color> for Background {
+    fn from(color: Color) -> Background {
+        Background::Solid(color)
+    }
+}
+
+impl Background {
+    // an equirectangular map, the transform turns it independently of the camera
+    pub fn environment(texture: Arc<ImageTexture>, transform: Matrix) -> Background {
+        Background::Environment {
+            texture,
+            transform_inv: transform.inverse(),
+        }
+    }
+
+    pub fn color_at(&self, direction: Vector) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { top, bottom } => {
+                let t = (direction.normalize().y + 1.0) / 2.0;
+                *bottom + (*top - *bottom) * t
+            }
+            Background::Environment {
+                texture,
+                transform_inv,
+            } => {
+                let direction = *transform_inv * direction;
+                let (u, v) =
+                    UvMapping::Spherical.uv(Vector::point(direction.x, direction.y, direction.z));
+
+                texture.sample(u, v)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationWarning {
+    NonFiniteLight { light: usize },
+    RefractiveIndexBelowOne { refractive_index: f64 },
+    DegenerateTransform,
+    EmptyGroup,
+}
+
+fn validate_element(element: &Element, warnings: &mut Vec<ValidationWarning>) {
+    match element {
+        Element::Composite(group) => {
+            if group.children.is_empty() {
+                warnings.push(ValidationWarning::EmptyGroup);
+            }
+            for child in &group.children {
+                validate_element(child, warnings);
+            }
+        }
+        Element::Lazy(lazy) => {
+            if !lazy.transform_inv.is_finite() {
+                warnings.push(ValidationWarning::DegenerateTransform);
+            }
+            for child in lazy.children.iter() {
+                validate_element(child, warnings);
+            }
+        }
+        Element::Primitive(shape) => {
+            if !shape.transform_inv.is_finite() || !shape.material_inv.is_finite() {
+                warnings.push(ValidationWarning::DegenerateTransform);
+            }
+            if shape.material.refractive_index < 1.0 {
+                warnings.push(ValidationWarning::RefractiveIndexBelowOne {
+                    refractive_index: shape.material.refractive_index,
+                });
+            }
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct World {
-    pub lights: Vec<PointLight>,
+    pub lights: Vec<Light>,
     pub elements: Vec<Element>,
+    pub clip_plane: Option<(Vector, f64)>,
+    pub background: Background,
+    pub settings: Settings,
 }
 
 impl World {
-    fn intersect<'a>(&'a self, ray: Ray, intersections: &mut Vec<Intersection<'a>>) {
+    fn intersect<'a>(&'a self, ray: Ray, mask: u32, intersections: &mut Vec<Intersection<'a>>) {
+        self.intersect_bounded(ray, f64::INFINITY, mask, intersections)
+    }
+
+    fn intersect_bounded<'a>(
+        &'a self,
+        ray: Ray,
+        t_max: f64,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) {
         intersections.clear();
 
         for element in &self.elements {
-            element.intersect(ray, intersections);
+            element.intersect_bounded(ray, t_max, intersections);
+        }
+
+        if let Some((normal, offset)) = self.clip_plane {
+            intersections.retain(|intersection| normal.dot(ray.position(intersection.t)) <= offset);
         }
+        intersections.retain(|intersection| intersection.shape.layer & mask != 0);
     }
 
     fn is_shadowed<'a>(
         &'a self,
-        light: PointLight,
+        origin: Vector,
         point: Vector,
+        time: f64,
+        ignore: Option<&Shape>,
+        mask: u32,
         intersections: &mut Vec<Intersection<'a>>,
     ) -> bool {
-        let vector = light.origin - point;
+        let vector = origin - point;
+        let distance = vector.magnitude();
+
+        let ray = Ray {
+            origin: point,
+            direction: vector.normalize(),
+            differentials: None,
+            time,
+        };
+
+        self.intersect_bounded(ray, distance, mask, intersections);
+
+        // a surface that doesn't cast shadows (e.g. the inner wall carved by a CSG
+        // cutter) must not hide the shadow casting surfaces behind it
+        intersections.iter().any(|intersection| {
+            intersection.shape.casts_shadow
+                && ignore != Some(intersection.shape)
+                && intersection.t >= 0.0
+                && intersection.t < distance
+        })
+    }
+
+    pub fn intensity_at<'a>(
+        &'a self,
+        light: Light,
+        point: Vector,
+        time: f64,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> f64 {
+        self.intensity_ignoring(light, point, time, None, ALL_LAYERS, intersections)
+    }
+
+    // `ignore` never blocks the light, e.g. the shape being shaded itself
+    fn intensity_ignoring<'a>(
+        &'a self,
+        light: Light,
+        point: Vector,
+        time: f64,
+        ignore: Option<&Shape>,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> f64 {
+        if self.settings.soft_shadow_radius > 0.0 && !matches!(light, Light::Area(_)) {
+            return 1.0 - self.occlusion(light.origin(), point, time, ignore, mask, intersections);
+        }
+
+        let samples = light.samples();
+        let lit = (0..samples)
+            .filter(|index| {
+                !self.is_shadowed(
+                    light.sample(*index),
+                    point,
+                    time,
+                    ignore,
+                    mask,
+                    intersections,
+                )
+            })
+            .count();
+
+        lit as f64 / samples as f64
+    }
+
+    // the cone from the point to the light disk has radius r at the occluder; how far
+    // the chord through the occluder fills 2r estimates the blocked share of the disk,
+    // so occluders close to the point cast sharp edges and those close to the light soft ones
+    fn occlusion<'a>(
+        &'a self,
+        origin: Vector,
+        point: Vector,
+        time: f64,
+        ignore: Option<&Shape>,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> f64 {
+        let vector = origin - point;
         let distance = vector.magnitude();
 
         let ray = Ray {
             origin: point,
             direction: vector.normalize(),
+            differentials: None,
+            time,
         };
 
-        self.intersect(ray, intersections);
+        self.intersect_bounded(ray, distance, mask, intersections);
         Intersection::sort(intersections);
 
-        if let Some(hit) = Intersection::hit(intersections) {
-            hit.shape.casts_shadow && hit.t < distance
-        } else {
-            false
+        let blocking = |intersection: &&Intersection| {
+            intersection.shape.casts_shadow
+                && ignore != Some(intersection.shape)
+                && intersection.t >= 0.0
+                && intersection.t < distance
+        };
+        let entry = match intersections.iter().find(blocking) {
+            Some(entry) => *entry,
+            None => return 0.0,
+        };
+        let exit = intersections
+            .iter()
+            .filter(|intersection| intersection.t > entry.t && intersection.shape == entry.shape)
+            .map(|intersection| intersection.t.min(distance))
+            .next();
+
+        match exit {
+            Some(exit) => {
+                let radius = self.settings.soft_shadow_radius * (entry.t + exit) / 2.0 / distance;
+                ((exit - entry.t) / (2.0 * radius)).min(1.0)
+            }
+            // an open surface has no depth to measure
+            None => 1.0,
+        }
+    }
+
+    fn caustic_transmission<'a>(
+        &'a self,
+        light: Light,
+        point: Vector,
+        time: f64,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> f64 {
+        let vector = light.origin() - point;
+        let direction = vector.normalize();
+
+        let ray = Ray {
+            origin: point,
+            direction,
+            differentials: None,
+            time,
+        };
+
+        self.intersect_bounded(ray, vector.magnitude(), mask, intersections);
+        Intersection::sort(intersections);
+
+        let mut transmission = 1.0;
+        let mut entered: Vec<&Shape> = vec![];
+
+        for intersection in intersections.iter().filter(|i| i.t > 0.0) {
+            let shape = intersection.shape;
+            if !shape.casts_shadow || entered.contains(&shape) {
+                continue;
+            }
+            if shape.material.transparency == 0.0 {
+                return 0.0;
+            }
+            entered.push(shape);
+
+            // light entering a curved refractive surface head-on converges behind it
+            let normal = intersection.normal(ray.position(intersection.t), time);
+            let focus = (shape.material.refractive_index - 1.0) * normal.dot(direction).abs();
+
+            transmission *= shape.material.transparency * (1.0 + focus);
+        }
+
+        transmission
+    }
+
+    fn shadow_fraction<'a>(
+        &'a self,
+        point: Vector,
+        time: f64,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> f64 {
+        if self.lights.is_empty() {
+            return 0.0;
         }
+
+        let lit: f64 = self
+            .lights
+            .iter()
+            .map(|light| self.intensity_ignoring(*light, point, time, None, mask, intersections))
+            .sum();
+
+        1.0 - lit / self.lights.len() as f64
     }
 
     fn shade_hit<'a>(
         &'a self,
         state: &State,
         fuel: i32,
+        mask: u32,
         intersections: &mut Vec<Intersection<'a>>,
     ) -> Color {
+        if state.shape.material.shadow_catcher {
+            let lit = 1.0 - self.shadow_fraction(state.over_point, state.time, mask, intersections);
+            let through_ray = Ray {
+                origin: state.under_point,
+                direction: -state.eye,
+                differentials: None,
+                time: state.time,
+            };
+
+            // the composited backdrop counts against `max_depth` like any other secondary ray
+            let behind = if fuel <= 0 {
+                self.background.color_at(through_ray.direction)
+            } else {
+                self.color_at_layer(through_ray, fuel - 1, mask, intersections)
+            };
+
+            return behind * lit;
+        }
+
+        let material = state.material();
         let mut color = Color::black();
 
         for light in &self.lights {
-            let shadowed = self.is_shadowed(*light, state.over_point, intersections);
-
-            let surface_color =
-                state
-                    .shape
-                    .lighting(*light, state.over_point, state.eye, state.normal, shadowed);
+            // past the terminator the shadow ray runs through the surface itself,
+            // which would cancel the diffuse term that wrap lighting bleeds there
+            let light_dot_normal = (light.origin() - state.over_point)
+                .normalize()
+                .dot(state.normal);
+            let ignore = (material.wrap > 0.0 && light_dot_normal < 0.0).then_some(state.shape);
+            let intensity = self.intensity_ignoring(
+                *light,
+                state.over_point,
+                state.time,
+                ignore,
+                mask,
+                intersections,
+            );
+
+            let mut surface_color = state.shape.lighting_in(
+                material,
+                *light,
+                state.over_point,
+                state.pattern_point(),
+                state.eye,
+                state.normal,
+                intensity,
+            );
+
+            if intensity < 1.0 && self.settings.caustics {
+                let transmission = self.caustic_transmission(
+                    *light,
+                    state.over_point,
+                    state.time,
+                    mask,
+                    intersections,
+                );
+                let lit = state.shape.lighting_in(
+                    material,
+                    *light,
+                    state.over_point,
+                    state.pattern_point(),
+                    state.eye,
+                    state.normal,
+                    1.0,
+                );
+
+                surface_color = surface_color + (lit - surface_color) * transmission;
+            }
 
-            let reflected_color = self.reflected_color(state, fuel, intersections);
+            let reflected_color = self.reflected_color(state, fuel, mask, intersections);
 
-            let refracted_color = self.refracted_color(state, fuel, intersections);
+            let refracted_color = self.refracted_color(state, fuel, mask, intersections);
 
             color += surface_color
-                + if state.shape.material.reflective > 0.0
-                    && state.shape.material.transparency > 0.0
-                {
+                + if material.transparency > 0.0 {
                     reflected_color * state.reflectance
                         + refracted_color * (1.0 - state.reflectance)
                 } else {
@@ -78,6 +422,27 @@ impl World {
                 }
         }
 
+        // emission is added once per hit, independent of the lights and their shadows
+        color += material.emission;
+
+        // unlike refraction the ray just carries on without bending
+        if material.opacity < 1.0 {
+            let through_ray = Ray {
+                origin: state.under_point,
+                direction: -state.eye,
+                differentials: None,
+                time: state.time,
+            };
+            // like every other secondary ray the continuation counts against `max_depth`
+            let behind = if fuel <= 0 {
+                self.background.color_at(through_ray.direction)
+            } else {
+                self.color_at_layer(through_ray, fuel - 1, mask, intersections)
+            };
+
+            color = color * material.opacity + behind * (1.0 - material.opacity);
+        }
+
         color
     }
 
@@ -85,50 +450,257 @@ impl World {
         &'a self,
         state: &State,
         fuel: i32,
+        mask: u32,
         intersections: &mut Vec<Intersection<'a>>,
     ) -> Color {
-        if fuel <= 0 || state.shape.material.reflective == 0.0 {
+        let material = state.material();
+
+        if fuel <= 0 || !self.settings.enable_reflections || material.reflective == 0.0 {
             Color::black()
+        } else if let Some(map) = &material.reflection_map {
+            let reflect = state.reflect;
+            let (u, v) = UvMapping::Spherical.uv(Vector::point(reflect.x, reflect.y, reflect.z));
+
+            map.sample(u, v) * material.reflective
+        } else if material.roughness > 0.0 && self.settings.glossy_samples > 0 {
+            self.glossy_color(state, material.roughness, fuel, mask, intersections)
+                * material.reflective
         } else {
             let reflect_ray = Ray {
                 origin: state.over_point,
                 direction: state.reflect,
+                differentials: None,
+                time: state.time,
+            };
+
+            let color = self.color_at_layer(reflect_ray, fuel - 1, mask, intersections);
+
+            color * material.reflective
+        }
+    }
+
+    // the lobe is uniform over a cone around the mirror direction, area lights
+    // are visible in it so that glossy surfaces pick up their highlights
+    fn glossy_color<'a>(
+        &'a self,
+        state: &State,
+        roughness: f64,
+        fuel: i32,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> Color {
+        let area_lights: Vec<&AreaLight> = self
+            .lights
+            .iter()
+            .filter_map(|light| match light {
+                Light::Area(light) => Some(light),
+                _ => None,
+            })
+            .collect();
+
+        let samples = self.settings.glossy_samples;
+        let light_samples = if area_lights.is_empty() {
+            0
+        } else {
+            ((samples as f64 * self.settings.light_bias).round() as usize).min(samples)
+        };
+        let cone_samples = samples - light_samples;
+
+        let axis = state.reflect.normalize();
+        let cos_max = (roughness.min(1.0) * PI / 2.0).cos();
+        let solid_angle = 2.0 * PI * (1.0 - cos_max);
+
+        let ray = |direction: Vector| Ray {
+            origin: state.over_point,
+            direction,
+            differentials: None,
+            time: state.time,
+        };
+
+        // balance heuristic over both strategies, see Veach's multi-sample estimator
+        let weight = |direction: Vector| {
+            if direction.dot(axis) < cos_max || direction.dot(state.normal) <= 0.0 {
+                return 0.0;
+            }
+
+            let light_pdf = area_lights
+                .iter()
+                .filter_map(|light| {
+                    light
+                        .intersect(ray(direction))
+                        .map(|t| t * t / (light.area() * light.normal().dot(direction).abs()))
+                })
+                .sum::<f64>()
+                / area_lights.len().max(1) as f64;
+
+            (1.0 / solid_angle)
+                / (cone_samples as f64 / solid_angle + light_samples as f64 * light_pdf)
+        };
+
+        let (u, v) = {
+            let helper = if axis.x.abs() > 0.9 {
+                Vector::vector(0.0, 1.0, 0.0)
+            } else {
+                Vector::vector(1.0, 0.0, 0.0)
             };
+            let u = axis.cross(helper).normalize();
+            (u, axis.cross(u))
+        };
+
+        let cone = (0..cone_samples).map(|sample| {
+            let z = 1.0 - (sample as f64 + 0.5) / cone_samples as f64 * (1.0 - cos_max);
+            let r = (1.0 - z * z).sqrt();
+            let angle = sample as f64 * PI * (3.0 - 5.0_f64.sqrt());
+
+            u * (r * angle.cos()) + v * (r * angle.sin()) + axis * z
+        });
+        let toward_lights = (0..light_samples).map(|sample| {
+            let light = Light::Area(*area_lights[sample % area_lights.len()]);
+            let target = light.sample(sample / area_lights.len() % light.samples());
+
+            (target - state.over_point).normalize()
+        });
+
+        let mut color = Color::black();
+        for direction in cone.chain(toward_lights).collect::<Vec<Vector>>() {
+            let weight = weight(direction);
+            if weight > 0.0 {
+                color +=
+                    self.radiance(ray(direction), &area_lights, fuel, mask, intersections) * weight;
+            }
+        }
 
-            let color = self.color_at(reflect_ray, fuel - 1, intersections);
+        color
+    }
 
-            color * state.shape.material.reflective
+    fn radiance<'a>(
+        &'a self,
+        ray: Ray,
+        area_lights: &[&AreaLight],
+        fuel: i32,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> Color {
+        let emitter = area_lights
+            .iter()
+            .filter_map(|light| light.intersect(ray).map(|t| (t, light.intensity)))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        if let Some((t, intensity)) = emitter {
+            self.intersect_bounded(ray, t, mask, intersections);
+            if !intersections.iter().any(|i| i.t >= 0.0 && i.t < t) {
+                return intensity;
+            }
         }
+
+        self.color_at_layer(ray, fuel - 1, mask, intersections)
     }
 
     fn refracted_color<'a>(
         &'a self,
         state: &State,
         fuel: i32,
+        mask: u32,
         intersections: &mut Vec<Intersection<'a>>,
     ) -> Color {
-        if fuel <= 0 || state.shape.material.transparency == 0.0 {
+        let material = state.material();
+
+        if fuel <= 0 || !self.settings.enable_refractions || material.transparency == 0.0 {
             Color::black()
+        } else if material.dispersion > 0.0 {
+            // shift this material's index per channel, red bending the least
+            let indices = |offset: f64| {
+                let index = material.refractive_index + offset * material.dispersion;
+                if state.inside {
+                    (index, state.n2)
+                } else {
+                    (state.n1, index)
+                }
+            };
+
+            let r = self
+                .refract(state, indices(-1.0), fuel, mask, intersections)
+                .r;
+            let g = self
+                .refract(state, indices(0.0), fuel, mask, intersections)
+                .g;
+            let b = self
+                .refract(state, indices(1.0), fuel, mask, intersections)
+                .b;
+
+            Color::new(r, g, b) * material.transparency
         } else {
-            let n_ratio = state.n1 / state.n2;
-            let cos_i = state.eye.dot(state.normal);
-            let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+            self.refract(state, (state.n1, state.n2), fuel, mask, intersections)
+                * material.transparency
+        }
+    }
 
-            if sin2_t > 1.0 {
-                Color::black()
-            } else {
-                let cos_t = (1.0 - sin2_t).sqrt();
-                let direction = state.normal * (n_ratio * cos_i - cos_t) - state.eye * n_ratio;
+    fn refract<'a>(
+        &'a self,
+        state: &State,
+        (n1, n2): (f64, f64),
+        fuel: i32,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> Color {
+        let n_ratio = n1 / n2;
+        let cos_i = state.eye.dot(state.normal);
+        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
 
-                let refract_ray = Ray {
-                    origin: state.under_point,
-                    direction,
-                };
+        if sin2_t > 1.0 {
+            Color::black()
+        } else {
+            let cos_t = (1.0 - sin2_t).sqrt();
+            let direction = state.normal * (n_ratio * cos_i - cos_t) - state.eye * n_ratio;
+
+            let refract_ray = Ray {
+                origin: state.under_point,
+                direction,
+                differentials: None,
+                time: state.time,
+            };
+
+            self.color_at_layer(refract_ray, fuel - 1, mask, intersections)
+        }
+    }
 
-                self.color_at(refract_ray, fuel - 1, intersections)
-                    * state.shape.material.transparency
+    pub fn validate(&self) -> Result<(), Vec<ValidationWarning>> {
+        let mut warnings = vec![];
+
+        for (index, light) in self.lights.iter().enumerate() {
+            let origin = light.origin();
+            let intensity = light.intensity();
+            if ![
+                origin.x,
+                origin.y,
+                origin.z,
+                intensity.r,
+                intensity.g,
+                intensity.b,
+            ]
+            .iter()
+            .all(|x| x.is_finite())
+            {
+                warnings.push(ValidationWarning::NonFiniteLight { light: index });
             }
         }
+
+        for element in &self.elements {
+            validate_element(element, &mut warnings);
+        }
+
+        if warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(warnings)
+        }
+    }
+
+    pub fn closest_surface(&self, point: Vector) -> Option<(f64, &Shape)> {
+        self.elements
+            .iter()
+            .filter_map(|element| element.closest_surface(point))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
     }
 
     pub fn color_at<'a>(
@@ -137,24 +709,228 @@ impl World {
         fuel: i32,
         intersections: &mut Vec<Intersection<'a>>,
     ) -> Color {
-        self.intersect(ray, intersections);
+        self.color_at_layer(ray, fuel, ALL_LAYERS, intersections)
+    }
+
+    pub fn render_scanline<'a>(
+        &'a self,
+        camera: &Camera,
+        y: usize,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> Vec<Color> {
+        (0..camera.hsize)
+            .map(|x| self.render_pixel(camera, x, y, intersections))
+            .collect()
+    }
+
+    pub fn render_pixel<'a>(
+        &'a self,
+        camera: &Camera,
+        x: usize,
+        y: usize,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> Color {
+        let colors: Vec<Color> = (0..camera.samples())
+            .map(|sample| {
+                let ray = camera.ray_at_lens(x, y, sample);
+                self.color_at(ray, self.settings.max_depth, intersections)
+            })
+            .collect();
+
+        Color::average(&colors)
+    }
+
+    // an equirectangular capture of everything visible from `center`, for `reflection_map`,
+    // so it should be baked before adding the objects that are going to use it
+    // texels sit on the corners of the uv grid, to match the bilinear lookup of `ImageTexture`
+    pub fn bake_environment(&self, center: Vector, resolution: usize) -> Image {
+        assert!(resolution >= 2);
+
+        let (width, height) = (2 * resolution, resolution);
+
+        let pixels: Vec<Color> = (0..(width * height))
+            .into_par_iter()
+            .map(|i| {
+                let u = (i % width) as f64 / (width - 1) as f64;
+                let v = 1.0 - (i / width) as f64 / (height - 1) as f64;
+                let ray = Ray {
+                    origin: center,
+                    direction: UvMapping::spherical_direction(u, v),
+                    differentials: None,
+                    time: 0.0,
+                };
+
+                self.color_at(ray, self.settings.max_depth, &mut vec![])
+            })
+            .collect();
+
+        let mut image = Image::new(width, height);
+        for (i, color) in pixels.into_iter().enumerate() {
+            image.write(i % width, i / width, color);
+        }
+
+        image
+    }
+
+    pub fn render_passes(&self, camera: &Camera) -> Passes {
+        let pixels: Vec<(Color, f64, f64, Option<Vector>)> = (0..(camera.hsize * camera.vsize))
+            .into_par_iter()
+            .map(|i| self.passes_at(camera, i % camera.hsize, i / camera.hsize, &mut vec![]))
+            .collect();
+
+        let mut passes = Passes {
+            color: Image::new(camera.hsize, camera.vsize),
+            alpha: Image::new(camera.hsize, camera.vsize),
+            depth: DepthImage::new(camera.hsize, camera.vsize),
+            normal: Image::new(camera.hsize, camera.vsize),
+        };
+
+        for (i, (color, alpha, depth, normal)) in pixels.into_iter().enumerate() {
+            let (x, y) = (i % camera.hsize, i / camera.hsize);
+
+            passes.color.write(x, y, color);
+            passes.alpha.write(x, y, Color::new(alpha, alpha, alpha));
+            passes.depth.write(x, y, depth);
+            passes.normal.write(
+                x,
+                y,
+                normal
+                    .map(|n| Color::new(n.x + 1.0, n.y + 1.0, n.z + 1.0) * 0.5)
+                    .unwrap_or_else(Color::black),
+            );
+        }
+
+        passes
+    }
+
+    // the first lens sample feeds every pass, the others only the color
+    fn passes_at<'a>(
+        &'a self,
+        camera: &Camera,
+        x: usize,
+        y: usize,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> (Color, f64, f64, Option<Vector>) {
+        let ray = camera.ray_at_lens(x, y, 0);
+        self.intersect(ray, ALL_LAYERS, intersections);
+        Intersection::sort(intersections);
+
+        let (color, alpha, depth, normal) = if let Some(hit) = Intersection::hit(intersections) {
+            let state = hit.prepare_state_with(ray, intersections, self.settings.epsilon);
+            let normal = hit.normal(ray.position(hit.t), ray.time);
+            let alpha = self.alpha_hit(&state, intersections);
+            let color = self.shade_hit(&state, self.settings.max_depth, ALL_LAYERS, intersections);
+
+            (color, alpha, hit.t, Some(normal))
+        } else {
+            (
+                self.background.color_at(ray.direction),
+                0.0,
+                f64::INFINITY,
+                None,
+            )
+        };
+
+        let mut colors = vec![color];
+        for sample in 1..camera.samples() {
+            let ray = camera.ray_at_lens(x, y, sample);
+            colors.push(self.color_at(ray, self.settings.max_depth, intersections));
+        }
+
+        (Color::average(&colors), alpha, depth, normal)
+    }
+
+    pub fn color_at_layer<'a>(
+        &'a self,
+        ray: Ray,
+        fuel: i32,
+        mask: u32,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> Color {
+        self.intersect(ray, mask, intersections);
         Intersection::sort(intersections);
 
         if let Some(hit) = Intersection::hit(intersections) {
-            let state = hit.prepare_state(ray, intersections);
-            self.shade_hit(&state, fuel, intersections)
+            let state = hit.prepare_state_with(ray, intersections, self.settings.epsilon);
+            self.shade_hit(&state, fuel, mask, intersections)
         } else {
-            Color::black()
+            self.background.color_at(ray.direction)
+        }
+    }
+
+    pub fn normal_at<'a>(
+        &'a self,
+        ray: Ray,
+        space: NormalSpace,
+        intersections: &mut Vec<Intersection<'a>>,
+    ) -> Option<Vector> {
+        self.intersect(ray, ALL_LAYERS, intersections);
+        Intersection::sort(intersections);
+
+        Intersection::hit(intersections).map(|hit| {
+            let point = ray.position(hit.t);
+
+            match space {
+                NormalSpace::World => hit.normal(point, ray.time),
+                NormalSpace::Object => {
+                    let point = hit.frame.map_or(point, |frame| frame * point);
+                    let point = hit.shape.at_rest(point, ray.time);
+                    let shape_point = hit.shape.transform_inv * point;
+                    let mut normal = hit.shape.geometry.normal(shape_point, hit.u, hit.v);
+                    normal.w = 0.0;
+
+                    normal.normalize()
+                }
+            }
+        })
+    }
+
+    pub fn depth_at<'a>(&'a self, ray: Ray, intersections: &mut Vec<Intersection<'a>>) -> f64 {
+        self.intersect(ray, ALL_LAYERS, intersections);
+        Intersection::sort(intersections);
+
+        Intersection::hit(intersections)
+            .map(|hit| hit.t)
+            .unwrap_or(f64::INFINITY)
+    }
+
+    pub fn alpha_at<'a>(&'a self, ray: Ray, intersections: &mut Vec<Intersection<'a>>) -> f64 {
+        self.intersect(ray, ALL_LAYERS, intersections);
+        Intersection::sort(intersections);
+
+        if let Some(hit) = Intersection::hit(intersections) {
+            let state = hit.prepare_state_with(ray, intersections, self.settings.epsilon);
+            self.alpha_hit(&state, intersections)
+        } else {
+            0.0
+        }
+    }
+
+    fn alpha_hit<'a>(&'a self, state: &State, intersections: &mut Vec<Intersection<'a>>) -> f64 {
+        if state.shape.material.shadow_catcher {
+            let shadow =
+                self.shadow_fraction(state.over_point, state.time, ALL_LAYERS, intersections);
+            let through_ray = Ray {
+                origin: state.under_point,
+                direction: -state.eye,
+                differentials: None,
+                time: state.time,
+            };
+            let behind = self.alpha_at(through_ray, intersections);
+
+            behind + (1.0 - behind) * shadow
+        } else {
+            1.0
         }
     }
 }
 
 impl Default for World {
     fn default() -> Self {
-        let light = PointLight {
+        let light = Light::Point(PointLight {
             intensity: Color::white(),
             origin: Vector::point(-10.0, 10.0, -10.0),
-        };
+        });
 
         let sphere1 = Element::sphere(ShapeArgs {
             material: Material {
@@ -178,23 +954,29 @@ impl Default for World {
         World {
             lights: vec![light],
             elements: vec![sphere1, sphere2],
+            clip_plane: None,
+            background: Background::Solid(Color::black()),
+            settings: Settings::default(),
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unused_unit)]
 mod tests {
     use super::*;
 
     use crate::approx::Approx;
-    use crate::config::FUEL;
+    use crate::config::{EPSILON, FUEL};
     use crate::intersection::Intersection;
-    use crate::shape::Shape;
+    use crate::shape::GroupKind;
+
+    use test_case::test_case;
 
     fn shape(element: &Element) -> &Shape {
         match element {
-            Element::Composite(_) => panic!("Expected primitive shape, found group."),
             Element::Primitive(shape) => shape,
+            _ => panic!("Expected primitive shape, found group."),
         }
     }
 
@@ -204,10 +986,12 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let mut is = vec![];
-        world.intersect(ray, &mut is);
+        world.intersect(ray, ALL_LAYERS, &mut is);
         Intersection::sort(&mut is);
 
         assert!(
@@ -225,6 +1009,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let sphere = &world.elements[0];
@@ -234,11 +1020,12 @@ mod tests {
             shape: shape(sphere),
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = i.prepare_state(ray, &vec![]);
 
-        let color = world.shade_hit(&state, FUEL, &mut vec![]);
+        let color = world.shade_hit(&state, FUEL, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::new(0.38066, 0.47583, 0.28550,)))
     }
@@ -246,16 +1033,18 @@ mod tests {
     #[test]
     fn shade_intersection_inside() {
         let world = World {
-            lights: vec![PointLight {
+            lights: vec![Light::Point(PointLight {
                 intensity: Color::white(),
                 origin: Vector::point(0.0, 0.25, 0.0),
-            }],
+            })],
             ..World::default()
         };
 
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let sphere = &world.elements[1];
@@ -265,11 +1054,12 @@ mod tests {
             shape: shape(sphere),
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = i.prepare_state(ray, &vec![]);
 
-        let color = world.shade_hit(&state, FUEL, &mut vec![]);
+        let color = world.shade_hit(&state, FUEL, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::new(0.90498, 0.90498, 0.90498,)))
     }
@@ -281,6 +1071,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 1.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let color = world.color_at(ray, FUEL, &mut vec![]);
@@ -295,6 +1087,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let color = world.color_at(ray, FUEL, &mut vec![]);
@@ -335,6 +1129,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.75),
             direction: Vector::vector(0.0, 0.0, -1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let color = world.color_at(ray, FUEL, &mut vec![]);
@@ -342,12 +1138,51 @@ mod tests {
         assert!(color.approx(&Color::white()))
     }
 
-    #[test]
+    #[test_case(Vector::point(0.0 ,   0.0, -2.0 ), 1.0 ; "lit")]
+    #[test_case(Vector::point(10.0, -10.0, 10.0), 0.0 ; "umbra")]
+    fn intensity_at_point_light(point: Vector, expected: f64) {
+        let world = World::default();
+
+        assert!(world
+            .intensity_at(world.lights[0], point, 0.0, &mut vec![])
+            .approx(&expected))
+    }
+
+    #[test_case(Vector::point(0.0 , 0.0 , -2.0), 1.0 , 1.0  ; "lit")]
+    #[test_case(Vector::point(0.0 , 0.0 ,  2.0), 0.0 , 0.0  ; "umbra")]
+    #[test_case(Vector::point(1.5 , 0.0 ,  2.0), 0.25, 0.75 ; "penumbra")]
+    #[test_case(Vector::point(1.25, 1.25,  3.0), 0.25, 1.0  ; "outer penumbra")]
+    fn intensity_at_area_light(point: Vector, min: f64, max: f64) {
+        let world = World {
+            lights: vec![Light::Area(AreaLight {
+                intensity: Color::white(),
+                origin: Vector::point(-0.5, -0.5, -5.0),
+                uvec: Vector::vector(1.0, 0.0, 0.0),
+                vvec: Vector::vector(0.0, 1.0, 0.0),
+                usteps: 2,
+                vsteps: 2,
+            })],
+            ..World::default()
+        };
+
+        let intensity = world.intensity_at(world.lights[0], point, 0.0, &mut vec![]);
+
+        assert!(min <= intensity && intensity <= max)
+    }
+
+    #[test]
     fn shadow_nothing_collinar_point_light() {
         let world = World::default();
         let point = Vector::point(0.0, 10.0, 0.0);
 
-        assert!(!world.is_shadowed(world.lights[0], point, &mut vec![]));
+        assert!(!world.is_shadowed(
+            world.lights[0].origin(),
+            point,
+            0.0,
+            None,
+            ALL_LAYERS,
+            &mut vec![]
+        ));
     }
 
     #[test]
@@ -355,7 +1190,14 @@ mod tests {
         let world = World::default();
         let point = Vector::point(10.0, -10.0, 10.0);
 
-        assert!(world.is_shadowed(world.lights[0], point, &mut vec![]));
+        assert!(world.is_shadowed(
+            world.lights[0].origin(),
+            point,
+            0.0,
+            None,
+            ALL_LAYERS,
+            &mut vec![]
+        ));
     }
 
     #[test]
@@ -363,7 +1205,14 @@ mod tests {
         let world = World::default();
         let point = Vector::point(-20.0, 20.0, -20.0);
 
-        assert!(!world.is_shadowed(world.lights[0], point, &mut vec![]));
+        assert!(!world.is_shadowed(
+            world.lights[0].origin(),
+            point,
+            0.0,
+            None,
+            ALL_LAYERS,
+            &mut vec![]
+        ));
     }
 
     #[test]
@@ -371,16 +1220,70 @@ mod tests {
         let world = World::default();
         let point = Vector::point(-2.0, 2.0, -2.0);
 
-        assert!(!world.is_shadowed(world.lights[0], point, &mut vec![]));
+        assert!(!world.is_shadowed(
+            world.lights[0].origin(),
+            point,
+            0.0,
+            None,
+            ALL_LAYERS,
+            &mut vec![]
+        ));
+    }
+
+    #[test]
+    fn shadow_grazing_plane() {
+        let world = World {
+            elements: vec![Element::plane(ShapeArgs::default())],
+            ..World::default()
+        };
+        let light = Vector::point(10.0, 0.1, 0.0);
+        let point = Vector::point(0.0, 0.0, 0.0);
+
+        assert!(!world.is_shadowed(light, point, 0.0, None, ALL_LAYERS, &mut vec![]));
+    }
+
+    #[test]
+    fn soft_shadow_contact_hardening() {
+        // share of the shadowed points along the floor that are only partially shadowed
+        let penumbra_share = |height: f64| {
+            let world = World {
+                lights: vec![Light::Point(PointLight {
+                    intensity: Color::white(),
+                    origin: Vector::point(0.0, 10.0, 0.0),
+                })],
+                elements: vec![Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(0.0, height, 0.0)
+                        * Matrix::scaling(0.5, 0.5, 0.5),
+                    ..ShapeArgs::default()
+                })],
+                settings: Settings {
+                    soft_shadow_radius: 1.0,
+                    ..Settings::default()
+                },
+                ..World::default()
+            };
+            let intensities: Vec<f64> = (0..400)
+                .map(|i| {
+                    let point = Vector::point(i as f64 * 0.01, 0.0, 0.0);
+                    world.intensity_at(world.lights[0], point, 0.0, &mut vec![])
+                })
+                .collect();
+            let shadowed = intensities.iter().filter(|&&i| i < 1.0).count();
+            let penumbra = intensities.iter().filter(|&&i| i > 0.0 && i < 1.0).count();
+
+            penumbra as f64 / shadowed as f64
+        };
+
+        assert!(penumbra_share(1.0) < 0.1 && penumbra_share(8.0) > 0.5)
     }
 
     #[test]
     fn color_intersection_in_shadow() {
         let world = World {
-            lights: vec![PointLight {
+            lights: vec![Light::Point(PointLight {
                 origin: Vector::point(0.0, 0.0, -10.0),
                 intensity: Color::white(),
-            }],
+            })],
             elements: vec![
                 Element::sphere(ShapeArgs::default()),
                 Element::sphere(ShapeArgs {
@@ -388,11 +1291,14 @@ mod tests {
                     ..ShapeArgs::default()
                 }),
             ],
+            ..World::default()
         };
 
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let intersection = Intersection {
@@ -403,13 +1309,95 @@ mod tests {
             }),
             u: None,
             v: None,
+            frame: None,
         };
 
-        let color = world.shade_hit(&intersection.prepare_state(ray, &vec![]), FUEL, &mut vec![]);
+        let color = world.shade_hit(
+            &intersection.prepare_state(ray, &vec![]),
+            FUEL,
+            ALL_LAYERS,
+            &mut vec![],
+        );
 
         assert!(color.approx(&Color::new(0.1, 0.1, 0.1,)))
     }
 
+    #[test]
+    fn emission_in_shadow() {
+        let world = World {
+            lights: vec![Light::Point(PointLight {
+                origin: Vector::point(0.0, 0.0, -10.0),
+                intensity: Color::white(),
+            })],
+            elements: vec![Element::sphere(ShapeArgs::default())],
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, 5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let intersection = Intersection {
+            t: 4.0,
+            shape: &Shape::sphere(ShapeArgs {
+                transform: Matrix::translation(0.0, 0.0, 10.0),
+                material: Material {
+                    emission: Color::new(0.5, 0.2, 0.0),
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            }),
+            u: None,
+            v: None,
+            frame: None,
+        };
+
+        let color = world.shade_hit(
+            &intersection.prepare_state(ray, &vec![]),
+            FUEL,
+            ALL_LAYERS,
+            &mut vec![],
+        );
+
+        // ambient plus emission, the light itself is blocked
+        assert!(color.approx(&Color::new(0.6, 0.3, 0.1)))
+    }
+
+    #[test_case(0.0, false ; "no wrap"  )]
+    #[test_case(0.5, true  ; "half wrap")]
+    #[test_case(1.0, true  ; "full wrap")]
+    fn wrap_past_terminator(wrap: f64, lit: bool) {
+        let world = World {
+            lights: vec![Light::Point(PointLight {
+                origin: Vector::point(10.0, 0.0, 0.0),
+                intensity: Color::white(),
+            })],
+            elements: vec![Element::sphere(ShapeArgs {
+                material: Material {
+                    wrap,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            })],
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(-0.2, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let color = world.color_at(ray, FUEL, &mut vec![]);
+
+        // anything above the ambient term is light bled past the terminator
+        assert_eq!(color.r > 0.1 + EPSILON, lit)
+    }
+
     #[test]
     fn reflected_color_nonreflective_materiall() {
         let sphere1 = Element::sphere(ShapeArgs {
@@ -443,6 +1431,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let intersection = Intersection {
@@ -450,11 +1440,12 @@ mod tests {
             shape: shape(&world.elements[1]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = intersection.prepare_state(ray, &vec![]);
 
-        let color = world.reflected_color(&state, FUEL, &mut vec![]);
+        let color = world.reflected_color(&state, FUEL, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::black()))
     }
@@ -477,6 +1468,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -3.0),
             direction: Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / 2.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let intersection = Intersection {
@@ -484,11 +1477,12 @@ mod tests {
             shape: shape(&world.elements[2]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = intersection.prepare_state(ray, &vec![]);
 
-        let color = world.reflected_color(&state, FUEL, &mut vec![]);
+        let color = world.reflected_color(&state, FUEL, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::new(0.190332, 0.237915, 0.142749,)))
     }
@@ -511,6 +1505,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -3.0),
             direction: Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / 2.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let intersection = Intersection {
@@ -518,21 +1514,105 @@ mod tests {
             shape: shape(&world.elements[2]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = intersection.prepare_state(ray, &vec![]);
 
-        let color = world.shade_hit(&state, FUEL, &mut vec![]);
+        let color = world.shade_hit(&state, FUEL, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::new(0.876757, 0.924340, 0.829174,)))
     }
 
+    #[test]
+    fn disabled_reflections() {
+        let world = |reflective: f64, enable_reflections: bool| {
+            let mut world = World {
+                settings: Settings {
+                    enable_reflections,
+                    ..Settings::default()
+                },
+                ..World::default()
+            };
+            world.elements.push(Element::plane(ShapeArgs {
+                transform: Matrix::translation(0.0, -1.0, 0.0),
+                material: Material {
+                    reflective,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            }));
+            world
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -3.0),
+            direction: Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / 2.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let mirror = world(1.0, true).color_at(ray, FUEL, &mut vec![]);
+        let disabled = world(1.0, false).color_at(ray, FUEL, &mut vec![]);
+        let flat = world(0.0, true).color_at(ray, FUEL, &mut vec![]);
+
+        assert!(disabled.approx(&flat) && !mirror.approx(&flat))
+    }
+
+    #[test]
+    fn glossy_light_bias() {
+        let world = |glossy_samples: usize, light_bias: f64| World {
+            lights: vec![Light::Area(AreaLight {
+                intensity: Color::white(),
+                origin: Vector::point(-0.5, 4.0, 7.5),
+                uvec: Vector::vector(1.0, 0.0, 0.0),
+                vvec: Vector::vector(0.0, 0.0, 1.0),
+                usteps: 4,
+                vsteps: 4,
+            })],
+            elements: vec![Element::plane(ShapeArgs {
+                material: Material {
+                    ambient: 0.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                    reflective: 1.0,
+                    roughness: 0.1,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            })],
+            settings: Settings {
+                glossy_samples,
+                light_bias,
+                ..Settings::default()
+            },
+            ..World::default()
+        };
+
+        // the mirror direction points at the center of the light
+        let ray = Ray {
+            origin: Vector::point(0.0, 1.0, -2.0),
+            direction: Vector::vector(0.0, -1.0, 2.0).normalize(),
+            differentials: None,
+            time: 0.0,
+        };
+        let highlight = |world: World| world.color_at(ray, FUEL, &mut vec![]).g;
+
+        let reference = highlight(world(20000, 0.0));
+        let uniform = highlight(world(16, 0.0));
+        let biased = highlight(world(16, 0.5));
+        assert!(
+            (biased - reference).abs() < (uniform - reference).abs()
+                && (biased - reference).abs() < 0.1 * reference
+        )
+    }
+
     #[test]
     fn color_at_mutually_reflective_surfaces() {
-        let light = PointLight {
+        let light = Light::Point(PointLight {
             origin: Vector::point(0.0, 0.0, 0.0),
             intensity: Color::white(),
-        };
+        });
 
         let lower_plane = Element::plane(ShapeArgs {
             transform: Matrix::translation(0.0, -1.0, 0.0),
@@ -555,11 +1635,14 @@ mod tests {
         let world = World {
             lights: vec![light],
             elements: vec![lower_plane, upper_plane],
+            ..World::default()
         };
 
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.0),
             direction: Vector::vector(0.0, 1.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let result = std::panic::catch_unwind(|| world.color_at(ray, FUEL, &mut vec![]));
@@ -585,6 +1668,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -3.0),
             direction: Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / 2.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let intersection = Intersection {
@@ -592,11 +1677,12 @@ mod tests {
             shape: shape(&world.elements[2]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let state = intersection.prepare_state(ray, &vec![]);
 
-        let color = world.reflected_color(&state, 0, &mut vec![]);
+        let color = world.reflected_color(&state, 0, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::black()))
     }
@@ -610,6 +1696,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -617,6 +1705,7 @@ mod tests {
             shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -624,13 +1713,14 @@ mod tests {
             shape,
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1, i2];
 
         let state = i1.prepare_state(ray, &is);
 
-        let color = world.refracted_color(&state, 5, &mut vec![]);
+        let color = world.refracted_color(&state, 5, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::black()))
     }
@@ -647,6 +1737,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -5.0),
             direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -654,6 +1746,7 @@ mod tests {
             shape: shape(&world.elements[0]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -661,13 +1754,14 @@ mod tests {
             shape: shape(&world.elements[0]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1, i2];
 
         let state = i1.prepare_state(ray, &is);
 
-        let color = world.refracted_color(&state, 0, &mut vec![]);
+        let color = world.refracted_color(&state, 0, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::black()))
     }
@@ -684,6 +1778,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 2.0f64.sqrt() / 2.0),
             direction: Vector::vector(0.0, 1.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -691,6 +1787,7 @@ mod tests {
             shape: shape(&world.elements[0]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -698,13 +1795,14 @@ mod tests {
             shape: shape(&world.elements[0]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1, i2];
 
         let state = i2.prepare_state(ray, &is);
 
-        let color = world.refracted_color(&state, 5, &mut vec![]);
+        let color = world.refracted_color(&state, 5, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::black()))
     }
@@ -726,6 +1824,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, 0.1),
             direction: Vector::vector(0.0, 1.0, 0.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -733,6 +1833,7 @@ mod tests {
             shape: shape(&world.elements[0]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let i2 = Intersection {
@@ -740,6 +1841,7 @@ mod tests {
             shape: shape(&world.elements[1]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let i3 = Intersection {
@@ -747,6 +1849,7 @@ mod tests {
             shape: shape(&world.elements[1]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let i4 = Intersection {
@@ -754,13 +1857,14 @@ mod tests {
             shape: shape(&world.elements[0]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1, i2, i3, i4];
 
         let state = i3.prepare_state(ray, &is);
 
-        let color = world.refracted_color(&state, 5, &mut vec![]);
+        let color = world.refracted_color(&state, 5, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::new(0.0, 0.998874, 0.047218,)))
     }
@@ -799,6 +1903,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -3.0),
             direction: Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / 2.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -806,15 +1912,133 @@ mod tests {
             shape: shape(&world.elements[2]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1];
 
         let state = i1.prepare_state(ray, &is);
 
-        let color = world.shade_hit(&state, 5, &mut vec![]);
+        let color = world.shade_hit(&state, 5, ALL_LAYERS, &mut vec![]);
+
+        // the refracted red is weighted by 1 - reflectance, unlike the book's 0.93642
+        assert!(color.approx(&Color::new(0.92591, 0.68643, 0.68643)))
+    }
+
+    #[test]
+    fn baked_environment_reflection() {
+        let background = Background::Gradient {
+            top: Color::white(),
+            bottom: Color::new(0.0, 0.0, 1.0),
+        };
+        let environment = World {
+            elements: vec![],
+            background: background.clone(),
+            ..World::default()
+        };
+        let map = Arc::new(ImageTexture::from(
+            environment.bake_environment(Vector::point(0.0, 0.0, 0.0), 32),
+        ));
+
+        let mirror = |reflection_map: Option<Arc<ImageTexture>>| {
+            Element::sphere(ShapeArgs {
+                material: Material {
+                    ambient: 0.0,
+                    diffuse: 0.0,
+                    specular: 0.0,
+                    reflective: 1.0,
+                    reflection_map,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            })
+        };
+        let traced = World {
+            elements: vec![mirror(None)],
+            background,
+            ..World::default()
+        };
+        let baked = World {
+            elements: vec![mirror(Some(map))],
+            background: Background::from(Color::black()),
+            ..World::default()
+        };
+
+        for y in [0.0, 0.5, -0.7] {
+            let ray = Ray {
+                origin: Vector::point(0.0, y, -5.0),
+                direction: Vector::vector(0.0, 0.0, 1.0),
+                differentials: None,
+                time: 0.0,
+            };
+            let exact = traced.color_at(ray, FUEL, &mut vec![]);
+            let approximate = baked.color_at(ray, FUEL, &mut vec![]);
+
+            let difference = exact - approximate;
+            assert!(
+                approximate.b > 0.5
+                    && difference
+                        .r
+                        .abs()
+                        .max(difference.g.abs())
+                        .max(difference.b.abs())
+                        < 0.05
+            )
+        }
+    }
+
+    #[test]
+    fn glass_sphere_fresnel_edge() {
+        // a black disk hides the white background right behind the sphere, so its center
+        // only shows the background by reflection, the grazing reflections at its edge miss the disk
+        let backdrop = Element::disk(
+            ShapeArgs {
+                transform: Matrix::translation(0.0, 0.0, 3.0) * Matrix::rotation_x(PI / 2.0),
+                material: Material {
+                    pattern: Pattern::plain(Color::black()),
+                    specular: 0.0,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            },
+            0.0,
+            1.5,
+        );
+        let glass = Element::sphere(ShapeArgs {
+            material: Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                specular: 0.0,
+                reflective: 1.0,
+                transparency: 1.0,
+                refractive_index: 1.5,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        });
+        let world = World {
+            lights: vec![Light::Point(PointLight {
+                origin: Vector::point(0.0, 0.0, -5.0),
+                intensity: Color::white(),
+            })],
+            elements: vec![backdrop, glass],
+            background: Background::from(Color::white()),
+            ..World::default()
+        };
+
+        let brightness = |y: f64| {
+            let ray = Ray {
+                origin: Vector::point(0.0, y, -5.0),
+                direction: Vector::vector(0.0, 0.0, 1.0),
+                differentials: None,
+                time: 0.0,
+            };
+            let color = world.color_at(ray, FUEL, &mut vec![]);
+
+            color.r + color.g + color.b
+        };
 
-        assert!(color.approx(&Color::new(0.93642, 0.68642, 0.68642,)))
+        assert!(brightness(0.99) > 5.0 * brightness(0.0))
     }
 
     #[test]
@@ -852,6 +2076,8 @@ mod tests {
         let ray = Ray {
             origin: Vector::point(0.0, 0.0, -3.0),
             direction: Vector::vector(0.0, 2.0f64.sqrt() / -2.0, 2.0f64.sqrt() / 2.0),
+            differentials: None,
+            time: 0.0,
         };
 
         let i1 = Intersection {
@@ -859,14 +2085,672 @@ mod tests {
             shape: shape(&world.elements[2]),
             u: None,
             v: None,
+            frame: None,
         };
 
         let is = vec![i1];
 
         let state = i1.prepare_state(ray, &is);
 
-        let color = world.shade_hit(&state, 5, &mut vec![]);
+        let color = world.shade_hit(&state, 5, ALL_LAYERS, &mut vec![]);
 
         assert!(color.approx(&Color::new(0.93391, 0.69643, 0.69243,)))
     }
+
+    #[test]
+    fn closest_surface() {
+        let world = World::default();
+        let (distance, closest) = world.closest_surface(Vector::point(0.0, 3.0, 0.0)).unwrap();
+
+        assert!(distance.approx(&2.0) && *closest == *shape(&world.elements[0]))
+    }
+
+    #[test]
+    fn closest_surface_nan_distance() {
+        let mut world = World::default();
+        world.elements.push(Element::sphere(ShapeArgs {
+            transform: Matrix::translation(f64::NAN, 0.0, 0.0),
+            ..ShapeArgs::default()
+        }));
+        let (distance, closest) = world.closest_surface(Vector::point(0.0, 3.0, 0.0)).unwrap();
+
+        assert!(distance.approx(&2.0) && *closest == *shape(&world.elements[0]))
+    }
+
+    #[test]
+    fn intersect_clip_plane() {
+        let world = World {
+            elements: vec![Element::sphere(ShapeArgs::default())],
+            clip_plane: Some((Vector::vector(0.0, 0.0, -1.0), 0.0)),
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let mut is = vec![];
+        world.intersect(ray, ALL_LAYERS, &mut is);
+
+        assert!(is.len() == 1 && is[0].t.approx(&6.0))
+    }
+
+    #[test]
+    fn shadow_catcher_maximum_recursive_depth() {
+        let world = World {
+            elements: vec![
+                Element::plane(ShapeArgs {
+                    material: Material {
+                        shadow_catcher: true,
+                        ..Material::default()
+                    },
+                    casts_shadow: false,
+                    ..ShapeArgs::default()
+                }),
+                Element::plane(ShapeArgs {
+                    transform: Matrix::translation(0.0, -1.0, 0.0),
+                    material: Material {
+                        ambient: 1.0,
+                        diffuse: 0.0,
+                        specular: 0.0,
+                        ..Material::default()
+                    },
+                    ..ShapeArgs::default()
+                }),
+            ],
+            background: Background::from(Color::new(0.0, 1.0, 0.0)),
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 1.0, 0.0),
+            direction: Vector::vector(0.0, -1.0, 0.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let color = |fuel: i32| world.color_at(ray, fuel, &mut vec![]);
+
+        // without any budget left the backdrop is replaced by the background
+        assert!(color(0).approx(&Color::new(0.0, 1.0, 0.0)) && color(1).approx(&Color::white()))
+    }
+
+    #[test]
+    fn shadow_catcher() {
+        let world = |with_backdrop: bool| {
+            let mut elements = vec![
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(0.0, 2.0, 0.0),
+                    ..ShapeArgs::default()
+                }),
+                Element::plane(ShapeArgs {
+                    material: Material {
+                        shadow_catcher: true,
+                        ..Material::default()
+                    },
+                    casts_shadow: false,
+                    ..ShapeArgs::default()
+                }),
+            ];
+
+            if with_backdrop {
+                elements.push(Element::plane(ShapeArgs {
+                    transform: Matrix::translation(0.0, -1.0, 0.0),
+                    material: Material {
+                        ambient: 1.0,
+                        diffuse: 0.0,
+                        specular: 0.0,
+                        ..Material::default()
+                    },
+                    casts_shadow: false,
+                    ..ShapeArgs::default()
+                }));
+            }
+
+            World {
+                lights: vec![Light::Point(PointLight {
+                    intensity: Color::white(),
+                    origin: Vector::point(0.0, 10.0, 0.0),
+                })],
+                elements,
+                ..World::default()
+            }
+        };
+
+        let shadowed = Ray {
+            origin: Vector::point(0.0, 0.5, -1.0),
+            direction: Vector::vector(0.0, -0.5, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+        let lit = Ray {
+            origin: Vector::point(5.0, 0.5, -1.0),
+            direction: Vector::vector(0.0, -0.5, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let composited = world(true);
+        let matte = world(false);
+
+        assert!(
+            composited
+                .color_at(lit, FUEL, &mut vec![])
+                .approx(&Color::white())
+                && composited
+                    .color_at(shadowed, FUEL, &mut vec![])
+                    .approx(&Color::black())
+                && matte.alpha_at(lit, &mut vec![]).approx(&0.0)
+                && matte.alpha_at(shadowed, &mut vec![]).approx(&1.0)
+        )
+    }
+
+    #[test]
+    fn validate_default_world() {
+        assert_eq!(World::default().validate(), Ok(()))
+    }
+
+    #[test]
+    fn validate() {
+        let mut world = World::default();
+        world.lights.push(Light::Point(PointLight {
+            intensity: Color::white(),
+            origin: Vector::point(f64::NAN, 0.0, 0.0),
+        }));
+        world.elements.push(Element::sphere(ShapeArgs {
+            material: Material {
+                refractive_index: 0.0,
+                ..Material::default()
+            },
+            ..ShapeArgs::default()
+        }));
+        world.elements.push(Element::composite(
+            Matrix::id(),
+            None,
+            GroupKind::Aggregation,
+            vec![],
+        ));
+        world.elements.push(Element::cube(ShapeArgs {
+            transform: Matrix::scaling(f64::NAN, 1.0, 1.0),
+            ..ShapeArgs::default()
+        }));
+
+        assert_eq!(
+            world.validate(),
+            Err(vec![
+                ValidationWarning::NonFiniteLight { light: 1 },
+                ValidationWarning::RefractiveIndexBelowOne {
+                    refractive_index: 0.0
+                },
+                ValidationWarning::EmptyGroup,
+                ValidationWarning::DegenerateTransform,
+            ])
+        )
+    }
+
+    #[test]
+    fn refracted_color_with_dispersion() {
+        let world = |dispersion: f64| World {
+            elements: vec![
+                Element::sphere(ShapeArgs {
+                    material: Material {
+                        ambient: 0.0,
+                        diffuse: 0.0,
+                        specular: 0.0,
+                        transparency: 1.0,
+                        refractive_index: 1.5,
+                        dispersion,
+                        ..Material::default()
+                    },
+                    ..ShapeArgs::default()
+                }),
+                Element::plane(ShapeArgs {
+                    transform: Matrix::translation(0.0, 0.0, 5.0)
+                        * Matrix::rotation_x(std::f64::consts::PI / 2.0),
+                    material: Material {
+                        pattern: Pattern::gradient(
+                            Matrix::translation(-5.0, 0.0, 0.0) * Matrix::scaling(10.0, 1.0, 1.0),
+                            Pattern::plain(Color::black()),
+                            Pattern::plain(Color::white()),
+                        ),
+                        ambient: 1.0,
+                        diffuse: 0.0,
+                        specular: 0.0,
+                        ..Material::default()
+                    },
+                    casts_shadow: false,
+                    ..ShapeArgs::default()
+                }),
+            ],
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.5, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let plain = world(0.0).color_at(ray, FUEL, &mut vec![]);
+        let dispersed = world(0.05).color_at(ray, FUEL, &mut vec![]);
+
+        assert!(plain.r.approx(&plain.b) && (dispersed.r - dispersed.b).abs() > 0.01)
+    }
+
+    #[test_case( 1.0, Color::new(1.0, 0.0, 0.0) ; "from above")]
+    #[test_case(-1.0, Color::new(0.0, 0.0, 1.0) ; "from below")]
+    fn backface(y: f64, expected: Color) {
+        let flat = |color: Color| Material {
+            pattern: Pattern::plain(color),
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        };
+
+        let world = World {
+            elements: vec![Element::plane(ShapeArgs {
+                material: Material {
+                    backface: Some(Box::new(flat(Color::new(0.0, 0.0, 1.0)))),
+                    ..flat(Color::new(1.0, 0.0, 0.0))
+                },
+                ..ShapeArgs::default()
+            })],
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, y, 0.0),
+            direction: Vector::vector(0.0, -y, 0.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        assert!(world.color_at(ray, FUEL, &mut vec![]).approx(&expected))
+    }
+
+    #[test]
+    fn alpha_mask() {
+        let backdrop = Color::new(1.0, 0.0, 0.0);
+        let flat = |color: Color| Material {
+            pattern: Pattern::plain(color),
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        };
+
+        let world = World {
+            lights: vec![Light::Point(PointLight {
+                intensity: Color::white(),
+                origin: Vector::point(0.0, 10.0, 0.0),
+            })],
+            elements: vec![
+                Element::plane(ShapeArgs {
+                    material: Material {
+                        alpha_mask: Some(Pattern::checkers(
+                            Matrix::id(),
+                            Pattern::plain(Color::white()),
+                            Pattern::plain(Color::black()),
+                        )),
+                        ..flat(Color::white())
+                    },
+                    casts_shadow: false,
+                    ..ShapeArgs::default()
+                }),
+                Element::plane(ShapeArgs {
+                    transform: Matrix::translation(0.0, -1.0, 0.0),
+                    material: flat(backdrop),
+                    casts_shadow: false,
+                    ..ShapeArgs::default()
+                }),
+            ],
+            ..World::default()
+        };
+
+        let ray = |x: f64| Ray {
+            origin: Vector::point(x, 1.0, 0.5),
+            direction: Vector::vector(0.0, -1.0, 0.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let mut buf = vec![];
+        let opaque = world.color_at(ray(0.5), FUEL, &mut buf);
+        let masked = world.color_at(ray(1.5), FUEL, &mut buf);
+
+        assert!(opaque.approx(&Color::white()));
+        assert!(masked.approx(&backdrop));
+    }
+
+    #[test]
+    fn caustics() {
+        let world = |caustics: bool| World {
+            lights: vec![Light::Point(PointLight {
+                intensity: Color::white(),
+                origin: Vector::point(0.0, 10.0, 0.0),
+            })],
+            elements: vec![
+                Element::plane(ShapeArgs::default()),
+                Element::sphere(ShapeArgs {
+                    transform: Matrix::translation(0.0, 2.0, 0.0),
+                    material: Material {
+                        transparency: 1.0,
+                        refractive_index: 1.5,
+                        ..Material::default()
+                    },
+                    ..ShapeArgs::default()
+                }),
+            ],
+            settings: Settings {
+                caustics,
+                ..Settings::default()
+            },
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.5, -5.0),
+            direction: Vector::vector(0.0, -0.5, 5.0).normalize(),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let plain = world(false).color_at(ray, FUEL, &mut vec![]);
+        let caustic = world(true).color_at(ray, FUEL, &mut vec![]);
+
+        assert!(caustic.r > plain.r && caustic.g > plain.g && caustic.b > plain.b)
+    }
+
+    #[test_case(Vector::point(0.0, -3.0, 0.0), Vector::point(0.0, 10.0, 0.0), false ; "through the carved hole")]
+    #[test_case(Vector::point(0.8, -3.0, 0.0), Vector::point(0.0, 10.0, 0.0), true  ; "through the remaining solid")]
+    #[test_case(Vector::point(0.0, -3.0, 0.0), Vector::point(3.0, 10.0, 0.0), true  ; "through the carved wall")]
+    fn csg_difference_shadow(point: Vector, origin: Vector, expected: bool) {
+        let cutter = |casts_shadow: bool| {
+            Element::cylinder(
+                ShapeArgs {
+                    transform: Matrix::scaling(0.5, 1.0, 0.5),
+                    casts_shadow,
+                    ..ShapeArgs::default()
+                },
+                -2.0,
+                2.0,
+                true,
+            )
+        };
+
+        let light = Light::Point(PointLight {
+            intensity: Color::white(),
+            origin,
+        });
+
+        for casts_shadow in [true, false] {
+            let world = World {
+                lights: vec![light],
+                elements: vec![Element::composite(
+                    Matrix::id(),
+                    None,
+                    GroupKind::Difference,
+                    vec![Element::cube(ShapeArgs::default()), cutter(casts_shadow)],
+                )],
+                ..World::default()
+            };
+
+            assert_eq!(
+                world.is_shadowed(light.origin(), point, 0.0, None, ALL_LAYERS, &mut vec![]),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn color_at_layer() {
+        let background = Color::new(0.2, 0.4, 0.6);
+        let flat = |color: Color| Material {
+            pattern: Pattern::plain(color),
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            ..Material::default()
+        };
+
+        let world = World {
+            elements: vec![
+                Element::sphere(ShapeArgs {
+                    material: flat(Color::white()),
+                    layer: 0b10,
+                    ..ShapeArgs::default()
+                }),
+                Element::plane(ShapeArgs {
+                    transform: Matrix::translation(0.0, 0.0, 5.0)
+                        * Matrix::rotation_x(std::f64::consts::PI / 2.0),
+                    material: flat(background),
+                    casts_shadow: false,
+                    layer: 0b01,
+                    ..ShapeArgs::default()
+                }),
+            ],
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let mut buf = vec![];
+        let all = world.color_at_layer(ray, FUEL, 0b11, &mut buf);
+        let background_only = world.color_at_layer(ray, FUEL, 0b01, &mut buf);
+        let foreground_only = world.color_at_layer(ray, FUEL, 0b10, &mut buf);
+
+        assert!(all.approx(&Color::white()));
+        assert!(background_only.approx(&background));
+        assert!(foreground_only.approx(&Color::white()));
+    }
+
+    #[test]
+    fn color_at_layer_masked_occluder() {
+        let world = |occluded: bool| {
+            let mut elements = vec![Element::plane(ShapeArgs {
+                transform: Matrix::translation(0.0, 0.0, 5.0)
+                    * Matrix::rotation_x(std::f64::consts::PI / 2.0),
+                layer: 0b01,
+                ..ShapeArgs::default()
+            })];
+            if occluded {
+                elements.push(Element::sphere(ShapeArgs {
+                    layer: 0b10,
+                    ..ShapeArgs::default()
+                }));
+            }
+
+            World {
+                lights: vec![Light::Point(PointLight {
+                    intensity: Color::white(),
+                    origin: Vector::point(0.0, 0.0, -10.0),
+                })],
+                elements,
+                ..World::default()
+            }
+        };
+
+        // passes beside the sphere, but the shadow ray from the plane runs through it
+        let origin = Vector::point(3.0, 0.0, -5.0);
+        let ray = Ray {
+            origin,
+            direction: (Vector::point(0.0, 0.0, 5.0) - origin).normalize(),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let unoccluded = world(false).color_at(ray, FUEL, &mut vec![]);
+        let world = world(true);
+        let mut buf = vec![];
+        let shadowed = world.color_at_layer(ray, FUEL, 0b11, &mut buf);
+        let background_only = world.color_at_layer(ray, FUEL, 0b01, &mut buf);
+
+        assert!(!shadowed.approx(&unoccluded));
+        assert!(background_only.approx(&unoccluded));
+    }
+
+    #[test]
+    fn opacity() {
+        let flat = |color: Color, opacity: f64| Material {
+            pattern: Pattern::plain(color),
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            opacity,
+            ..Material::default()
+        };
+        let wall = |z: f64, material: Material| {
+            Element::plane(ShapeArgs {
+                transform: Matrix::translation(0.0, 0.0, z)
+                    * Matrix::rotation_x(std::f64::consts::PI / 2.0),
+                material,
+                ..ShapeArgs::default()
+            })
+        };
+
+        let world = World {
+            elements: vec![
+                wall(0.0, flat(Color::new(1.0, 0.0, 0.0), 0.5)),
+                wall(5.0, flat(Color::new(0.0, 0.0, 1.0), 1.0)),
+            ],
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        let color = world.color_at(ray, FUEL, &mut vec![]);
+
+        assert!(color.approx(&Color::new(0.5, 0.0, 0.5)))
+    }
+
+    #[test]
+    fn opacity_maximum_recursive_depth() {
+        let red = Material {
+            pattern: Pattern::plain(Color::new(1.0, 0.0, 0.0)),
+            ambient: 1.0,
+            diffuse: 0.0,
+            specular: 0.0,
+            opacity: 0.5,
+            ..Material::default()
+        };
+        let wall = |z: f64| {
+            Element::plane(ShapeArgs {
+                transform: Matrix::translation(0.0, 0.0, z)
+                    * Matrix::rotation_x(std::f64::consts::PI / 2.0),
+                material: red.clone(),
+                ..ShapeArgs::default()
+            })
+        };
+
+        let world = World {
+            elements: (0..10).map(|z| wall(z as f64)).collect(),
+            background: Background::from(Color::new(0.0, 1.0, 0.0)),
+            ..World::default()
+        };
+
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, -5.0),
+            direction: Vector::vector(0.0, 0.0, 1.0),
+            differentials: None,
+            time: 0.0,
+        };
+
+        // the second wall has no budget left and sees the background right behind it
+        let color = world.color_at(ray, 1, &mut vec![]);
+
+        assert!(color.approx(&Color::new(0.75, 0.25, 0.0)))
+    }
+
+    #[test_case(Background::Solid(Color::new(0.2, 0.4, 0.6)), Vector::vector(0.0,  0.0, 1.0), Color::new(0.2, 0.4, 0.6) ; "solid")]
+    #[test_case(Background::Gradient { top: Color::white(), bottom: Color::black() }, Vector::vector(0.0,  1.0, 0.0), Color::white()            ; "gradient top")]
+    #[test_case(Background::Gradient { top: Color::white(), bottom: Color::black() }, Vector::vector(0.0, -1.0, 0.0), Color::black()            ; "gradient bottom")]
+    #[test_case(Background::Gradient { top: Color::white(), bottom: Color::black() }, Vector::vector(0.0,  0.0, 1.0), Color::new(0.5, 0.5, 0.5) ; "gradient horizon")]
+    fn background_miss(background: Background, direction: Vector, expected: Color) {
+        let world = World {
+            elements: vec![],
+            background,
+            ..World::default()
+        };
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, 0.0),
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
+
+        assert!(world.color_at(ray, FUEL, &mut vec![]).approx(&expected))
+    }
+
+    // u runs from -z over +x, +z and -x back to -z, one texel per quarter turn
+    #[test_case(0.0     , Vector::vector(1.0, 0.0,  0.0), Color::new(0.0, 1.0, 0.0) ; "unrotated")]
+    #[test_case(PI / 2.0, Vector::vector(1.0, 0.0,  0.0), Color::new(0.0, 0.0, 1.0) ; "rotated")]
+    #[test_case(PI / 2.0, Vector::vector(0.0, 0.0, -1.0), Color::new(0.0, 1.0, 0.0) ; "rotated back")]
+    fn background_environment_rotation(angle: f64, direction: Vector, expected: Color) {
+        let texture = ImageTexture::new(
+            5,
+            1,
+            vec![
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(0.0, 1.0, 0.0),
+                Color::new(0.0, 0.0, 1.0),
+                Color::white(),
+                Color::new(1.0, 0.0, 0.0),
+            ],
+        );
+        let world = World {
+            elements: vec![],
+            background: Background::environment(Arc::new(texture), Matrix::rotation_y(angle)),
+            ..World::default()
+        };
+        let ray = Ray {
+            origin: Vector::point(0.0, 0.0, 0.0),
+            direction,
+            differentials: None,
+            time: 0.0,
+        };
+
+        assert!(world.color_at(ray, FUEL, &mut vec![]).approx(&expected))
+    }
+
+    #[test]
+    fn background_reflected() {
+        let world = World {
+            elements: vec![Element::plane(ShapeArgs {
+                material: Material {
+                    pattern: Pattern::plain(Color::black()),
+                    reflective: 0.5,
+                    specular: 0.0,
+                    ..Material::default()
+                },
+                ..ShapeArgs::default()
+            })],
+            background: Color::new(0.0, 0.0, 1.0).into(),
+            ..World::default()
+        };
+        let ray = Ray {
+            origin: Vector::point(0.0, 1.0, -1.0),
+            direction: Vector::vector(0.0, -1.0, 1.0).normalize(),
+            differentials: None,
+            time: 0.0,
+        };
+
+        assert!(world
+            .color_at(ray, FUEL, &mut vec![])
+            .approx(&Color::new(0.0, 0.0, 0.5)))
+    }
 }